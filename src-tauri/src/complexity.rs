@@ -0,0 +1,618 @@
+use crate::database::{Database, ProblemRecord};
+use crate::gemini::{parse_json, ChatMessage, ChatOptions, GeminiClient};
+use crate::prompts;
+use crate::runner::{self, SourceCode};
+use crate::usage;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Up to this share of the budget the code should pass comfortably.
+const OK_RATIO: f64 = 1.0;
+/// Beyond this share it is almost certainly too slow; in between it depends on constants.
+const TOO_SLOW_RATIO: f64 = 10.0;
+const COMPLEXITY_PROMPT: &str =
+    "Estimate the time and space complexity of the {language} solution below for the competitive \
+programming problem, assuming the worst-case input allowed by the constraints. \
+Give `operations` as an arithmetic expression of the dominant operation count that uses only the \
+constraint names listed under [제약 조건] (for example `N^2` or `N*log(N) + Q`), without `O(...)`. \
+Give `estimated_operations` as that count evaluated at the maximum input. \
+Write the explanation in Korean and name the part of the code that dominates the running time.";
+
+/// An upper bound from the statement, such as `N ≤ 100000`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Constraint {
+    pub name: String,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Ok,
+    Tight,
+    TooSlow,
+    /// The time limit or the operation count could not be determined.
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ComplexityCheck {
+    pub time_complexity: String,
+    pub space_complexity: String,
+    pub operations: String,
+    pub explanation: String,
+    pub constraints: Vec<Constraint>,
+    pub time_limit_secs: Option<f64>,
+    pub estimated_operations: Option<f64>,
+    /// The model's own count, used when `operations` does not evaluate with the parsed constraints.
+    pub estimated_by_model: bool,
+    pub operations_budget: Option<f64>,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Deserialize)]
+struct ComplexityResponse {
+    time_complexity: String,
+    space_complexity: String,
+    operations: String,
+    estimated_operations: Option<f64>,
+    explanation: String,
+}
+
+fn complexity_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "time_complexity": { "type": "STRING" },
+            "space_complexity": { "type": "STRING" },
+            "operations": { "type": "STRING" },
+            "estimated_operations": { "type": "NUMBER" },
+            "explanation": { "type": "STRING" }
+        },
+        "required": ["time_complexity", "space_complexity", "operations", "explanation"]
+    })
+}
+
+/// Roughly how many simple operations per second a typical judge runs in each language.
+fn operations_per_second(language: &str) -> f64 {
+    match language.to_ascii_lowercase().as_str() {
+        "python" | "python3" | "pypy" => 1e7,
+        "java" | "kotlin" | "javascript" | "typescript" | "swift" => 5e7,
+        _ => 1e8,
+    }
+}
+
+/// Statement HTML as plain text with `<sup>` written as `^` and comparison signs unified.
+fn plain_text(html: &str) -> String {
+    let html = html.replace("<sup>", "^").replace("<sub>", "_");
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = [
+        ("&le;", "≤"),
+        ("&#8804;", "≤"),
+        ("&ge;", "≥"),
+        ("&#8805;", "≥"),
+        ("&lt;", "<"),
+        ("&gt;", ">"),
+        ("&times;", "*"),
+        ("&nbsp;", " "),
+        ("&amp;", "&"),
+        ("<=", "≤"),
+        (">=", "≥"),
+        ("≦", "≤"),
+        ("≧", "≥"),
+        ("×", "*"),
+        ("·", "*"),
+        ("⋅", "*"),
+    ]
+    .iter()
+    .fold(text, |text, (from, to)| text.replace(from, to));
+
+    // Thousands separators, so `100,000` reads as one number.
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .iter()
+        .enumerate()
+        .filter(|&(i, &c)| {
+            !(c == ','
+                && i > 0
+                && chars[i - 1].is_ascii_digit()
+                && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        })
+        .map(|(_, &c)| c)
+        .collect()
+}
+
+fn split_number(text: &str) -> Option<(f64, &str)> {
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let value = text[..end].parse().ok()?;
+    Some((value, &text[end..]))
+}
+
+/// A bound such as `100000`, `10^5` or `2 * 10^5` at the start of `text`.
+fn leading_number(text: &str) -> Option<f64> {
+    let (mut value, mut rest) = split_number(text.trim_start())?;
+    if let Some(exponent) = rest.trim_start().strip_prefix('^') {
+        let (exponent, after) = split_number(exponent.trim_start())?;
+        value = value.powf(exponent);
+        rest = after;
+    }
+    if let Some(factor) = rest.trim_start().strip_prefix('*') {
+        if let Some((base, after)) = split_number(factor.trim_start()) {
+            match after.trim_start().strip_prefix('^') {
+                Some(exponent) => {
+                    value *= base.powf(split_number(exponent.trim_start())?.0);
+                }
+                None => value *= base,
+            }
+        }
+    }
+    Some(value)
+}
+
+fn is_name(word: &str) -> bool {
+    let first = word.chars().next();
+    word.chars().count() <= 6
+        && first.is_some_and(|c| c.is_ascii_alphabetic() || c == '|')
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '|')
+}
+
+/// Names like `N` or `N, M` directly before (or after) a comparison sign.
+fn names(run: &str) -> Vec<String> {
+    run.split(',')
+        .filter_map(|part| part.split_whitespace().last())
+        .filter(|word| is_name(word))
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '|' | ',' | ' ')
+}
+
+fn trailing_names(segment: &str) -> Vec<String> {
+    let start = segment
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_name_char(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    names(&segment[start..])
+}
+
+fn leading_names(segment: &str) -> Vec<String> {
+    let end = segment.find(|c| !is_name_char(c)).unwrap_or(segment.len());
+    names(&segment[..end])
+}
+
+#[derive(Clone)]
+enum Bound {
+    Value(f64),
+    Name(String),
+}
+
+fn bound(text: &str) -> Option<Bound> {
+    leading_number(text).map(Bound::Value).or_else(|| {
+        let word = text.split_whitespace().next()?;
+        let word = word.trim_end_matches(|c: char| !(c.is_ascii_alphanumeric() || c == '|'));
+        is_name(word).then(|| Bound::Name(word.to_string()))
+    })
+}
+
+/// The bounds stated in a problem's input description and statement.
+pub fn problem_constraints(problem: &ProblemRecord) -> Vec<Constraint> {
+    parse_constraints(&format!(
+        "{}\n{}",
+        problem.input_description, problem.description
+    ))
+}
+
+/// The largest value each variable may take, from chains like `1 ≤ N, M ≤ 10^5`,
+/// `10^9 ≥ A_i` or `1 ≤ K ≤ N`, in order of first appearance.
+fn parse_constraints(html: &str) -> Vec<Constraint> {
+    let text = plain_text(html);
+    let mut segments = Vec::new();
+    let mut signs = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if matches!(c, '≤' | '<' | '≥' | '>') {
+            segments.push(&text[start..i]);
+            signs.push(c);
+            start = i + c.len_utf8();
+        }
+    }
+    segments.push(&text[start..]);
+
+    let mut bounds: Vec<(String, Bound)> = Vec::new();
+    let mut bounded_before = false;
+    for (k, sign) in signs.iter().enumerate() {
+        let (mut left, right) = (segments[k], segments[k + 1]);
+        if matches!(sign, '≤' | '<') {
+            // In `K ≤ N, Q ≤ 10`, the `N` was the previous bound and only `Q` is bounded here.
+            if bounded_before {
+                let rest = left.trim_start();
+                left = &rest[rest.find([' ', ',']).unwrap_or(rest.len())..];
+            }
+            let names = trailing_names(left);
+            bounded_before = !names.is_empty();
+            let Some(limit) = bound(right) else { continue };
+            for name in names {
+                bounds.push((name, limit.clone()));
+            }
+        } else {
+            bounded_before = false;
+            let number_start = left
+                .char_indices()
+                .rev()
+                .find(|&(_, c)| !(c.is_ascii_digit() || matches!(c, '.' | '^' | '*' | ' ')))
+                .map_or(0, |(i, c)| i + c.len_utf8());
+            let Some(limit) = leading_number(&left[number_start..]) else {
+                continue;
+            };
+            for name in leading_names(right) {
+                bounds.push((name, Bound::Value(limit)));
+            }
+        }
+    }
+
+    let mut constraints: Vec<Constraint> = Vec::new();
+    // Bounds by another variable (`K ≤ N`) resolve once that variable is known.
+    for _ in 0..2 {
+        for (name, limit) in &bounds {
+            let max = match limit {
+                Bound::Value(value) => *value,
+                Bound::Name(other) => match constraints.iter().find(|c| &c.name == other) {
+                    Some(c) => c.max,
+                    None => continue,
+                },
+            };
+            match constraints.iter_mut().find(|c| &c.name == name) {
+                Some(existing) => existing.max = existing.max.max(max),
+                None => constraints.push(Constraint {
+                    name: name.clone(),
+                    max,
+                }),
+            }
+        }
+    }
+    constraints
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit() || **d == '.') {
+                number.push(d);
+                chars.next();
+            }
+            let value = number
+                .parse()
+                .map_err(|_| format!("Invalid number: {}", number))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '|' {
+            let mut name = String::new();
+            if c == '|' {
+                chars.next();
+                name.push('|');
+                for d in chars.by_ref() {
+                    name.push(d);
+                    if d == '|' {
+                        break;
+                    }
+                }
+            } else {
+                while let Some(&d) = chars
+                    .peek()
+                    .filter(|d| d.is_ascii_alphanumeric() || **d == '_')
+                {
+                    name.push(d);
+                    chars.next();
+                }
+            }
+            tokens.push(Token::Name(name));
+        } else if "+-*/^()".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else if matches!(c, '×' | '·' | '⋅') {
+            tokens.push(Token::Op('*'));
+            chars.next();
+        } else {
+            return Err(format!("Unexpected character in expression: {}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Evaluates an operation count like `N*log(N) + Q` with every variable at its bound.
+struct Evaluator<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    constraints: &'a [Constraint],
+}
+
+impl Evaluator<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                value /= self.unary()?;
+            } else if matches!(
+                self.peek(),
+                Some(Token::Number(_) | Token::Name(_) | Token::Op('('))
+            ) {
+                // `N log N`
+                value *= self.unary()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+        let base = self.primary()?;
+        if self.eat('^') {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| "Unexpected end of expression".to_string())?;
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(value),
+            Token::Op('(') => {
+                let value = self.sum()?;
+                if !self.eat(')') {
+                    return Err("Missing )".to_string());
+                }
+                Ok(value)
+            }
+            Token::Name(name) => {
+                let function: Option<fn(f64) -> f64> = match name.to_ascii_lowercase().as_str() {
+                    "log" | "log2" | "lg" => Some(|x| x.max(1.0).log2()),
+                    "ln" => Some(|x| x.max(1.0).ln()),
+                    "log10" => Some(|x| x.max(1.0).log10()),
+                    "sqrt" => Some(|x| x.max(0.0).sqrt()),
+                    _ => None,
+                };
+                match function {
+                    Some(function) => Ok(function(self.unary()?)),
+                    None => self.variable(&name),
+                }
+            }
+            Token::Op(op) => Err(format!("Unexpected {} in expression", op)),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<f64, String> {
+        let exact = self.constraints.iter().find(|c| c.name == name);
+        let loose = || {
+            let mut matches = self
+                .constraints
+                .iter()
+                .filter(|c| c.name.eq_ignore_ascii_case(name));
+            matches.next().filter(|_| matches.next().is_none())
+        };
+        exact
+            .or_else(loose)
+            .map(|c| c.max)
+            .ok_or_else(|| format!("Unknown variable: {}", name))
+    }
+}
+
+fn evaluate(expression: &str, constraints: &[Constraint]) -> Result<f64, String> {
+    let trimmed = expression.trim();
+    let inner = trimmed
+        .strip_prefix("O(")
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or(trimmed);
+    let mut evaluator = Evaluator {
+        tokens: tokenize(inner)?,
+        position: 0,
+        constraints,
+    };
+    let value = evaluator.sum()?;
+    if evaluator.position != evaluator.tokens.len() {
+        return Err(format!("Could not evaluate expression: {}", expression));
+    }
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("Expression has no sensible value: {}", expression));
+    }
+    Ok(value)
+}
+
+fn verdict(operations: Option<f64>, budget: Option<f64>) -> Verdict {
+    match (operations, budget) {
+        (Some(operations), Some(budget)) if budget > 0.0 => {
+            let ratio = operations / budget;
+            if ratio <= OK_RATIO {
+                Verdict::Ok
+            } else if ratio <= TOO_SLOW_RATIO {
+                Verdict::Tight
+            } else {
+                Verdict::TooSlow
+            }
+        }
+        _ => Verdict::Unknown,
+    }
+}
+
+/// Asks the model how the code scales and checks the resulting operation count
+/// against the time limit before the code is run or submitted.
+pub async fn check(
+    client: &GeminiClient,
+    db: &Database,
+    problem_id: &str,
+    model: &str,
+    source: &SourceCode,
+    options: ChatOptions,
+) -> Result<ComplexityCheck, String> {
+    let problem = db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Problem not found: {}", problem_id))?;
+    if source.code.trim().is_empty() {
+        return Err("No code to check".to_string());
+    }
+
+    let constraints = problem_constraints(&problem);
+    let listed = if constraints.is_empty() {
+        "(찾지 못함: 문제 설명에서 직접 읽어 주세요)".to_string()
+    } else {
+        constraints
+            .iter()
+            .map(|c| format!("{} ≤ {}", c.name, c.max))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let messages = vec![ChatMessage::user(format!(
+        "{}\n\n{}\n\n[제약 조건]\n{}\n\n[코드]\n{}",
+        COMPLEXITY_PROMPT.replace("{language}", &source.language),
+        prompts::problem_context(&problem),
+        listed,
+        source.code
+    ))];
+    let options = ChatOptions {
+        max_continuations: Some(0),
+        response_schema: Some(complexity_schema()),
+        ..options
+    };
+
+    let started = Instant::now();
+    let result = client.chat(model, messages, "", &options, None).await;
+    usage::record(db, Some(problem_id), "complexity", model, started, &result);
+    let response = result?;
+    let parsed: ComplexityResponse = parse_json(&response.text)?;
+
+    let evaluated = evaluate(&parsed.operations, &constraints).ok();
+    let estimated_by_model = evaluated.is_none() && parsed.estimated_operations.is_some();
+    let estimated_operations = evaluated.or(parsed.estimated_operations);
+    let time_limit_secs =
+        runner::parse_time_limit(&problem.time_limit).map(|limit| limit.as_secs_f64());
+    let operations_budget =
+        time_limit_secs.map(|secs| secs * operations_per_second(&source.language));
+
+    Ok(ComplexityCheck {
+        time_complexity: parsed.time_complexity,
+        space_complexity: parsed.space_complexity,
+        operations: parsed.operations,
+        explanation: parsed.explanation,
+        constraints,
+        time_limit_secs,
+        estimated_operations,
+        estimated_by_model,
+        operations_budget,
+        verdict: verdict(estimated_operations, operations_budget),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraint(name: &str, max: f64) -> Constraint {
+        Constraint {
+            name: name.to_string(),
+            max,
+        }
+    }
+
+    #[test]
+    fn parses_constraints_from_statement_html() {
+        let html = "<p>첫째 줄에 N과 M이 주어진다. (1 &le; N, M &le; 100,000)</p>\
+            <p>둘째 줄에 A<sub>i</sub>가 주어진다. (-10<sup>9</sup> &le; A<sub>i</sub> &le; 10<sup>9</sup>)</p>\
+            <p>1 ≤ K ≤ N, Q ≤ 2 × 10<sup>5</sup>, 500 &ge; T</p>";
+        assert_eq!(
+            parse_constraints(html),
+            [
+                constraint("N", 1e5),
+                constraint("M", 1e5),
+                constraint("A_i", 1e9),
+                constraint("K", 1e5),
+                constraint("Q", 2e5),
+                constraint("T", 500.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluates_operation_counts_and_compares_with_the_budget() {
+        let constraints = [constraint("N", 1e5), constraint("Q", 1e3)];
+        assert_eq!(evaluate("N^2", &constraints).unwrap(), 1e10);
+        assert_eq!(evaluate("O(n + Q)", &constraints).unwrap(), 101_000.0);
+        let n_log_n = evaluate("N log(N) + 2*Q", &constraints).unwrap();
+        assert!((n_log_n - (1e5 * 1e5f64.log2() + 2e3)).abs() < 1e-6);
+        assert!(evaluate("N * M", &constraints).is_err());
+        assert!(evaluate("N +", &constraints).is_err());
+
+        let budget = runner::parse_time_limit("1 초")
+            .map(|limit| limit.as_secs_f64() * operations_per_second("cpp"));
+        assert_eq!(verdict(Some(1e10), budget), Verdict::TooSlow);
+        assert_eq!(verdict(Some(5e8), budget), Verdict::Tight);
+        assert_eq!(verdict(Some(n_log_n), budget), Verdict::Ok);
+        assert_eq!(verdict(Some(1e10), None), Verdict::Unknown);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::database::{ChatRecord, Database};
+use crate::usage;
+use crate::gemini::{estimate_tokens, ChatMessage, ChatOptions, GeminiClient, GenerationSettings};
+use std::time::Instant;
+
+const DEFAULT_CONTEXT_BUDGET: u32 = 64_000;
+const MIN_RECENT_MESSAGES: usize = 2;
+const SUMMARY_PROMPT: &str = "Summarize the following part of a tutoring conversation about a competitive programming problem. \
+Keep every fact needed to continue helping: the approaches discussed, hints already given, bugs found, \
+test cases mentioned and any decisions made. Write it in the same language as the conversation. \
+Reply with the summary only.";
+
+/// Keeps a conversation within the context budget by replacing its older turns with
+/// a summary. The problem statement is not part of `messages`; the client sends it
+/// separately (see `GeminiClient::with_problem_context`), so every turn here may be
+/// summarized.
+pub async fn fit_to_budget(
+    client: &GeminiClient,
+    db: &Database,
+    thread: Option<&ChatRecord>,
+    model: &str,
+    messages: Vec<ChatMessage>,
+    system_prompt: &str,
+    options: &ChatOptions,
+) -> Result<Vec<ChatMessage>, String> {
+    let budget = options.context_budget.unwrap_or(DEFAULT_CONTEXT_BUDGET);
+    if messages.len() <= MIN_RECENT_MESSAGES {
+        return Ok(messages);
+    }
+
+    if client.estimate_request_tokens(&messages, system_prompt) < budget / 2 {
+        return Ok(messages);
+    }
+
+    let count = client.count_tokens(model, &messages, system_prompt).await;
+    if count.tokens <= budget {
+        return Ok(messages);
+    }
+
+    let Some(plan) = plan(&messages, budget / 2, thread) else {
+        return Ok(messages);
+    };
+
+    let summary = match plan.stored_summary {
+        Some(summary) if plan.summarize_from == plan.tail_start => summary,
+        previous_summary => {
+            let summary = summarize(
+                client,
+                db,
+                thread.map(|chat| chat.problem_id.as_str()),
+                model,
+                previous_summary.as_deref(),
+                &messages[plan.summarize_from..plan.tail_start],
+                options,
+            )
+            .await?;
+            if let Some(chat) = thread {
+                db.save_chat_summary(chat.id, &summary, plan.tail_start as i64)
+                    .map_err(|e| e.to_string())?;
+            }
+            summary
+        }
+    };
+
+    Ok(compact(messages, plan.tail_start, &summary))
+}
+
+/// Where to cut a conversation that no longer fits: everything before `tail_start` is
+/// replaced by a summary.
+#[derive(Debug, PartialEq)]
+struct Plan {
+    tail_start: usize,
+    /// The stored summary covers the messages before this index; the ones from here
+    /// up to `tail_start` still need summarizing.
+    summarize_from: usize,
+    stored_summary: Option<String>,
+}
+
+fn plan(messages: &[ChatMessage], tail_budget: u32, thread: Option<&ChatRecord>) -> Option<Plan> {
+    let stored = thread
+        .and_then(|chat| Some((chat.summary.clone()?, chat.summarized_count as usize)))
+        .filter(|(_, count)| count + MIN_RECENT_MESSAGES <= messages.len());
+    let (stored_summary, summarize_from) = match stored {
+        Some((summary, count)) => (Some(summary), count),
+        None => (None, 0),
+    };
+
+    let tail_start = recent_start(messages, tail_budget).max(summarize_from);
+    (tail_start > 0).then_some(Plan {
+        tail_start,
+        summarize_from,
+        stored_summary,
+    })
+}
+
+fn recent_start(messages: &[ChatMessage], tail_budget: u32) -> usize {
+    let mut used = 0;
+    let mut start = messages.len();
+
+    while start > 0 {
+        let cost = estimate_tokens(&messages[start - 1].content);
+        let keep_anyway = messages.len() - start < MIN_RECENT_MESSAGES;
+        if !keep_anyway && used + cost > tail_budget {
+            break;
+        }
+        used += cost;
+        start -= 1;
+    }
+
+    start
+}
+
+fn latest_code(messages: &[ChatMessage]) -> Option<&ChatMessage> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user" && m.content.contains("```"))
+}
+
+/// The recent turns, led by the summary and the latest code when that code would
+/// otherwise be cut.
+fn compact(messages: Vec<ChatMessage>, tail_start: usize, summary: &str) -> Vec<ChatMessage> {
+    let code = latest_code(&messages[..tail_start])
+        .filter(|_| latest_code(&messages[tail_start..]).is_none())
+        .map(|m| m.content.clone());
+
+    let mut note = format!("[Summary of the earlier conversation]\n{}", summary);
+    if let Some(code) = code {
+        note = format!("{}\n\n[Latest code]\n{}", note, code);
+    }
+
+    let mut tail: Vec<ChatMessage> = messages.into_iter().skip(tail_start).collect();
+    match tail.first_mut() {
+        Some(first) if first.role == "user" => {
+            first.content = format!("{}\n\n{}", note, first.content);
+        }
+        _ => tail.insert(0, ChatMessage::user(note)),
+    }
+    tail
+}
+
+async fn summarize(
+    client: &GeminiClient,
+    db: &Database,
+    problem_id: Option<&str>,
+    model: &str,
+    previous_summary: Option<&str>,
+    messages: &[ChatMessage],
+    options: &ChatOptions,
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    if let Some(summary) = previous_summary {
+        transcript.push_str(&format!("[Earlier summary]\n{}\n\n", summary));
+    }
+    for message in messages {
+        transcript.push_str(&format!("[{}]\n{}\n\n", message.role, message.content));
+    }
+
+    let request = vec![ChatMessage::user(format!("{}\n\n{}", SUMMARY_PROMPT, transcript))];
+    let summary_options = ChatOptions {
+        max_continuations: Some(0),
+        generation: Some(GenerationSettings {
+            temperature: Some(0.2),
+            ..GenerationSettings::default()
+        }),
+        max_retries: options.max_retries,
+        fallback_models: options.fallback_models.clone(),
+        ..ChatOptions::default()
+    };
+
+    let started = Instant::now();
+    let result = client
+        .chat(model, request, "", &summary_options, None)
+        .await;
+    usage::record(db, problem_id, "summary", model, started, &result);
+
+    result
+        .map(|response| response.text)
+        .map_err(|e| format!("Failed to summarize chat history: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            tool_calls: vec![],
+        }
+    }
+
+    fn conversation() -> Vec<ChatMessage> {
+        vec![
+            message("user", "어떻게 풀어야 하나요?"),
+            message("assistant", &"설명 ".repeat(400)),
+            message("user", "이 코드가 틀려요\n```\nprint(1)\n```"),
+            message("assistant", &"분석 ".repeat(400)),
+            message("user", "고마워요"),
+            message("assistant", "천만에요"),
+        ]
+    }
+
+    fn thread(summary: Option<&str>, summarized_count: i64) -> ChatRecord {
+        ChatRecord {
+            id: 1,
+            problem_id: "1000".to_string(),
+            title: String::new(),
+            active_message_id: None,
+            summary: summary.map(str::to_string),
+            summarized_count,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn cuts_at_the_budget_but_keeps_the_latest_turns() {
+        let messages = conversation();
+        assert_eq!(recent_start(&messages, 50), 4);
+        assert_eq!(recent_start(&messages, 0), 4);
+        assert_eq!(recent_start(&messages, 100_000), 0);
+
+        let plan = plan(&messages, 50, None).unwrap();
+        assert_eq!(
+            plan,
+            Plan {
+                tail_start: 4,
+                summarize_from: 0,
+                stored_summary: None,
+            }
+        );
+        assert!(super::plan(&messages, 100_000, None).is_none());
+    }
+
+    #[test]
+    fn keeps_the_latest_code_that_would_be_summarized() {
+        let compacted = compact(conversation(), 4, "요약");
+        assert_eq!(compacted.len(), 2);
+        assert_eq!(compacted[0].role, "user");
+        assert!(compacted[0].content.starts_with("[Summary of the earlier conversation]\n요약"));
+        assert!(compacted[0].content.contains("[Latest code]\n이 코드가 틀려요"));
+        assert!(compacted[0].content.ends_with("고마워요"));
+
+        // A tail starting with a reply gets its own leading turn.
+        let compacted = compact(conversation(), 3, "요약");
+        assert_eq!(compacted.len(), 4);
+        assert!(compacted[0].content.contains("[Latest code]"));
+        assert_eq!(compacted[1].role, "assistant");
+
+        // Code still in the tail is not repeated.
+        let compacted = compact(conversation(), 2, "요약");
+        assert_eq!(compacted.len(), 4);
+        assert!(!compacted[0].content.contains("[Latest code]"));
+        assert!(compacted[0].content.ends_with("```"));
+    }
+
+    #[test]
+    fn reuses_the_stored_summary() {
+        let messages = conversation();
+        let stored = thread(Some("이전 요약"), 4);
+        let plan = plan(&messages, 50, Some(&stored)).unwrap();
+        assert_eq!(plan.tail_start, 4);
+        assert_eq!(plan.summarize_from, 4);
+        assert_eq!(plan.stored_summary.as_deref(), Some("이전 요약"));
+
+        // Only the turns after the stored summary are summarized again.
+        let older = thread(Some("이전 요약"), 2);
+        let plan = super::plan(&messages, 50, Some(&older)).unwrap();
+        assert_eq!((plan.summarize_from, plan.tail_start), (2, 4));
+
+        // A summary covering more than the conversation (an edited branch) is ignored.
+        let stale = thread(Some("이전 요약"), 5);
+        assert_eq!(super::plan(&messages, 50, Some(&stale)).unwrap().summarize_from, 0);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::response_cache;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+/// Gemini rejects cached contents below roughly this many tokens, so smaller
+/// prompts are sent inline instead of failing a create request every turn.
+pub const MIN_CACHE_TOKENS: u32 = 1024;
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+const RETRY_UNAVAILABLE_AFTER: Duration = Duration::from_secs(30 * 60);
+/// Every problem and prompt gets its own cache, so switching between them would
+/// leave old caches billed until they expire; past this many the oldest is deleted.
+const MAX_LIVE_CACHES: usize = 4;
+
+/// A `cachedContents` entry holding a system prompt and problem statement. It lives in
+/// the project of the key that created it, so requests using it must send that key.
+#[derive(Debug, Clone)]
+pub struct CachedContext {
+    pub name: String,
+    pub key_label: String,
+    expires_at: Instant,
+}
+
+enum Entry {
+    Ready(CachedContext),
+    Unavailable(Instant),
+}
+
+pub enum Lookup {
+    Ready(CachedContext),
+    /// Creating a cache failed recently (unsupported model, prompt too small, ...).
+    Unavailable,
+    Missing,
+}
+
+/// Context caches created during this run, keyed by model, system prompt and problem
+/// so every turn of a session reuses the same entry until it is about to expire.
+/// Entries that are dropped while still alive on the server are kept in `retired`
+/// until the client deletes them.
+#[derive(Clone, Default)]
+pub struct ContextCaches {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    retired: Arc<Mutex<Vec<CachedContext>>>,
+}
+
+impl ContextCaches {
+    pub fn key(model: &str, system_prompt: &str, problem_context: &str) -> String {
+        response_cache::key(&["context", model, system_prompt, problem_context])
+    }
+
+    pub fn lookup(&self, key: &str) -> Lookup {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(Entry::Ready(context)) if context.expires_at > now + EXPIRY_MARGIN => {
+                Lookup::Ready(context.clone())
+            }
+            Some(Entry::Unavailable(until)) if *until > now => Lookup::Unavailable,
+            Some(_) => {
+                self.retire(entries.remove(key));
+                Lookup::Missing
+            }
+            None => Lookup::Missing,
+        }
+    }
+
+    pub fn insert(
+        &self,
+        key: &str,
+        name: String,
+        key_label: String,
+        ttl: Duration,
+    ) -> CachedContext {
+        let context = CachedContext {
+            name,
+            key_label,
+            expires_at: Instant::now() + ttl,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        self.retire(entries.insert(key.to_string(), Entry::Ready(context.clone())));
+
+        let mut live: Vec<(Instant, String)> = entries
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                Entry::Ready(context) => Some((context.expires_at, key.clone())),
+                Entry::Unavailable(_) => None,
+            })
+            .collect();
+        live.sort();
+        for (_, oldest) in live.iter().rev().skip(MAX_LIVE_CACHES) {
+            self.retire(entries.remove(oldest));
+        }
+        context
+    }
+
+    pub fn mark_unavailable(&self, key: &str) {
+        let replaced = self.entries.lock().unwrap().insert(
+            key.to_string(),
+            Entry::Unavailable(Instant::now() + RETRY_UNAVAILABLE_AFTER),
+        );
+        self.retire(replaced);
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        let removed = self.entries.lock().unwrap().remove(key);
+        self.retire(removed);
+    }
+
+    /// Caches that are no longer used but may still exist on the server.
+    pub fn take_retired(&self) -> Vec<CachedContext> {
+        std::mem::take(&mut *self.retired.lock().unwrap())
+    }
+
+    fn retire(&self, entry: Option<Entry>) {
+        if let Some(Entry::Ready(context)) = entry {
+            if context.expires_at > Instant::now() {
+                self.retired.lock().unwrap().push(context);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(contexts: Vec<CachedContext>) -> Vec<String> {
+        contexts.into_iter().map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn retires_replaced_invalidated_and_oldest_caches() {
+        let caches = ContextCaches::default();
+        for i in 0..MAX_LIVE_CACHES {
+            let ttl = CACHE_TTL + Duration::from_secs(i as u64);
+            caches.insert(
+                &i.to_string(),
+                format!("cachedContents/{}", i),
+                "key".into(),
+                ttl,
+            );
+        }
+        assert!(caches.take_retired().is_empty());
+
+        caches.insert(
+            "new",
+            "cachedContents/new".into(),
+            "key".into(),
+            CACHE_TTL * 2,
+        );
+        assert_eq!(names(caches.take_retired()), ["cachedContents/0"]);
+        assert!(matches!(caches.lookup("0"), Lookup::Missing));
+
+        caches.invalidate("1");
+        caches.mark_unavailable("2");
+        caches.insert("3", "cachedContents/3b".into(), "key".into(), CACHE_TTL);
+        assert_eq!(
+            names(caches.take_retired()),
+            ["cachedContents/1", "cachedContents/2", "cachedContents/3"]
+        );
+        assert!(matches!(caches.lookup("2"), Lookup::Unavailable));
+        assert!(matches!(caches.lookup("3"), Lookup::Ready(_)));
+
+        // Entries near expiry are recreated, and the old one retired.
+        caches.insert("4", "cachedContents/4".into(), "key".into(), EXPIRY_MARGIN);
+        assert!(matches!(caches.lookup("4"), Lookup::Missing));
+        assert_eq!(names(caches.take_retired()), ["cachedContents/4"]);
+    }
+}
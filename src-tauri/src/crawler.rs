@@ -19,14 +19,9 @@ pub struct Sample {
     pub output: String,
 }
 
-pub async fn fetch_problem(problem_id: &str) -> Result<Problem, String> {
+pub async fn fetch_problem(client: &reqwest::Client, problem_id: &str) -> Result<Problem, String> {
     let url = format!("https://www.acmicpc.net/problem/{}", problem_id);
     
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36")
-        .build()
-        .map_err(|e| e.to_string())?;
-    
     let response = client
         .get(&url)
         .send()
@@ -88,7 +83,7 @@ fn parse_problem(problem_id: &str, html: &str) -> Result<Problem, String> {
         .map(|e| e.text().collect::<String>().trim().to_string())
         .collect();
     
-    let time_limit = limits.get(0).cloned().unwrap_or_default();
+    let time_limit = limits.first().cloned().unwrap_or_default();
     let memory_limit = limits.get(1).cloned().unwrap_or_default();
     
     let sample_inputs: Vec<String> = document
@@ -103,7 +98,7 @@ fn parse_problem(problem_id: &str, html: &str) -> Result<Problem, String> {
     
     let samples: Vec<Sample> = sample_inputs
         .into_iter()
-        .zip(sample_outputs.into_iter())
+        .zip(sample_outputs)
         .map(|(input, output)| Sample { input, output })
         .collect();
     
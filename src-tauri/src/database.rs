@@ -1,8 +1,21 @@
-use rusqlite::{Connection, Result as SqliteResult};
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Mutex;
 
+const DEFAULT_THREAD_TITLE: &str = "기본 대화";
+const MESSAGE_COLUMNS: &str = "m.id, m.thread_id, m.parent_id, m.role, m.content, m.model, m.tokens,
+    m.tool_calls_json, m.thoughts,
+    (SELECT COUNT(*) FROM messages s
+     WHERE s.thread_id = m.thread_id AND s.parent_id IS m.parent_id AND s.id < m.id),
+    (SELECT COUNT(*) FROM messages s
+     WHERE s.thread_id = m.thread_id AND s.parent_id IS m.parent_id),
+    m.created_at";
+
+const NOTE_SELECT: &str = "n.id, n.problem_id, COALESCE(p.title, ''), n.key_idea, n.algorithm,
+    n.complexity, n.pitfalls_json, n.code_version_id, n.model, n.edited, n.created_at, n.updated_at
+    FROM notes n LEFT JOIN problems p ON p.problem_id = n.problem_id";
+
 pub struct Database {
     pub conn: Mutex<Connection>,
 }
@@ -25,18 +38,219 @@ pub struct ProblemRecord {
 pub struct ChatRecord {
     pub id: i64,
     pub problem_id: String,
-    pub messages_json: String,
+    pub title: String,
+    pub active_message_id: Option<i64>,
+    pub summary: Option<String>,
+    pub summarized_count: i64,
     pub created_at: String,
     pub updated_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageRecord {
+    pub id: i64,
+    pub thread_id: i64,
+    pub parent_id: Option<i64>,
+    pub role: String,
+    pub content: String,
+    pub model: Option<String>,
+    pub tokens: Option<i64>,
+    pub tool_calls_json: String,
+    pub thoughts: String,
+    pub sibling_index: i64,
+    pub sibling_count: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewMessage<'a> {
+    pub thread_id: i64,
+    pub parent_id: Option<i64>,
+    pub role: &'a str,
+    pub content: &'a str,
+    pub model: Option<&'a str>,
+    pub tokens: Option<i64>,
+    pub tool_calls_json: &'a str,
+    pub thoughts: &'a str,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SolveRecord {
     pub id: i64,
     pub problem_id: String,
+    pub hints_used: i64,
     pub solved_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HintRecord {
+    pub id: i64,
+    pub problem_id: String,
+    pub level: i64,
+    pub content: String,
+    pub model: String,
+    pub revealed_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptTemplateRecord {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub body: String,
+    pub builtin: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GenerationPresetRecord {
+    pub id: i64,
+    pub model: String,
+    pub name: String,
+    pub settings_json: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TestCaseRecord {
+    pub id: i64,
+    pub problem_id: String,
+    pub input: String,
+    pub expected_output: String,
+    pub rationale: String,
+    pub source: String,
+    pub verified: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTestCase<'a> {
+    pub problem_id: &'a str,
+    pub input: &'a str,
+    pub expected_output: &'a str,
+    pub rationale: &'a str,
+    pub source: &'a str,
+    pub verified: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CodeVersionRecord {
+    pub id: i64,
+    pub problem_id: String,
+    pub language: String,
+    pub code: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CodeReviewRecord {
+    pub id: i64,
+    pub code_version_id: i64,
+    pub model: String,
+    pub summary: String,
+    pub findings_json: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteRecord {
+    pub id: i64,
+    pub problem_id: String,
+    pub title: String,
+    pub key_idea: String,
+    pub algorithm: String,
+    pub complexity: String,
+    pub pitfalls_json: String,
+    pub code_version_id: Option<i64>,
+    pub model: String,
+    pub edited: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteContent<'a> {
+    pub key_idea: &'a str,
+    pub algorithm: &'a str,
+    pub complexity: &'a str,
+    pub pitfalls_json: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct AiUsageEntry<'a> {
+    pub problem_id: Option<&'a str>,
+    pub model: &'a str,
+    pub api_key_label: Option<&'a str>,
+    pub kind: &'a str,
+    pub prompt_tokens: u32,
+    pub output_tokens: u32,
+    pub thinking_tokens: u32,
+    pub latency_ms: i64,
+    pub error: Option<&'a str>,
+}
+
+/// What usage is totalled by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroup {
+    Day,
+    Model,
+    Problem,
+    ApiKey,
+}
+
+impl UsageGroup {
+    pub fn parse(group_by: &str) -> Result<Self, String> {
+        match group_by {
+            "day" => Ok(UsageGroup::Day),
+            "model" => Ok(UsageGroup::Model),
+            "problem" => Ok(UsageGroup::Problem),
+            "api_key" => Ok(UsageGroup::ApiKey),
+            other => Err(format!("Unknown usage grouping: {}", other)),
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            UsageGroup::Day => "date(u.created_at)",
+            UsageGroup::Model => "u.model",
+            UsageGroup::Problem => "COALESCE(u.problem_id, '')",
+            UsageGroup::ApiKey => "COALESCE(u.api_key_label, '')",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageAggregate {
+    pub key: String,
+    pub calls: i64,
+    pub errors: i64,
+    pub prompt_tokens: i64,
+    pub output_tokens: i64,
+    pub thinking_tokens: i64,
+    pub avg_latency_ms: f64,
+    pub estimated_cost: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModelPrice {
+    pub model: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub updated_at: String,
+}
+
+/// An embedding of a problem statement. `content_hash` tells whether the statement
+/// changed since it was embedded.
+#[derive(Debug, Clone)]
+pub struct ProblemEmbedding {
+    pub problem_id: String,
+    pub content_hash: String,
+    pub vector: Vec<f32>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ActivityData {
     pub date: String,
@@ -59,7 +273,7 @@ impl Database {
     }
 
     fn init_tables(&self) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
         
         conn.execute_batch(
             "
@@ -78,23 +292,181 @@ impl Database {
             
             CREATE TABLE IF NOT EXISTS chats (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
-                problem_id TEXT NOT NULL UNIQUE,
-                messages_json TEXT NOT NULL,
+                problem_id TEXT NOT NULL,
+                title TEXT NOT NULL,
+                messages_json TEXT NOT NULL DEFAULT '[]',
+                active_message_id INTEGER,
+                summary TEXT,
+                summarized_count INTEGER NOT NULL DEFAULT 0,
                 created_at TEXT NOT NULL DEFAULT (datetime('now')),
                 updated_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
             
+            CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                thread_id INTEGER NOT NULL,
+                parent_id INTEGER,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                model TEXT,
+                tokens INTEGER,
+                tool_calls_json TEXT NOT NULL DEFAULT '[]',
+                thoughts TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
             CREATE TABLE IF NOT EXISTS solve_records (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 problem_id TEXT NOT NULL,
                 solved_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
             
+            CREATE TABLE IF NOT EXISTS generation_presets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                model TEXT NOT NULL,
+                name TEXT NOT NULL,
+                settings_json TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(model, name)
+            );
+            
+            CREATE TABLE IF NOT EXISTS ai_usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                problem_id TEXT,
+                model TEXT NOT NULL,
+                api_key_label TEXT,
+                kind TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL DEFAULT 0,
+                output_tokens INTEGER NOT NULL DEFAULT 0,
+                thinking_tokens INTEGER NOT NULL DEFAULT 0,
+                latency_ms INTEGER NOT NULL,
+                success INTEGER NOT NULL,
+                error TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS model_prices (
+                model TEXT PRIMARY KEY,
+                input_per_million REAL NOT NULL,
+                output_per_million REAL NOT NULL,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS test_cases (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                problem_id TEXT NOT NULL,
+                input TEXT NOT NULL,
+                expected_output TEXT NOT NULL,
+                rationale TEXT NOT NULL DEFAULT '',
+                source TEXT NOT NULL DEFAULT 'manual',
+                verified INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS code_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                problem_id TEXT NOT NULL,
+                language TEXT NOT NULL,
+                code TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(problem_id, language, code)
+            );
+            
+            CREATE TABLE IF NOT EXISTS code_reviews (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                code_version_id INTEGER NOT NULL,
+                model TEXT NOT NULL,
+                summary TEXT NOT NULL,
+                findings_json TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS hints (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                problem_id TEXT NOT NULL,
+                level INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                model TEXT NOT NULL,
+                revealed_at TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(problem_id, level)
+            );
+            
+            CREATE TABLE IF NOT EXISTS hint_reveals (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                problem_id TEXT NOT NULL,
+                level INTEGER NOT NULL,
+                solve_record_id INTEGER,
+                revealed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS prompt_templates (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                description TEXT NOT NULL DEFAULT '',
+                body TEXT NOT NULL,
+                builtin INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS problem_templates (
+                problem_id TEXT PRIMARY KEY,
+                template_id INTEGER NOT NULL
+            );
+            
+            CREATE TABLE IF NOT EXISTS notes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                problem_id TEXT NOT NULL UNIQUE,
+                key_idea TEXT NOT NULL,
+                algorithm TEXT NOT NULL,
+                complexity TEXT NOT NULL,
+                pitfalls_json TEXT NOT NULL DEFAULT '[]',
+                code_version_id INTEGER,
+                model TEXT NOT NULL,
+                edited INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            
+            CREATE TABLE IF NOT EXISTS problem_embeddings (
+                problem_id TEXT NOT NULL,
+                model TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                vector BLOB NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (problem_id, model)
+            );
+            
             CREATE INDEX IF NOT EXISTS idx_problems_problem_id ON problems(problem_id);
             CREATE INDEX IF NOT EXISTS idx_chats_problem_id ON chats(problem_id);
+            CREATE INDEX IF NOT EXISTS idx_messages_thread_id ON messages(thread_id);
+            CREATE INDEX IF NOT EXISTS idx_messages_parent_id ON messages(parent_id);
             CREATE INDEX IF NOT EXISTS idx_solve_records_solved_at ON solve_records(solved_at);
+            CREATE INDEX IF NOT EXISTS idx_ai_usage_created_at ON ai_usage(created_at);
+            CREATE INDEX IF NOT EXISTS idx_test_cases_problem_id ON test_cases(problem_id);
+            CREATE INDEX IF NOT EXISTS idx_code_reviews_code_version_id ON code_reviews(code_version_id);
+            CREATE INDEX IF NOT EXISTS idx_hint_reveals_problem_id ON hint_reveals(problem_id);
             "
         )?;
+
+        add_column_if_missing(&conn, "chats", "summary", "TEXT")?;
+        add_column_if_missing(&conn, "chats", "summarized_count", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "solve_records", "hints_used", "INTEGER NOT NULL DEFAULT 0")?;
+        migrate_chat_threads(&mut conn)?;
+        add_column_if_missing(&conn, "chats", "active_message_id", "INTEGER")?;
+        // Usage tables created before key pools have no key label yet.
+        add_column_if_missing(&conn, "ai_usage", "api_key_label", "TEXT")?;
+        migrate_chat_messages(&conn)?;
+
+        for (name, description, body) in crate::templates::BUILTIN_TEMPLATES {
+            conn.execute(
+                "INSERT OR IGNORE INTO prompt_templates (name, description, body, builtin)
+                 VALUES (?1, ?2, ?3, 1)",
+                [name, description, body],
+            )?;
+        }
         
         Ok(())
     }
@@ -175,62 +547,252 @@ impl Database {
         rows.collect()
     }
 
-    pub fn save_chat(&self, problem_id: &str, messages_json: &str) -> SqliteResult<i64> {
+    pub fn get_chat_by_problem(&self, problem_id: &str) -> SqliteResult<Option<ChatRecord>> {
         let conn = self.conn.lock().unwrap();
-        
-        let existing: Option<i64> = conn
-            .query_row(
-                "SELECT id FROM chats WHERE problem_id = ?1",
-                [problem_id],
-                |row| row.get(0),
-            )
-            .ok();
-        
-        if let Some(chat_id) = existing {
-            conn.execute(
-                "UPDATE chats SET messages_json = ?1, updated_at = datetime('now') WHERE id = ?2",
-                rusqlite::params![messages_json, chat_id],
-            )?;
-            Ok(chat_id)
-        } else {
-            conn.execute(
-                "INSERT INTO chats (problem_id, messages_json) VALUES (?1, ?2)",
-                [problem_id, messages_json],
-            )?;
-            Ok(conn.last_insert_rowid())
-        }
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, title, active_message_id, summary, summarized_count, created_at, updated_at
+             FROM chats WHERE problem_id = ?1
+             ORDER BY updated_at DESC, id DESC
+             LIMIT 1"
+        )?;
+
+        let mut rows = stmt.query_map([problem_id], chat_from_row)?;
+        rows.next().transpose()
     }
 
-    pub fn get_chat_by_problem(&self, problem_id: &str) -> SqliteResult<Option<ChatRecord>> {
+    pub fn create_chat_thread(&self, problem_id: &str, title: &str) -> SqliteResult<ChatRecord> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO chats (problem_id, title, messages_json) VALUES (?1, ?2, '[]')",
+            [problem_id, title],
+        )?;
+
+        conn.query_row(
+            "SELECT id, problem_id, title, active_message_id, summary, summarized_count, created_at, updated_at
+             FROM chats WHERE id = ?1",
+            [conn.last_insert_rowid()],
+            chat_from_row,
+        )
+    }
+
+    pub fn get_chat_thread(&self, id: i64) -> SqliteResult<Option<ChatRecord>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, problem_id, messages_json, created_at, updated_at
-             FROM chats WHERE problem_id = ?1"
+            "SELECT id, problem_id, title, active_message_id, summary, summarized_count, created_at, updated_at
+             FROM chats WHERE id = ?1"
         )?;
-        
-        let mut rows = stmt.query([problem_id])?;
-        if let Some(row) = rows.next()? {
-            Ok(Some(ChatRecord {
-                id: row.get(0)?,
-                problem_id: row.get(1)?,
-                messages_json: row.get(2)?,
-                created_at: row.get(3)?,
-                updated_at: row.get(4)?,
-            }))
-        } else {
-            Ok(None)
+
+        let mut rows = stmt.query_map([id], chat_from_row)?;
+        rows.next().transpose()
+    }
+
+    pub fn get_chat_threads(&self, problem_id: &str) -> SqliteResult<Vec<ChatRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, title, active_message_id, summary, summarized_count, created_at, updated_at
+             FROM chats WHERE problem_id = ?1
+             ORDER BY updated_at DESC, id DESC"
+        )?;
+
+        let rows = stmt.query_map([problem_id], chat_from_row)?;
+        rows.collect()
+    }
+
+    pub fn rename_chat_thread(&self, id: i64, title: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE chats SET title = ?1, updated_at = datetime('now') WHERE id = ?2",
+            rusqlite::params![title, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn delete_chat_thread(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM messages WHERE thread_id = ?1", [id])?;
+        let deleted = conn.execute("DELETE FROM chats WHERE id = ?1", [id])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn add_message(&self, message: &NewMessage) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute(
+            "INSERT INTO messages (thread_id, parent_id, role, content, model, tokens, tool_calls_json, thoughts)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                message.thread_id,
+                message.parent_id,
+                message.role,
+                message.content,
+                message.model,
+                message.tokens,
+                message.tool_calls_json,
+                message.thoughts,
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        activate_branch(&tx, message.thread_id, id, id)?;
+
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Stores `content` as a new sibling of the message, keeping the original branch intact.
+    pub fn edit_message(&self, id: i64, content: &str) -> SqliteResult<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let inserted = tx.execute(
+            "INSERT INTO messages (thread_id, parent_id, role, content)
+             SELECT thread_id, parent_id, role, ?1 FROM messages WHERE id = ?2",
+            rusqlite::params![content, id],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        let new_id = tx.last_insert_rowid();
+        let thread_id: i64 =
+            tx.query_row("SELECT thread_id FROM messages WHERE id = ?1", [new_id], |row| {
+                row.get(0)
+            })?;
+        activate_branch(&tx, thread_id, new_id, new_id)?;
+
+        tx.commit()?;
+        Ok(Some(new_id))
+    }
+
+    /// Makes the branch through the message active, following its most recent replies.
+    pub fn switch_branch(&self, id: i64) -> SqliteResult<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let thread_id: Option<i64> = tx
+            .query_row("SELECT thread_id FROM messages WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .ok();
+        let Some(thread_id) = thread_id else {
+            return Ok(None);
+        };
+
+        let mut leaf = id;
+        while let Ok(child) = tx.query_row(
+            "SELECT id FROM messages WHERE parent_id = ?1 ORDER BY id DESC LIMIT 1",
+            [leaf],
+            |row| row.get(0),
+        ) {
+            leaf = child;
         }
+        activate_branch(&tx, thread_id, leaf, id)?;
+
+        tx.commit()?;
+        Ok(Some(thread_id))
     }
 
-    pub fn delete_problem(&self, problem_id: &str) -> SqliteResult<()> {
+    pub fn get_message(&self, id: i64) -> SqliteResult<Option<MessageRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages m WHERE m.id = ?1",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query_map([id], message_from_row)?;
+        rows.next().transpose()
+    }
+
+    /// Returns the path from the root of the thread down to the message.
+    pub fn get_branch(&self, leaf_id: i64) -> SqliteResult<Vec<MessageRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "WITH RECURSIVE branch(id, depth) AS (
+                SELECT ?1, 0
+                UNION ALL
+                SELECT p.parent_id, b.depth + 1
+                FROM messages p JOIN branch b ON p.id = b.id
+                WHERE p.parent_id IS NOT NULL
+             )
+             SELECT {} FROM branch b JOIN messages m ON m.id = b.id
+             ORDER BY b.depth DESC",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([leaf_id], message_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_active_branch(&self, thread_id: i64) -> SqliteResult<Vec<MessageRecord>> {
+        match self
+            .get_chat_thread(thread_id)?
+            .and_then(|chat| chat.active_message_id)
+        {
+            Some(leaf_id) => self.get_branch(leaf_id),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn get_message_siblings(&self, id: i64) -> SqliteResult<Vec<MessageRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages m
+             JOIN messages o ON o.id = ?1
+             WHERE m.thread_id = o.thread_id AND m.parent_id IS o.parent_id
+             ORDER BY m.id ASC",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([id], message_from_row)?;
+        rows.collect()
+    }
+
+    pub fn save_chat_summary(
+        &self,
+        id: i64,
+        summary: &str,
+        summarized_count: i64,
+    ) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM problems WHERE problem_id = ?1", [problem_id])?;
-        conn.execute("DELETE FROM chats WHERE problem_id = ?1", [problem_id])?;
+
+        conn.execute(
+            "UPDATE chats SET summary = ?1, summarized_count = ?2 WHERE id = ?3",
+            rusqlite::params![summary, summarized_count, id],
+        )?;
+
         Ok(())
     }
 
+    pub fn delete_problem(&self, problem_id: &str) -> SqliteResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM problems WHERE problem_id = ?1", [problem_id])?;
+        tx.execute(
+            "DELETE FROM messages WHERE thread_id IN
+             (SELECT id FROM chats WHERE problem_id = ?1)",
+            [problem_id],
+        )?;
+        tx.execute("DELETE FROM chats WHERE problem_id = ?1", [problem_id])?;
+        tx.execute("DELETE FROM test_cases WHERE problem_id = ?1", [problem_id])?;
+        tx.execute("DELETE FROM hints WHERE problem_id = ?1", [problem_id])?;
+        tx.execute(
+            "DELETE FROM hint_reveals WHERE problem_id = ?1 AND solve_record_id IS NULL",
+            [problem_id],
+        )?;
+        tx.execute("DELETE FROM problem_templates WHERE problem_id = ?1", [problem_id])?;
+        tx.execute("DELETE FROM problem_embeddings WHERE problem_id = ?1", [problem_id])?;
+        tx.execute("DELETE FROM notes WHERE problem_id = ?1", [problem_id])?;
+        tx.execute(
+            "DELETE FROM code_reviews WHERE code_version_id IN
+             (SELECT id FROM code_versions WHERE problem_id = ?1)",
+            [problem_id],
+        )?;
+        tx.execute("DELETE FROM code_versions WHERE problem_id = ?1", [problem_id])?;
+        tx.commit()
+    }
+
     pub fn record_solve(&self, problem_id: &str) -> SqliteResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
         
         let today_exists: bool = conn
             .query_row(
@@ -244,25 +806,63 @@ impl Database {
             return Ok(0);
         }
         
-        conn.execute(
-            "INSERT INTO solve_records (problem_id) VALUES (?1)",
+        // Hints revealed since the previous solve belong to this one, even if the
+        // ladder was regenerated in between.
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO solve_records (problem_id, hints_used)
+             VALUES (?1, (SELECT COUNT(*) FROM hint_reveals
+                          WHERE problem_id = ?1 AND solve_record_id IS NULL))",
             [problem_id],
         )?;
+        let id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE hint_reveals SET solve_record_id = ?1
+             WHERE problem_id = ?2 AND solve_record_id IS NULL",
+            rusqlite::params![id, problem_id],
+        )?;
+        tx.commit()?;
         
-        Ok(conn.last_insert_rowid())
+        Ok(id)
     }
 
     pub fn unrecord_solve(&self, problem_id: &str) -> SqliteResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
         
-        let deleted = conn.execute(
-            "DELETE FROM solve_records WHERE problem_id = ?1 AND date(solved_at) = date('now')",
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE hint_reveals SET solve_record_id = NULL WHERE solve_record_id IN
+             (SELECT id FROM solve_records WHERE problem_id = ?1 AND date(solved_at) = date('now'))",
             [problem_id],
         )?;
+        let deleted = tx.execute(
+            "DELETE FROM solve_records WHERE problem_id = ?1 AND date(solved_at) = date('now')",
+            [problem_id],
+        )?;
+        tx.commit()?;
         
         Ok(deleted > 0)
     }
 
+    pub fn get_solve_records(&self, problem_id: &str) -> SqliteResult<Vec<SolveRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, hints_used, solved_at
+             FROM solve_records WHERE problem_id = ?1 ORDER BY solved_at DESC"
+        )?;
+
+        let rows = stmt.query_map([problem_id], |row| {
+            Ok(SolveRecord {
+                id: row.get(0)?,
+                problem_id: row.get(1)?,
+                hints_used: row.get(2)?,
+                solved_at: row.get(3)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
     pub fn is_solved_today(&self, problem_id: &str) -> SqliteResult<bool> {
         let conn = self.conn.lock().unwrap();
         
@@ -306,4 +906,1109 @@ impl Database {
         
         rows.collect()
     }
+
+    pub fn save_generation_preset(
+        &self,
+        model: &str,
+        name: &str,
+        settings_json: &str,
+    ) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO generation_presets (model, name, settings_json) VALUES (?1, ?2, ?3)
+             ON CONFLICT(model, name) DO UPDATE SET
+                settings_json = excluded.settings_json,
+                updated_at = datetime('now')",
+            [model, name, settings_json],
+        )?;
+
+        conn.query_row(
+            "SELECT id FROM generation_presets WHERE model = ?1 AND name = ?2",
+            [model, name],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_generation_preset(
+        &self,
+        model: &str,
+        name: &str,
+    ) -> SqliteResult<Option<GenerationPresetRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, model, name, settings_json, created_at, updated_at
+             FROM generation_presets WHERE model = ?1 AND name = ?2"
+        )?;
+
+        let mut rows = stmt.query([model, name])?;
+        if let Some(row) = rows.next()? {
+            Ok(Some(GenerationPresetRecord {
+                id: row.get(0)?,
+                model: row.get(1)?,
+                name: row.get(2)?,
+                settings_json: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_generation_presets(
+        &self,
+        model: Option<&str>,
+    ) -> SqliteResult<Vec<GenerationPresetRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, model, name, settings_json, created_at, updated_at
+             FROM generation_presets
+             WHERE ?1 IS NULL OR model = ?1
+             ORDER BY model ASC, name ASC"
+        )?;
+
+        let rows = stmt.query_map([model], |row| {
+            Ok(GenerationPresetRecord {
+                id: row.get(0)?,
+                model: row.get(1)?,
+                name: row.get(2)?,
+                settings_json: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn delete_generation_preset(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM generation_presets WHERE id = ?1", [id])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn save_prompt_template(
+        &self,
+        name: &str,
+        description: &str,
+        body: &str,
+    ) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO prompt_templates (name, description, body) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET
+                description = excluded.description,
+                body = excluded.body,
+                updated_at = datetime('now')",
+            [name, description, body],
+        )?;
+
+        conn.query_row(
+            "SELECT id FROM prompt_templates WHERE name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_prompt_template(&self, id: i64) -> SqliteResult<Option<PromptTemplateRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, body, builtin, created_at, updated_at
+             FROM prompt_templates WHERE id = ?1"
+        )?;
+
+        let mut rows = stmt.query_map([id], prompt_template_from_row)?;
+        rows.next().transpose()
+    }
+
+    pub fn get_prompt_templates(&self) -> SqliteResult<Vec<PromptTemplateRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, body, builtin, created_at, updated_at
+             FROM prompt_templates
+             ORDER BY builtin DESC, name ASC"
+        )?;
+
+        let rows = stmt.query_map([], prompt_template_from_row)?;
+        rows.collect()
+    }
+
+    pub fn delete_prompt_template(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM prompt_templates WHERE id = ?1 AND builtin = 0",
+            [id],
+        )?;
+        if deleted > 0 {
+            conn.execute("DELETE FROM problem_templates WHERE template_id = ?1", [id])?;
+        }
+        Ok(deleted > 0)
+    }
+
+    pub fn set_problem_template(
+        &self,
+        problem_id: &str,
+        template_id: Option<i64>,
+    ) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        match template_id {
+            Some(template_id) => conn.execute(
+                "INSERT INTO problem_templates (problem_id, template_id) VALUES (?1, ?2)
+                 ON CONFLICT(problem_id) DO UPDATE SET template_id = excluded.template_id",
+                rusqlite::params![problem_id, template_id],
+            )?,
+            None => conn.execute(
+                "DELETE FROM problem_templates WHERE problem_id = ?1",
+                [problem_id],
+            )?,
+        };
+        Ok(())
+    }
+
+    pub fn get_problem_template(
+        &self,
+        problem_id: &str,
+    ) -> SqliteResult<Option<PromptTemplateRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.name, t.description, t.body, t.builtin, t.created_at, t.updated_at
+             FROM problem_templates p
+             JOIN prompt_templates t ON t.id = p.template_id
+             WHERE p.problem_id = ?1"
+        )?;
+
+        let mut rows = stmt.query_map([problem_id], prompt_template_from_row)?;
+        rows.next().transpose()
+    }
+
+    pub fn log_ai_usage(&self, entry: &AiUsageEntry) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO ai_usage
+             (problem_id, model, api_key_label, kind, prompt_tokens, output_tokens, thinking_tokens, latency_ms, success, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            rusqlite::params![
+                entry.problem_id,
+                entry.model,
+                entry.api_key_label,
+                entry.kind,
+                entry.prompt_tokens,
+                entry.output_tokens,
+                entry.thinking_tokens,
+                entry.latency_ms,
+                entry.error.is_none(),
+                entry.error,
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_usage_aggregates(&self, group: UsageGroup, days: u32) -> SqliteResult<Vec<UsageAggregate>> {
+        let key = group.key();
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {key} AS group_key,
+                    COUNT(*),
+                    SUM(CASE WHEN u.success THEN 0 ELSE 1 END),
+                    SUM(u.prompt_tokens),
+                    SUM(u.output_tokens),
+                    SUM(u.thinking_tokens),
+                    AVG(u.latency_ms),
+                    SUM(COALESCE(
+                        (u.prompt_tokens * p.input_per_million
+                            + (u.output_tokens + u.thinking_tokens) * p.output_per_million) / 1000000.0,
+                        0
+                    ))
+             FROM ai_usage u
+             LEFT JOIN model_prices p ON p.model = u.model
+             WHERE u.created_at >= datetime('now', ?1)
+             GROUP BY group_key
+             ORDER BY group_key ASC"
+        ))?;
+
+        let days_param = format!("-{} days", days);
+        let rows = stmt.query_map([&days_param], |row| {
+            Ok(UsageAggregate {
+                key: row.get(0)?,
+                calls: row.get(1)?,
+                errors: row.get(2)?,
+                prompt_tokens: row.get(3)?,
+                output_tokens: row.get(4)?,
+                thinking_tokens: row.get(5)?,
+                avg_latency_ms: row.get(6)?,
+                estimated_cost: row.get(7)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn set_model_price(
+        &self,
+        model: &str,
+        input_per_million: f64,
+        output_per_million: f64,
+    ) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO model_prices (model, input_per_million, output_per_million)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(model) DO UPDATE SET
+                input_per_million = excluded.input_per_million,
+                output_per_million = excluded.output_per_million,
+                updated_at = datetime('now')",
+            rusqlite::params![model, input_per_million, output_per_million],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_model_prices(&self) -> SqliteResult<Vec<ModelPrice>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT model, input_per_million, output_per_million, updated_at
+             FROM model_prices ORDER BY model ASC"
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(ModelPrice {
+                model: row.get(0)?,
+                input_per_million: row.get(1)?,
+                output_per_million: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn delete_model_price(&self, model: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM model_prices WHERE model = ?1", [model])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn save_test_case(&self, test_case: &NewTestCase) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO test_cases (problem_id, input, expected_output, rationale, source, verified)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                test_case.problem_id,
+                test_case.input,
+                test_case.expected_output,
+                test_case.rationale,
+                test_case.source,
+                test_case.verified,
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_test_case(&self, id: i64) -> SqliteResult<Option<TestCaseRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, input, expected_output, rationale, source, verified, created_at
+             FROM test_cases WHERE id = ?1"
+        )?;
+
+        let mut rows = stmt.query([id])?;
+        if let Some(row) = rows.next()? {
+            Ok(Some(TestCaseRecord {
+                id: row.get(0)?,
+                problem_id: row.get(1)?,
+                input: row.get(2)?,
+                expected_output: row.get(3)?,
+                rationale: row.get(4)?,
+                source: row.get(5)?,
+                verified: row.get(6)?,
+                created_at: row.get(7)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_test_cases(&self, problem_id: &str) -> SqliteResult<Vec<TestCaseRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, input, expected_output, rationale, source, verified, created_at
+             FROM test_cases WHERE problem_id = ?1 ORDER BY id ASC"
+        )?;
+
+        let rows = stmt.query_map([problem_id], |row| {
+            Ok(TestCaseRecord {
+                id: row.get(0)?,
+                problem_id: row.get(1)?,
+                input: row.get(2)?,
+                expected_output: row.get(3)?,
+                rationale: row.get(4)?,
+                source: row.get(5)?,
+                verified: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn delete_test_case(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM test_cases WHERE id = ?1", [id])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn save_code_version(&self, problem_id: &str, language: &str, code: &str) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR IGNORE INTO code_versions (problem_id, language, code) VALUES (?1, ?2, ?3)",
+            [problem_id, language, code],
+        )?;
+
+        conn.query_row(
+            "SELECT id FROM code_versions WHERE problem_id = ?1 AND language = ?2 AND code = ?3",
+            [problem_id, language, code],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_code_versions(&self, problem_id: &str) -> SqliteResult<Vec<CodeVersionRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, language, code, created_at
+             FROM code_versions WHERE problem_id = ?1 ORDER BY id DESC"
+        )?;
+
+        let rows = stmt.query_map([problem_id], |row| {
+            Ok(CodeVersionRecord {
+                id: row.get(0)?,
+                problem_id: row.get(1)?,
+                language: row.get(2)?,
+                code: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn save_code_review(
+        &self,
+        code_version_id: i64,
+        model: &str,
+        summary: &str,
+        findings_json: &str,
+    ) -> SqliteResult<CodeReviewRecord> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO code_reviews (code_version_id, model, summary, findings_json)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![code_version_id, model, summary, findings_json],
+        )?;
+
+        conn.query_row(
+            "SELECT id, code_version_id, model, summary, findings_json, created_at
+             FROM code_reviews WHERE id = ?1",
+            [conn.last_insert_rowid()],
+            |row| {
+                Ok(CodeReviewRecord {
+                    id: row.get(0)?,
+                    code_version_id: row.get(1)?,
+                    model: row.get(2)?,
+                    summary: row.get(3)?,
+                    findings_json: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            },
+        )
+    }
+
+    pub fn get_code_reviews(&self, code_version_id: i64) -> SqliteResult<Vec<CodeReviewRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, code_version_id, model, summary, findings_json, created_at
+             FROM code_reviews WHERE code_version_id = ?1 ORDER BY id DESC"
+        )?;
+
+        let rows = stmt.query_map([code_version_id], |row| {
+            Ok(CodeReviewRecord {
+                id: row.get(0)?,
+                code_version_id: row.get(1)?,
+                model: row.get(2)?,
+                summary: row.get(3)?,
+                findings_json: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn replace_hints(&self, problem_id: &str, model: &str, hints: &[String]) -> SqliteResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM hints WHERE problem_id = ?1", [problem_id])?;
+        for (i, content) in hints.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO hints (problem_id, level, content, model) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![problem_id, i as i64 + 1, content, model],
+            )?;
+        }
+
+        tx.commit()
+    }
+
+    pub fn count_hints(&self, problem_id: &str) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM hints WHERE problem_id = ?1",
+            [problem_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_revealed_hints(&self, problem_id: &str) -> SqliteResult<Vec<HintRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, problem_id, level, content, model, revealed_at, created_at
+             FROM hints WHERE problem_id = ?1 AND revealed_at IS NOT NULL
+             ORDER BY level ASC"
+        )?;
+
+        let rows = stmt.query_map([problem_id], |row| {
+            Ok(HintRecord {
+                id: row.get(0)?,
+                problem_id: row.get(1)?,
+                level: row.get(2)?,
+                content: row.get(3)?,
+                model: row.get(4)?,
+                revealed_at: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn reveal_next_hint(&self, problem_id: &str) -> SqliteResult<Option<HintRecord>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let next: Option<(i64, i64)> = tx
+            .query_row(
+                "SELECT id, level FROM hints WHERE problem_id = ?1 AND revealed_at IS NULL
+                 ORDER BY level ASC LIMIT 1",
+                [problem_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((id, level)) = next else {
+            return Ok(None);
+        };
+
+        tx.execute(
+            "UPDATE hints SET revealed_at = datetime('now') WHERE id = ?1",
+            [id],
+        )?;
+        tx.execute(
+            "INSERT INTO hint_reveals (problem_id, level) VALUES (?1, ?2)",
+            rusqlite::params![problem_id, level],
+        )?;
+
+        let hint = tx.query_row(
+            "SELECT id, problem_id, level, content, model, revealed_at, created_at
+             FROM hints WHERE id = ?1",
+            [id],
+            |row| {
+                Ok(Some(HintRecord {
+                    id: row.get(0)?,
+                    problem_id: row.get(1)?,
+                    level: row.get(2)?,
+                    content: row.get(3)?,
+                    model: row.get(4)?,
+                    revealed_at: row.get(5)?,
+                    created_at: row.get(6)?,
+                }))
+            },
+        )?;
+        tx.commit()?;
+        Ok(hint)
+    }
+
+    /// Stores a freshly written note, replacing the problem's previous one. A note the
+    /// user edited is only replaced with `overwrite_edits`; otherwise `None` is returned.
+    pub fn save_note(
+        &self,
+        problem_id: &str,
+        content: &NoteContent,
+        code_version_id: Option<i64>,
+        model: &str,
+        overwrite_edits: bool,
+    ) -> SqliteResult<Option<NoteRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let saved = conn.execute(
+            "INSERT INTO notes
+             (problem_id, key_idea, algorithm, complexity, pitfalls_json, code_version_id, model)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(problem_id) DO UPDATE SET
+                key_idea = excluded.key_idea,
+                algorithm = excluded.algorithm,
+                complexity = excluded.complexity,
+                pitfalls_json = excluded.pitfalls_json,
+                code_version_id = excluded.code_version_id,
+                model = excluded.model,
+                edited = 0,
+                updated_at = datetime('now')
+             WHERE notes.edited = 0 OR ?8",
+            rusqlite::params![
+                problem_id,
+                content.key_idea,
+                content.algorithm,
+                content.complexity,
+                content.pitfalls_json,
+                code_version_id,
+                model,
+                overwrite_edits
+            ],
+        )?;
+        if saved == 0 {
+            return Ok(None);
+        }
+
+        conn.query_row(
+            &format!("SELECT {} WHERE n.problem_id = ?1", NOTE_SELECT),
+            [problem_id],
+            note_from_row,
+        )
+        .map(Some)
+    }
+
+    pub fn update_note(&self, id: i64, content: &NoteContent) -> SqliteResult<Option<NoteRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE notes SET key_idea = ?1, algorithm = ?2, complexity = ?3, pitfalls_json = ?4,
+                edited = 1, updated_at = datetime('now')
+             WHERE id = ?5",
+            rusqlite::params![
+                content.key_idea,
+                content.algorithm,
+                content.complexity,
+                content.pitfalls_json,
+                id
+            ],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+
+        conn.query_row(
+            &format!("SELECT {} WHERE n.id = ?1", NOTE_SELECT),
+            [id],
+            note_from_row,
+        )
+        .map(Some)
+    }
+
+    pub fn get_note(&self, problem_id: &str) -> SqliteResult<Option<NoteRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} WHERE n.problem_id = ?1", NOTE_SELECT))?;
+        let mut rows = stmt.query_map([problem_id], note_from_row)?;
+        rows.next().transpose()
+    }
+
+    /// Notes whose problem title or text contains every word of `query`, newest first.
+    /// An empty query lists every note.
+    pub fn search_notes(&self, query: &str) -> SqliteResult<Vec<NoteRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| {
+                let escaped = word
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                format!("%{}%", escaped)
+            })
+            .collect();
+        let conditions = (1..=words.len())
+            .map(|i| {
+                format!(
+                    "(n.problem_id || ' ' || COALESCE(p.title, '') || ' ' || n.key_idea || ' ' ||
+                      n.algorithm || ' ' || n.complexity || ' ' || n.pitfalls_json) LIKE ?{} ESCAPE '\\'",
+                    i
+                )
+            })
+            .collect::<Vec<_>>();
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} {} ORDER BY n.updated_at DESC, n.id DESC",
+            NOTE_SELECT, filter
+        ))?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(words.iter()), note_from_row)?;
+        rows.collect()
+    }
+
+    pub fn delete_note(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM notes WHERE id = ?1", [id])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn get_problem_embeddings(&self, model: &str) -> SqliteResult<Vec<ProblemEmbedding>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT problem_id, content_hash, vector FROM problem_embeddings WHERE model = ?1",
+        )?;
+
+        let rows = stmt.query_map([model], |row| {
+            let bytes: Vec<u8> = row.get(2)?;
+            Ok(ProblemEmbedding {
+                problem_id: row.get(0)?,
+                content_hash: row.get(1)?,
+                vector: bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn save_problem_embedding(
+        &self,
+        problem_id: &str,
+        model: &str,
+        content_hash: &str,
+        vector: &[f32],
+    ) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+        conn.execute(
+            "INSERT OR REPLACE INTO problem_embeddings (problem_id, model, content_hash, vector)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![problem_id, model, content_hash, bytes],
+        )?;
+        Ok(())
+    }
+}
+
+fn note_from_row(row: &rusqlite::Row) -> SqliteResult<NoteRecord> {
+    Ok(NoteRecord {
+        id: row.get(0)?,
+        problem_id: row.get(1)?,
+        title: row.get(2)?,
+        key_idea: row.get(3)?,
+        algorithm: row.get(4)?,
+        complexity: row.get(5)?,
+        pitfalls_json: row.get(6)?,
+        code_version_id: row.get(7)?,
+        model: row.get(8)?,
+        edited: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+fn prompt_template_from_row(row: &rusqlite::Row) -> SqliteResult<PromptTemplateRecord> {
+    Ok(PromptTemplateRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        body: row.get(3)?,
+        builtin: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+fn chat_from_row(row: &rusqlite::Row) -> SqliteResult<ChatRecord> {
+    Ok(ChatRecord {
+        id: row.get(0)?,
+        problem_id: row.get(1)?,
+        title: row.get(2)?,
+        active_message_id: row.get(3)?,
+        summary: row.get(4)?,
+        summarized_count: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+fn message_from_row(row: &rusqlite::Row) -> SqliteResult<MessageRecord> {
+    Ok(MessageRecord {
+        id: row.get(0)?,
+        thread_id: row.get(1)?,
+        parent_id: row.get(2)?,
+        role: row.get(3)?,
+        content: row.get(4)?,
+        model: row.get(5)?,
+        tokens: row.get(6)?,
+        tool_calls_json: row.get(7)?,
+        thoughts: row.get(8)?,
+        sibling_index: row.get(9)?,
+        sibling_count: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+/// Points the thread at `leaf_id`. The stored summary is dropped when the branch changes
+/// at or before the last summarized position, since it describes messages no longer shown.
+fn activate_branch(
+    conn: &Connection,
+    thread_id: i64,
+    leaf_id: i64,
+    changed_id: i64,
+) -> SqliteResult<()> {
+    let position: i64 = conn.query_row(
+        "WITH RECURSIVE ancestors(id) AS (
+            SELECT parent_id FROM messages WHERE id = ?1
+            UNION ALL
+            SELECT m.parent_id FROM messages m JOIN ancestors a ON m.id = a.id
+         )
+         SELECT COUNT(*) FROM ancestors WHERE id IS NOT NULL",
+        [changed_id],
+        |row| row.get(0),
+    )?;
+
+    conn.execute(
+        "UPDATE chats SET
+            active_message_id = ?1,
+            summary = CASE WHEN ?2 <= summarized_count THEN NULL ELSE summary END,
+            summarized_count = CASE WHEN ?2 <= summarized_count THEN 0 ELSE summarized_count END,
+            updated_at = datetime('now')
+         WHERE id = ?3",
+        rusqlite::params![leaf_id, position, thread_id],
+    )?;
+    Ok(())
+}
+
+/// Rebuilds the `chats` table created with `problem_id UNIQUE` so a problem can hold
+/// several named threads. Existing conversations become each problem's default thread.
+fn migrate_chat_threads(conn: &mut Connection) -> SqliteResult<()> {
+    if has_column(conn, "chats", "title")? {
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    tx.execute_batch(&format!(
+        "
+        CREATE TABLE chats_threads (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            problem_id TEXT NOT NULL,
+            title TEXT NOT NULL,
+            messages_json TEXT NOT NULL,
+            summary TEXT,
+            summarized_count INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        INSERT INTO chats_threads
+            (id, problem_id, title, messages_json, summary, summarized_count, created_at, updated_at)
+        SELECT id, problem_id, '{}', messages_json, summary, summarized_count, created_at, updated_at
+        FROM chats;
+        DROP TABLE chats;
+        ALTER TABLE chats_threads RENAME TO chats;
+        CREATE INDEX IF NOT EXISTS idx_chats_problem_id ON chats(problem_id);
+        ",
+        DEFAULT_THREAD_TITLE
+    ))?;
+    tx.commit()
+}
+
+/// Moves conversations stored as one `messages_json` blob into `messages` rows,
+/// as a single linear branch per thread.
+fn migrate_chat_messages(conn: &Connection) -> SqliteResult<()> {
+    let legacy: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT id, messages_json FROM chats WHERE messages_json != '[]'")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<SqliteResult<_>>()?
+    };
+    if legacy.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for (thread_id, messages_json) in legacy {
+        // History that does not parse stays in the legacy column rather than being
+        // replaced by an empty conversation.
+        let Ok(messages) = serde_json::from_str::<Vec<serde_json::Value>>(&messages_json) else {
+            continue;
+        };
+        let mut parent_id: Option<i64> = None;
+
+        for message in messages {
+            let tool_calls_json = message
+                .get("tool_calls")
+                .map(|calls| calls.to_string())
+                .unwrap_or_else(|| "[]".to_string());
+            tx.execute(
+                "INSERT INTO messages (thread_id, parent_id, role, content, tool_calls_json)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    thread_id,
+                    parent_id,
+                    message["role"].as_str().unwrap_or("user"),
+                    message["content"].as_str().unwrap_or_default(),
+                    tool_calls_json,
+                ],
+            )?;
+            parent_id = Some(tx.last_insert_rowid());
+        }
+
+        tx.execute(
+            "UPDATE chats SET messages_json = '[]', active_message_id = ?1 WHERE id = ?2",
+            rusqlite::params![parent_id, thread_id],
+        )?;
+    }
+    tx.commit()
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> SqliteResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<SqliteResult<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    Ok(exists)
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> SqliteResult<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crawler::Problem;
+    use std::ops::Deref;
+
+    /// The schema the first release created, before threads and message rows.
+    const BASELINE_SCHEMA: &str = "
+        CREATE TABLE problems (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            problem_id TEXT NOT NULL UNIQUE,
+            title TEXT NOT NULL,
+            description TEXT NOT NULL,
+            input_description TEXT NOT NULL,
+            output_description TEXT NOT NULL,
+            samples_json TEXT NOT NULL,
+            time_limit TEXT NOT NULL,
+            memory_limit TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE chats (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            problem_id TEXT NOT NULL UNIQUE,
+            messages_json TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE solve_records (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            problem_id TEXT NOT NULL,
+            solved_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX idx_problems_problem_id ON problems(problem_id);
+        CREATE INDEX idx_chats_problem_id ON chats(problem_id);
+        CREATE INDEX idx_solve_records_solved_at ON solve_records(solved_at);
+    ";
+
+    fn test_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("psup-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// A fresh database in a temp file that is removed when the test ends.
+    pub(crate) struct TestDb {
+        db: Database,
+        path: PathBuf,
+    }
+
+    impl Deref for TestDb {
+        type Target = Database;
+
+        fn deref(&self) -> &Database {
+            &self.db
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    pub(crate) fn test_db(name: &str) -> TestDb {
+        let path = test_path(name);
+        TestDb {
+            db: Database::new(path.clone()).unwrap(),
+            path,
+        }
+    }
+
+    pub(crate) fn test_problem(id: &str, title: &str) -> Problem {
+        Problem {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: String::new(),
+            input_description: String::new(),
+            output_description: String::new(),
+            samples: vec![],
+            time_limit: "1 초".to_string(),
+            memory_limit: "128 MB".to_string(),
+        }
+    }
+
+    fn baseline_db(name: &str, chats: &[(&str, &str)]) -> PathBuf {
+        let path = test_path(name);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(BASELINE_SCHEMA).unwrap();
+        for (problem_id, messages_json) in chats {
+            conn.execute(
+                "INSERT INTO chats (problem_id, messages_json) VALUES (?1, ?2)",
+                [problem_id, messages_json],
+            )
+            .unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn estimates_usage_cost_from_model_prices() {
+        let db = test_db("usage");
+        let entry = |model: &'static str, error: Option<&'static str>| AiUsageEntry {
+            problem_id: Some("1000"),
+            model,
+            api_key_label: Some("main"),
+            kind: "chat",
+            prompt_tokens: 1_000_000,
+            output_tokens: 200_000,
+            thinking_tokens: 300_000,
+            latency_ms: 100,
+            error,
+        };
+        db.log_ai_usage(&entry("gemini-2.5-flash", None)).unwrap();
+        db.log_ai_usage(&entry("gemini-2.5-flash", Some("quota")))
+            .unwrap();
+        db.log_ai_usage(&entry("unpriced", None)).unwrap();
+        db.set_model_price("gemini-2.5-flash", 0.3, 2.5).unwrap();
+
+        let usage = db.get_usage_aggregates(UsageGroup::Model, 1).unwrap();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(
+            (usage[0].key.as_str(), usage[0].calls, usage[0].errors),
+            ("gemini-2.5-flash", 2, 1)
+        );
+        // Each call: 1M input at 0.3 plus 0.5M output and thinking at 2.5.
+        assert!((usage[0].estimated_cost - 2.0 * 1.55).abs() < 1e-9);
+        assert_eq!(usage[1].estimated_cost, 0.0);
+
+        let by_key = db.get_usage_aggregates(UsageGroup::ApiKey, 1).unwrap();
+        assert_eq!((by_key[0].key.as_str(), by_key[0].calls), ("main", 3));
+        assert!(UsageGroup::parse("week").is_err());
+    }
+
+    #[test]
+    fn upgrades_the_baseline_schema() {
+        let path = baseline_db(
+            "upgrade",
+            &[(
+                "1000",
+                r#"[{"role":"user","content":"질문"},{"role":"assistant","content":"답변"}]"#,
+            )],
+        );
+
+        let db = Database::new(path.clone()).unwrap();
+        let threads = db.get_chat_threads("1000").unwrap();
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].title, DEFAULT_THREAD_TITLE);
+        let branch = db.get_active_branch(threads[0].id).unwrap();
+        let contents: Vec<&str> = branch.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["질문", "답변"]);
+        drop(db);
+
+        // Opening an upgraded database again leaves it as it is.
+        let db = Database::new(path.clone()).unwrap();
+        assert_eq!(db.get_chat_threads("1000").unwrap().len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn keeps_legacy_history_that_does_not_parse() {
+        let broken = r#"[{"role":"user","content":"잘린 기록"#;
+        let path = baseline_db(
+            "legacy",
+            &[
+                ("1000", r#"[{"role":"user","content":"질문"}]"#),
+                ("1001", broken),
+            ],
+        );
+
+        let db = Database::new(path.clone()).unwrap();
+        let thread = db.get_chat_threads("1001").unwrap().remove(0);
+        assert!(db.get_active_branch(thread.id).unwrap().is_empty());
+        let messages_json: String = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT messages_json FROM chats WHERE id = ?1",
+                [thread.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(messages_json, broken);
+
+        let thread = db.get_chat_threads("1000").unwrap().remove(0);
+        assert_eq!(db.get_active_branch(thread.id).unwrap().len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rolls_back_a_failed_thread_migration() {
+        let path = baseline_db("rollback", &[("1000", "[]")]);
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE chats_threads (id INTEGER)")
+            .unwrap();
+
+        assert!(migrate_chat_threads(&mut conn).is_err());
+        assert!(conn.is_autocommit());
+        assert!(!has_column(&conn, "chats", "title").unwrap());
+        let chats: i64 = conn
+            .query_row("SELECT COUNT(*) FROM chats", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(chats, 1);
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,306 @@
+use crate::database::{Database, ProblemRecord};
+use crate::gemini::{Embeddings, GeminiClient};
+use crate::response_cache;
+use crate::usage;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Instant;
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+pub const DEFAULT_SIMILAR_LIMIT: usize = 5;
+/// Statements are cut here so one huge problem cannot exceed the model's input limit.
+const MAX_EMBED_CHARS: usize = 6000;
+/// The most texts Gemini accepts in one `batchEmbedContents` request.
+const MAX_EMBED_BATCH: usize = 100;
+/// A large library is embedded over several searches rather than all in the first one.
+const MAX_EMBEDS_PER_SEARCH: usize = 300;
+
+/// Turns text into vectors whose cosine similarity reflects how alike the texts are.
+pub trait EmbeddingProvider: Send + Sync {
+    /// Stored with each vector, since vectors of different models cannot be compared.
+    fn model(&self) -> &str;
+    /// One vector per text, in the same order.
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings, String>>;
+}
+
+pub struct GeminiEmbeddings {
+    client: GeminiClient,
+    model: String,
+}
+
+impl GeminiEmbeddings {
+    pub fn new(client: GeminiClient, model: Option<String>) -> Self {
+        GeminiEmbeddings {
+            client,
+            model: model.unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string()),
+        }
+    }
+}
+
+impl EmbeddingProvider for GeminiEmbeddings {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings, String>> {
+        Box::pin(self.client.embed(&self.model, texts))
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SimilarProblem {
+    pub problem_id: String,
+    pub title: String,
+    pub score: f32,
+}
+
+fn problem_text(problem: &ProblemRecord) -> String {
+    let text = format!(
+        "{}\n\n{}\n\n{}\n\n{}",
+        problem.title, problem.description, problem.input_description, problem.output_description
+    );
+    text.chars().take(MAX_EMBED_CHARS).collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Embeds the problems that were added or changed since their last embedding, in
+/// batches and at most `MAX_EMBEDS_PER_SEARCH` at a time, and returns every vector by
+/// problem. `required` goes first and must be embedded; if a later batch fails, the
+/// failure is recorded in the usage log and the rest wait for the next search.
+async fn refresh(
+    db: &Database,
+    provider: &dyn EmbeddingProvider,
+    problems: &[ProblemRecord],
+    required: &str,
+) -> Result<HashMap<String, Vec<f32>>, String> {
+    let mut stored: HashMap<String, (String, Vec<f32>)> = db
+        .get_problem_embeddings(provider.model())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|e| (e.problem_id, (e.content_hash, e.vector)))
+        .collect();
+
+    // Outdated vectors are dropped, so a problem that changed is left out of the
+    // results until it is embedded again.
+    let mut pending: Vec<(&str, String, String)> = vec![];
+    for problem in problems {
+        let text = problem_text(problem);
+        let hash = response_cache::key(&[&text]);
+        if stored
+            .get(&problem.problem_id)
+            .is_some_and(|(stored_hash, _)| *stored_hash == hash)
+        {
+            continue;
+        }
+        stored.remove(&problem.problem_id);
+        pending.push((problem.problem_id.as_str(), text, hash));
+    }
+    pending.sort_by_key(|(problem_id, _, _)| *problem_id != required);
+    pending.truncate(MAX_EMBEDS_PER_SEARCH);
+
+    for batch in pending.chunks(MAX_EMBED_BATCH) {
+        let texts: Vec<String> = batch.iter().map(|(_, text, _)| text.clone()).collect();
+        let started = Instant::now();
+        let result = provider.embed(&texts).await;
+        let usage_result = match &result {
+            Ok(embedded) => Ok(embedded.response.clone()),
+            Err(error) => Err(error.clone()),
+        };
+        usage::record(
+            db,
+            None,
+            "embedding",
+            provider.model(),
+            started,
+            &usage_result,
+        );
+
+        let embedded = match result {
+            Ok(embedded) => embedded,
+            Err(error) if batch.iter().any(|(id, _, _)| *id == required) => return Err(error),
+            Err(_) => break,
+        };
+        for ((problem_id, _, hash), vector) in batch.iter().zip(embedded.vectors) {
+            db.save_problem_embedding(problem_id, provider.model(), hash, &vector)
+                .map_err(|e| e.to_string())?;
+            stored.insert(problem_id.to_string(), (hash.clone(), vector));
+        }
+    }
+
+    Ok(stored
+        .into_iter()
+        .map(|(problem_id, (_, vector))| (problem_id, vector))
+        .collect())
+}
+
+/// The saved problems most like `problem_id`, best match first.
+pub async fn find_similar(
+    db: &Database,
+    provider: &dyn EmbeddingProvider,
+    problem_id: &str,
+    limit: usize,
+) -> Result<Vec<SimilarProblem>, String> {
+    let problems = db.get_all_problems().map_err(|e| e.to_string())?;
+    if !problems.iter().any(|p| p.problem_id == problem_id) {
+        return Err(format!("Problem not found: {}", problem_id));
+    }
+
+    let vectors = refresh(db, provider, &problems, problem_id).await?;
+    let target = vectors
+        .get(problem_id)
+        .ok_or_else(|| format!("No embedding for problem {}", problem_id))?;
+
+    let mut similar: Vec<SimilarProblem> = problems
+        .iter()
+        .filter(|p| p.problem_id != problem_id)
+        .filter_map(|p| {
+            let vector = vectors.get(&p.problem_id)?;
+            Some(SimilarProblem {
+                problem_id: p.problem_id.clone(),
+                title: p.title.clone(),
+                score: cosine(target, vector),
+            })
+        })
+        .collect();
+    similar.sort_by(|a, b| b.score.total_cmp(&a.score));
+    similar.truncate(limit);
+    Ok(similar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::Problem;
+    use crate::database::tests::{test_db, test_problem};
+    use crate::database::UsageGroup;
+    use crate::gemini::ChatResponse;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Counts letters, so statements sharing words end up close together.
+    #[derive(Default)]
+    struct LetterCounts {
+        batches: AtomicUsize,
+        texts: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    impl EmbeddingProvider for LetterCounts {
+        fn model(&self) -> &str {
+            "letters"
+        }
+
+        fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings, String>> {
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            if self.failing.load(Ordering::Relaxed) {
+                return Box::pin(async { Err("Quota exceeded".to_string()) });
+            }
+            self.texts.fetch_add(texts.len(), Ordering::Relaxed);
+            let vectors = texts
+                .iter()
+                .map(|text| {
+                    let mut counts = vec![0.0; 26];
+                    for c in text
+                        .to_ascii_lowercase()
+                        .bytes()
+                        .filter(u8::is_ascii_lowercase)
+                    {
+                        counts[(c - b'a') as usize] += 1.0;
+                    }
+                    counts
+                })
+                .collect();
+            Box::pin(async move {
+                Ok(Embeddings {
+                    vectors,
+                    response: ChatResponse {
+                        model: "letters".to_string(),
+                        ..ChatResponse::default()
+                    },
+                })
+            })
+        }
+    }
+
+    fn problem(id: &str, title: &str, description: &str) -> Problem {
+        Problem {
+            description: description.to_string(),
+            ..test_problem(id, title)
+        }
+    }
+
+    #[tokio::test]
+    async fn ranks_by_cosine_and_embeds_only_new_problems() {
+        let db = test_db("embeddings");
+        db.save_problem(&problem("1", "shortest path", "dijkstra graph edges"))
+            .unwrap();
+        db.save_problem(&problem("2", "shortest paths", "dijkstra graph weights"))
+            .unwrap();
+        db.save_problem(&problem("3", "zzz", "xyzzy quux")).unwrap();
+        let provider = LetterCounts::default();
+
+        let similar = find_similar(&db, &provider, "1", 5).await.unwrap();
+        let ids: Vec<&str> = similar.iter().map(|s| s.problem_id.as_str()).collect();
+        assert_eq!(ids, ["2", "3"]);
+        assert!(similar[0].score > similar[1].score);
+        assert_eq!(provider.batches.load(Ordering::Relaxed), 1);
+        assert_eq!(provider.texts.load(Ordering::Relaxed), 3);
+
+        db.save_problem(&problem("4", "graph", "edges")).unwrap();
+        let similar = find_similar(&db, &provider, "1", 1).await.unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_eq!(provider.texts.load(Ordering::Relaxed), 4);
+        assert!(find_similar(&db, &provider, "404", 5).await.is_err());
+
+        // A failed refresh of other problems still answers from the stored vectors,
+        // and the failure shows up in the usage log.
+        provider.failing.store(true, Ordering::Relaxed);
+        db.save_problem(&problem("5", "tree", "dfs")).unwrap();
+        assert_eq!(find_similar(&db, &provider, "1", 5).await.unwrap().len(), 3);
+        assert!(find_similar(&db, &provider, "5", 5).await.is_err());
+        let usage = db.get_usage_aggregates(UsageGroup::Model, 1).unwrap();
+        assert_eq!(
+            (usage[0].key.as_str(), usage[0].calls, usage[0].errors),
+            ("letters", 4, 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn embeds_a_large_library_in_bounded_batches() {
+        let db = test_db("embeddings-batch");
+        for id in 0..MAX_EMBEDS_PER_SEARCH + 10 {
+            db.save_problem(&problem(&id.to_string(), "graph", "edges"))
+                .unwrap();
+        }
+        let provider = LetterCounts::default();
+
+        let required = (MAX_EMBEDS_PER_SEARCH + 5).to_string();
+        find_similar(&db, &provider, &required, 5).await.unwrap();
+        assert_eq!(
+            provider.batches.load(Ordering::Relaxed),
+            MAX_EMBEDS_PER_SEARCH.div_ceil(MAX_EMBED_BATCH)
+        );
+        assert_eq!(
+            provider.texts.load(Ordering::Relaxed),
+            MAX_EMBEDS_PER_SEARCH
+        );
+
+        find_similar(&db, &provider, "0", 5).await.unwrap();
+        assert_eq!(
+            provider.texts.load(Ordering::Relaxed),
+            MAX_EMBEDS_PER_SEARCH + 10
+        );
+    }
+}
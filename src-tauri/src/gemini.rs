@@ -1,261 +1,2901 @@
+use crate::context_cache::{self, CachedContext, ContextCaches, Lookup};
+use crate::keyring::KeyRing;
+use crate::redact;
+use crate::response_cache::{self, ResponseCache};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::future::BoxFuture;
 use futures::StreamExt;
 
 const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const API_KEY_HEADER: &str = "x-goog-api-key";
+const DEFAULT_MAX_CONTINUATIONS: u32 = 2;
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 8192;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const MESSAGE_TOKEN_OVERHEAD: u32 = 4;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(20);
+const MAX_TOOL_ROUNDS: u32 = 6;
+const CONTINUE_PROMPT: &str = "Your previous answer was cut off because it reached the output limit. \
+Continue exactly where it stopped, without repeating anything or adding a preamble.";
+
+const MODELS_PAGE_SIZE: &str = "1000";
+const MAX_MODEL_PAGES: usize = 20;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GeminiModel {
     pub name: String,
     pub display_name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub input_token_limit: u32,
+    #[serde(default)]
+    pub output_token_limit: u32,
+    #[serde(default)]
+    pub supported_generation_methods: Vec<String>,
+    #[serde(default)]
+    pub thinking: bool,
+}
+
+impl GeminiModel {
+    /// Embedding, image and audio-only models are listed too but cannot hold a chat.
+    pub fn can_chat(&self) -> bool {
+        self.supported_generation_methods
+            .iter()
+            .any(|m| m == "generateContent")
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct ListModelsResponse {
+    #[serde(default)]
     models: Vec<Model>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Model {
     name: String,
-    #[serde(rename = "displayName")]
+    #[serde(rename = "displayName", default)]
     display_name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(rename = "inputTokenLimit", default)]
+    input_token_limit: u32,
+    #[serde(rename = "outputTokenLimit", default)]
+    output_token_limit: u32,
+    #[serde(rename = "supportedGenerationMethods", default)]
+    supported_generation_methods: Vec<String>,
+    thinking: Option<bool>,
+}
+
+impl From<Model> for GeminiModel {
+    fn from(model: Model) -> Self {
+        let name = model.name.replace("models/", "");
+        GeminiModel {
+            thinking: model.thinking.unwrap_or_else(|| supports_thinking(&name)),
+            display_name: if model.display_name.is_empty() {
+                name.clone()
+            } else {
+                model.display_name
+            },
+            name,
+            version: model.version,
+            input_token_limit: model.input_token_limit,
+            output_token_limit: model.output_token_limit,
+            supported_generation_methods: model.supported_generation_methods,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
-struct GeminiRequest {
-    contents: Vec<Content>,
+struct GeminiRequest<'a> {
+    contents: &'a [Content],
     generation_config: GenerationConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolSet>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<ToolConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cached_content: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateCachedContentRequest {
+    model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<SystemInstruction>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contents: Vec<Content>,
+    ttl: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SystemInstruction {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CachedContentResponse {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ToolSet {
+    function_declarations: Vec<FunctionDeclaration>,
 }
 
 #[derive(Debug, Serialize)]
+struct ToolConfig {
+    function_calling_config: FunctionCallingConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionCallingConfig {
+    mode: &'static str,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FunctionDeclaration {
+    pub name: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+}
+
+pub trait ToolExecutor: Send + Sync {
+    fn declarations(&self) -> Vec<FunctionDeclaration>;
+    fn call<'a>(&'a self, name: &'a str, args: &'a serde_json::Value) -> BoxFuture<'a, serde_json::Value>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolInvocation {
+    pub name: String,
+    pub args: serde_json::Value,
+    pub result: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Clone)]
 struct Content {
     role: String,
     parts: Vec<Part>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone, Default)]
 struct Part {
-    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_call: Option<FunctionCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_response: Option<FunctionResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thought_signature: Option<String>,
+}
+
+impl Part {
+    fn text(text: impl Into<String>) -> Self {
+        Part {
+            text: Some(text.into()),
+            ..Part::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct FunctionResponse {
+    name: String,
+    response: serde_json::Value,
 }
 
 #[derive(Debug, Serialize)]
 struct GenerationConfig {
     temperature: f32,
     max_output_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_config: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct ThinkingConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_budget: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include_thoughts: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GenerationSettings {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub max_output_tokens: Option<u32>,
+    pub stop_sequences: Option<Vec<String>>,
+    pub thinking_budget: Option<i32>,
+    pub include_thoughts: Option<bool>,
+}
+
+impl GenerationSettings {
+    pub fn or(self, base: GenerationSettings) -> GenerationSettings {
+        GenerationSettings {
+            temperature: self.temperature.or(base.temperature),
+            top_p: self.top_p.or(base.top_p),
+            top_k: self.top_k.or(base.top_k),
+            max_output_tokens: self.max_output_tokens.or(base.max_output_tokens),
+            stop_sequences: self.stop_sequences.or(base.stop_sequences),
+            thinking_budget: self.thinking_budget.or(base.thinking_budget),
+            include_thoughts: self.include_thoughts.or(base.include_thoughts),
+        }
+    }
+
+    fn to_config(&self, model: &str) -> GenerationConfig {
+        GenerationConfig {
+            temperature: self.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            max_output_tokens: self.max_output_tokens.unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS),
+            top_p: self.top_p,
+            top_k: self.top_k,
+            stop_sequences: self.stop_sequences.clone().unwrap_or_default(),
+            thinking_config: (supports_thinking(model)
+                && (self.thinking_budget.is_some() || self.include_thoughts.is_some()))
+            .then_some(ThinkingConfig {
+                thinking_budget: self.thinking_budget,
+                include_thoughts: self.include_thoughts,
+            }),
+            response_mime_type: None,
+            response_schema: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ChatOptions {
+    pub max_continuations: Option<u32>,
+    pub preset: Option<String>,
+    pub generation: Option<GenerationSettings>,
+    pub max_retries: Option<u32>,
+    pub fallback_models: Option<Vec<String>>,
+    pub context_budget: Option<u32>,
+    pub enable_tools: Option<bool>,
+    pub template_id: Option<i64>,
+    pub cache: Option<bool>,
+    pub context_cache: Option<bool>,
+    #[serde(skip)]
+    pub response_schema: Option<serde_json::Value>,
+}
+
+impl ChatOptions {
+    fn generation_config(&self, model: &str) -> GenerationConfig {
+        let mut config = self.generation.clone().unwrap_or_default().to_config(model);
+        if let Some(schema) = &self.response_schema {
+            config.response_mime_type = Some("application/json".to_string());
+            config.response_schema = Some(schema.clone());
+        }
+        config
+    }
+
+    /// An explicit `cache` wins; otherwise only deterministic (temperature 0) requests
+    /// are answered from the response cache.
+    fn use_cache(&self) -> bool {
+        self.cache.unwrap_or_else(|| {
+            self.generation
+                .as_ref()
+                .and_then(|g| g.temperature)
+                .is_some_and(|t| t <= 0.0)
+        })
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn model_chain(&self, model: &str) -> Vec<String> {
+        let mut models = vec![model.to_string()];
+        for fallback in self.fallback_models.iter().flatten() {
+            if !models.contains(fallback) {
+                models.push(fallback.clone());
+            }
+        }
+        models
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: RETRY_BASE_DELAY,
+            max_delay: RETRY_MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait before retry `attempt`, never longer than `max_delay` even with jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        (exponential + jitter(exponential / 2)).min(self.max_delay)
+    }
+}
+
+fn jitter(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let max_millis = max.as_millis() as u64;
+    if max_millis == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(u64::from(nanos) % max_millis)
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+fn is_quota_exhausted(status: reqwest::StatusCode, message: &str) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || message.contains("RESOURCE_EXHAUSTED")
+}
+
+/// The `Retry-After` header, given either as seconds or as an HTTP date.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => Some(
+            parse_http_date(value)?
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        ),
+    }
+}
+
+/// An IMF-fixdate such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut clock = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar.
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+/// `RetryInfo.retryDelay` (for example `"37s"`) from the details of an error body.
+fn retry_delay(details: &[serde_json::Value]) -> Option<Duration> {
+    details
+        .iter()
+        .filter(|detail| {
+            detail["@type"]
+                .as_str()
+                .is_some_and(|t| t.ends_with("google.rpc.RetryInfo"))
+        })
+        .find_map(|detail| detail["retryDelay"].as_str())
+        .and_then(|delay| delay.trim().strip_suffix('s')?.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// What went wrong with a request, so the UI can tell a hiccup worth retrying apart
+/// from a problem with the settings.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Network,
+    RateLimited,
+    Auth,
+    InvalidRequest,
+    Server,
+    Other,
+}
+
+impl ErrorKind {
+    fn classify(code: Option<u16>, api_status: Option<&str>, message: &str) -> Self {
+        if message.contains("API_KEY_INVALID") || message.contains("API key not valid") {
+            return ErrorKind::Auth;
+        }
+        match (code, api_status) {
+            (Some(429), _) | (_, Some("RESOURCE_EXHAUSTED")) => ErrorKind::RateLimited,
+            (Some(401 | 403), _) | (_, Some("UNAUTHENTICATED" | "PERMISSION_DENIED")) => {
+                ErrorKind::Auth
+            }
+            (Some(500..=599), _) | (_, Some("INTERNAL" | "UNAVAILABLE" | "DEADLINE_EXCEEDED")) => {
+                ErrorKind::Server
+            }
+            (Some(400..=499), _) => ErrorKind::InvalidRequest,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Network | ErrorKind::RateLimited | ErrorKind::Server
+        )
+    }
+}
+
+/// A failed generation, carrying whatever part of the answer arrived before it failed.
+#[derive(Debug)]
+pub struct GenerationError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub partial: Option<Box<ChatResponse>>,
+    /// How long the API asked us to wait before trying again.
+    retry_after: Option<Duration>,
+}
+
+impl GenerationError {
+    fn new(kind: ErrorKind, message: String) -> Self {
+        GenerationError {
+            kind,
+            message,
+            partial: None,
+            retry_after: None,
+        }
+    }
+
+    fn with_partial(self, partial: ChatResponse) -> Self {
+        GenerationError {
+            partial: Some(Box::new(partial)),
+            ..self
+        }
+    }
+}
+
+impl From<String> for GenerationError {
+    fn from(message: String) -> Self {
+        GenerationError::new(ErrorKind::Other, message)
+    }
+}
+
+impl From<GeminiError> for GenerationError {
+    fn from(error: GeminiError) -> Self {
+        let kind = ErrorKind::classify(error.code, error.status.as_deref(), &error.message);
+        let message = match error.status {
+            Some(status) => format!("Gemini API error ({}): {}", status, error.message),
+            None => format!("Gemini API error: {}", error.message),
+        };
+        GenerationError {
+            retry_after: retry_delay(&error.details),
+            ..GenerationError::new(kind, message)
+        }
+    }
+}
+
+impl std::fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+struct ApiFailure {
+    error: GenerationError,
+    can_fall_back: bool,
+}
+
+impl ApiFailure {
+    fn fatal(error: GenerationError) -> Self {
+        ApiFailure {
+            error,
+            can_fall_back: false,
+        }
+    }
+}
+
+const MAX_ERROR_BODY_CHARS: usize = 200;
+
+async fn api_error(response: reqwest::Response) -> GenerationError {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    // Streaming requests report errors as a JSON array of responses.
+    let error = serde_json::from_str::<GeminiResponse>(&body)
+        .ok()
+        .or_else(|| {
+            serde_json::from_str::<Vec<GeminiResponse>>(&body)
+                .ok()
+                .and_then(|responses| responses.into_iter().next())
+        })
+        .and_then(|response| response.error);
+
+    match error {
+        Some(error) => GenerationError {
+            kind: ErrorKind::classify(
+                Some(error.code.unwrap_or(status.as_u16())),
+                error.status.as_deref(),
+                &error.message,
+            ),
+            ..GenerationError::from(error)
+        },
+        None => {
+            let body: String = body.trim().chars().take(MAX_ERROR_BODY_CHARS).collect();
+            GenerationError::new(
+                ErrorKind::classify(Some(status.as_u16()), None, &body),
+                format!("API error: {} {}", status, body).trim_end().to_string(),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CountTokensRequest<'a> {
+    contents: &'a [Content],
+}
+
+#[derive(Debug, Deserialize)]
+struct CountTokensResponse {
+    #[serde(rename = "totalTokens")]
+    total_tokens: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedContentRequest<'a> {
+    model: String,
+    content: Content,
+    task_type: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchEmbedContentsRequest<'a> {
+    requests: Vec<EmbedContentRequest<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchEmbedContentsResponse {
+    #[serde(default)]
+    embeddings: Vec<ContentEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentEmbedding {
+    #[serde(default)]
+    values: Vec<f32>,
+}
+
+/// Vectors for a batch of texts. `response` carries the model, key and estimated input
+/// tokens so usage tracking can record the request like any other call.
+pub struct Embeddings {
+    pub vectors: Vec<Vec<f32>>,
+    pub response: ChatResponse,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenCount {
+    pub tokens: u32,
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ChatResponse {
+    pub text: String,
+    pub model: String,
+    pub api_key_label: String,
+    pub usage: TokenUsage,
+    pub tool_calls: Vec<ToolInvocation>,
+    #[serde(default)]
+    pub thoughts: String,
+    #[serde(default)]
+    pub cached: bool,
+    /// Why the context cache could not be used for this answer; it was sent inline.
+    #[serde(skip)]
+    pub context_cache_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Round {
+    text: String,
+    thoughts: String,
+    parts: Vec<Part>,
+    function_calls: Vec<FunctionCall>,
+    truncated: bool,
+    finish_reason: Option<String>,
+    usage: Option<UsageMetadata>,
+}
+
+impl Round {
+    fn absorb(
+        &mut self,
+        response: GeminiResponse,
+        on_event: Option<&(dyn Fn(StreamEvent) + Send + Sync)>,
+    ) -> Result<(), GenerationError> {
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+        if response.usage_metadata.is_some() {
+            self.usage = response.usage_metadata;
+        }
+        if let Some(reason) = response.prompt_feedback.and_then(|f| f.block_reason) {
+            self.finish_reason = Some(format!("prompt blocked: {}", reason));
+        }
+
+        let Some(candidate) = response.candidates.and_then(|c| c.into_iter().next()) else {
+            return Ok(());
+        };
+        self.truncated |= candidate.hit_token_limit();
+        if candidate.finish_reason.is_some() {
+            self.finish_reason = candidate.finish_reason.clone();
+        }
+
+        for part in candidate.content.map(|c| c.parts).unwrap_or_default() {
+            // Thought summaries are shown apart from the answer and never sent back.
+            if part.thought {
+                if !part.text.is_empty() {
+                    self.thoughts.push_str(&part.text);
+                    if let Some(on_event) = on_event {
+                        on_event(StreamEvent::thought(part.text));
+                    }
+                }
+                continue;
+            }
+            if let Some(call) = part.function_call {
+                self.function_calls.push(call.clone());
+                self.parts.push(Part {
+                    function_call: Some(call),
+                    thought_signature: part.thought_signature,
+                    ..Part::default()
+                });
+                continue;
+            }
+            if part.text.is_empty() && part.thought_signature.is_none() {
+                continue;
+            }
+
+            self.text.push_str(&part.text);
+            if let Some(on_event) = on_event {
+                on_event(StreamEvent::answer(part.text.clone()));
+            }
+            self.parts.push(Part {
+                thought_signature: part.thought_signature,
+                ..Part::text(part.text)
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn supports_thinking(model: &str) -> bool {
+    ["gemini-2.5", "gemini-3"]
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
 }
 
 #[derive(Debug, Deserialize)]
 struct GeminiResponse {
     candidates: Option<Vec<Candidate>>,
     error: Option<GeminiError>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<PromptFeedback>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct UsageMetadata {
+    #[serde(rename = "promptTokenCount", default)]
+    prompt_token_count: u32,
+    #[serde(rename = "candidatesTokenCount", default)]
+    candidates_token_count: u32,
+    #[serde(rename = "thoughtsTokenCount", default)]
+    thoughts_token_count: u32,
+    #[serde(rename = "cachedContentTokenCount", default)]
+    cached_content_token_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub output_tokens: u32,
+    pub thinking_tokens: u32,
+    #[serde(default)]
+    pub cached_tokens: u32,
+}
+
+impl TokenUsage {
+    fn add(&mut self, metadata: &UsageMetadata) {
+        self.prompt_tokens += metadata.prompt_token_count;
+        self.output_tokens += metadata.candidates_token_count;
+        self.thinking_tokens += metadata.thoughts_token_count;
+        self.cached_tokens += metadata.cached_content_token_count;
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Candidate {
-    content: CandidateContent,
+    content: Option<CandidateContent>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
+}
+
+impl Candidate {
+    fn hit_token_limit(&self) -> bool {
+        self.finish_reason.as_deref() == Some("MAX_TOKENS")
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct CandidateContent {
+    #[serde(default)]
     parts: Vec<ResponsePart>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ResponsePart {
+    #[serde(default)]
     text: String,
+    #[serde(default)]
+    thought: bool,
+    #[serde(rename = "functionCall")]
+    function_call: Option<FunctionCall>,
+    #[serde(rename = "thoughtSignature")]
+    thought_signature: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct GeminiError {
+    code: Option<u16>,
     message: String,
+    status: Option<String>,
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolInvocation>,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatMessage {
+            role: "user".to_string(),
+            content: content.into(),
+            tool_calls: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PartKind {
+    Answer,
+    Thought,
 }
 
+/// Events sent while a reply streams in. Every stream ends with exactly one `Done`
+/// or `Error`, so listeners never wait on a reply that already failed.
 #[derive(Debug, Clone, Serialize)]
-pub struct StreamChunk {
-    pub text: String,
-    pub done: bool,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    Delta {
+        text: String,
+        kind: PartKind,
+    },
+    Usage {
+        usage: TokenUsage,
+    },
+    Error {
+        kind: ErrorKind,
+        message: String,
+        retryable: bool,
+        /// The answer streamed before the failure, if any.
+        partial: Option<ChatResponse>,
+    },
+    Done,
 }
 
-pub async fn fetch_available_models(api_key: &str) -> Result<Vec<GeminiModel>, String> {
-    let client = reqwest::Client::new();
-    let url = format!("{}?key={}", GEMINI_API_BASE, api_key);
-    
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch models: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(format!("API error: {}", response.status()));
+impl StreamEvent {
+    fn answer(text: impl Into<String>) -> Self {
+        StreamEvent::Delta {
+            text: text.into(),
+            kind: PartKind::Answer,
+        }
     }
-    
-    let list_response: ListModelsResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse models: {}", e))?;
-    
-    let models = list_response
-        .models
-        .into_iter()
-        .map(|m| GeminiModel {
-            name: m.name.replace("models/", ""),
-            display_name: m.display_name,
-        })
-        .collect();
-    
-    Ok(models)
-}
 
-fn build_contents(messages: Vec<ChatMessage>, system_prompt: &str) -> Vec<Content> {
-    let mut contents: Vec<Content> = vec![];
-    
-    if !system_prompt.is_empty() {
-        contents.push(Content {
-            role: "user".to_string(),
-            parts: vec![Part {
-                text: format!("[System Instructions]\n{}\n\n[End System Instructions]", system_prompt),
-            }],
-        });
-        contents.push(Content {
-            role: "model".to_string(),
-            parts: vec![Part {
-                text: "I understand. I'll follow these instructions.".to_string(),
-            }],
-        });
+    fn thought(text: impl Into<String>) -> Self {
+        StreamEvent::Delta {
+            text: text.into(),
+            kind: PartKind::Thought,
+        }
     }
-    
-    for msg in messages {
-        contents.push(Content {
-            role: if msg.role == "user" { "user".to_string() } else { "model".to_string() },
-            parts: vec![Part { text: msg.content }],
-        });
+
+    pub fn error(error: &GenerationError) -> Self {
+        StreamEvent::Error {
+            kind: error.kind,
+            message: redact::redact(&error.message),
+            retryable: error.kind.is_retryable(),
+            partial: error.partial.as_deref().cloned(),
+        }
     }
-    
-    contents
 }
 
-pub async fn chat(
+/// Every constructor takes the `reqwest::Client` to send requests with, normally the
+/// shared client from the network settings, so none can skip the proxy and timeouts.
+pub struct GeminiClient {
+    http: reqwest::Client,
+    base_url: String,
+    keys: Arc<KeyRing>,
+    cache: Option<ResponseCache>,
+    context_caches: Option<ContextCaches>,
+    problem_context: Option<String>,
+}
 
-    api_key: &str,
-    model: &str,
-    messages: Vec<ChatMessage>,
-    system_prompt: &str,
-) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let contents = build_contents(messages, system_prompt);
-    
-    let request = GeminiRequest {
-        contents,
-        generation_config: GenerationConfig {
-            temperature: 0.7,
-            max_output_tokens: 8192,
-        },
-    };
-    
-    let url = format!("{}/{}:generateContent?key={}", GEMINI_API_BASE, model, api_key);
-    
-    let response = client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-    
-    let gemini_response: GeminiResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-    
-    if let Some(error) = gemini_response.error {
-        return Err(format!("Gemini API error: {}", error.message));
+impl GeminiClient {
+    pub fn new(http: reqwest::Client, api_key: &str) -> Self {
+        Self::with_base_url(http, api_key, GEMINI_API_BASE)
     }
-    
-    gemini_response
-        .candidates
-        .and_then(|c| c.into_iter().next())
-        .and_then(|c| c.content.parts.into_iter().next())
-        .map(|p| p.text)
-        .ok_or_else(|| "No response from Gemini".to_string())
-}
-
-pub async fn chat_stream(
-    app: AppHandle,
-    api_key: &str,
-    model: &str,
-    messages: Vec<ChatMessage>,
-    system_prompt: &str,
-    session_id: String,
-) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let contents = build_contents(messages, system_prompt);
-    
-    let request = GeminiRequest {
-        contents,
-        generation_config: GenerationConfig {
-            temperature: 0.7,
-            max_output_tokens: 8192,
-        },
+
+    pub fn with_keys(http: reqwest::Client, keys: Arc<KeyRing>) -> Self {
+        Self::with_keys_and_base_url(http, keys, GEMINI_API_BASE)
+    }
+
+    pub fn with_base_url(http: reqwest::Client, api_key: &str, base_url: &str) -> Self {
+        Self::with_keys_and_base_url(http, Arc::new(KeyRing::single(api_key)), base_url)
+    }
+
+    pub fn with_keys_and_base_url(
+        http: reqwest::Client,
+        keys: Arc<KeyRing>,
+        base_url: &str,
+    ) -> Self {
+        GeminiClient {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            keys,
+            cache: None,
+            context_caches: None,
+            problem_context: None,
+        }
+    }
+
+    pub fn with_context_caches(mut self, context_caches: ContextCaches) -> Self {
+        self.context_caches = Some(context_caches);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Sends the problem statement ahead of every conversation, so callers pass only
+    /// the turns themselves and compaction never has to guess where the statement is.
+    pub fn with_problem_context(mut self, problem_context: Option<String>) -> Self {
+        self.problem_context = problem_context.filter(|context| !context.trim().is_empty());
+        self
+    }
+
+    /// A local estimate of a request's size, including the problem context.
+    pub fn estimate_request_tokens(&self, messages: &[ChatMessage], system_prompt: &str) -> u32 {
+        estimate_message_tokens(messages, system_prompt)
+            + self.problem_context.as_deref().map_or(0, estimate_tokens)
+    }
+
+    fn method_url(&self, model: &str, method: &str) -> String {
+        format!("{}/{}:{}", self.base_url, model, method)
+    }
+
+    fn api_root(&self) -> &str {
+        self.base_url
+            .strip_suffix("/models")
+            .unwrap_or(&self.base_url)
+    }
+
+    fn cached_contents_url(&self) -> String {
+        format!("{}/cachedContents", self.api_root())
+    }
+
+    /// Caches the system prompt and the problem statement, the parts every turn of a
+    /// conversation repeats.
+    async fn create_cached_context(
+        &self,
+        caches: &ContextCaches,
+        cache_key: &str,
+        model: &str,
+        system_prompt: &str,
+    ) -> Result<CachedContext, String> {
+        let key = self.keys.next().ok_or_else(|| "API key is not set".to_string())?;
+        let request = CreateCachedContentRequest {
+            model: format!("models/{}", model),
+            system_instruction: (!system_prompt.is_empty()).then(|| SystemInstruction {
+                parts: vec![Part::text(system_prompt)],
+            }),
+            contents: build_contents(&[], "", self.problem_context.as_deref()),
+            ttl: format!("{}s", context_cache::CACHE_TTL.as_secs()),
+        };
+        let response = self
+            .http
+            .post(self.cached_contents_url())
+            .header(API_KEY_HEADER, &key.secret)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to create context cache: {}", e))?;
+        if !response.status().is_success() {
+            return Err(api_error(response).await.message);
+        }
+        let created: CachedContentResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse context cache: {}", e))?;
+
+        Ok(caches.insert(cache_key, created.name, key.label, context_cache::CACHE_TTL))
+    }
+
+    /// The context cache holding `system_prompt` and the problem context, created on
+    /// first use. `None` means both are sent inline: caching is off, they are too small,
+    /// or the model refused to cache them recently. An error is a failed create.
+    async fn cached_context(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        options: &ChatOptions,
+        tools: Option<&dyn ToolExecutor>,
+    ) -> Result<Option<(String, CachedContext)>, String> {
+        let Some(caches) = self.context_caches.as_ref() else {
+            return Ok(None);
+        };
+        let problem_context = self.problem_context.as_deref().unwrap_or_default();
+        if !options.context_cache.unwrap_or(true)
+            || tools.is_some()
+            || estimate_tokens(system_prompt) + estimate_tokens(problem_context)
+                < context_cache::MIN_CACHE_TOKENS
+        {
+            return Ok(None);
+        }
+
+        let cache_key = ContextCaches::key(model, system_prompt, problem_context);
+        match caches.lookup(&cache_key) {
+            Lookup::Ready(context) => Ok(Some((cache_key, context))),
+            Lookup::Unavailable => Ok(None),
+            Lookup::Missing => {
+                match self
+                    .create_cached_context(caches, &cache_key, model, system_prompt)
+                    .await
+                {
+                    Ok(context) => Ok(Some((cache_key, context))),
+                    Err(error) => {
+                        caches.mark_unavailable(&cache_key);
+                        Err(error)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Deletes caches that were replaced or dropped before their TTL ran out. This is
+    /// best effort: Gemini removes any that are missed once they expire.
+    async fn delete_retired_contexts(&self) {
+        let Some(caches) = &self.context_caches else {
+            return;
+        };
+        for context in caches.take_retired() {
+            let Some(key) = self.keys.get(&context.key_label) else {
+                continue;
+            };
+            let _ = self
+                .http
+                .delete(format!("{}/{}", self.api_root(), context.name))
+                .header(API_KEY_HEADER, &key.secret)
+                .send()
+                .await;
+        }
+    }
+
+    /// Chat-capable models only; see `fetch_all_models` for the full list.
+    pub async fn fetch_available_models(&self) -> Result<Vec<GeminiModel>, String> {
+        let models = self.fetch_all_models().await?;
+        Ok(models.into_iter().filter(GeminiModel::can_chat).collect())
+    }
+
+    pub async fn fetch_all_models(&self) -> Result<Vec<GeminiModel>, String> {
+        let mut models = vec![];
+        let mut page_token: Option<String> = None;
+
+        for _ in 0..MAX_MODEL_PAGES {
+            let key = self.keys.next().ok_or_else(|| "API key is not set".to_string())?;
+            let mut query = vec![("pageSize", MODELS_PAGE_SIZE)];
+            if let Some(token) = &page_token {
+                query.push(("pageToken", token));
+            }
+            let response = self
+                .http
+                .get(&self.base_url)
+                .header(API_KEY_HEADER, &key.secret)
+                .query(&query)
+                .send()
+                .await
+                .map_err(|e| format!("Failed to fetch models: {}", e))?;
+
+            if !response.status().is_success() {
+                if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    self.keys.cool_down(&key.label, retry_after(&response));
+                }
+                return Err(format!("API error: {}", response.status()));
+            }
+
+            let list_response: ListModelsResponse = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse models: {}", e))?;
+            models.extend(list_response.models.into_iter().map(GeminiModel::from));
+
+            page_token = list_response.next_page_token.filter(|t| !t.is_empty());
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(models)
+    }
+
+    async fn post_with_retry<T: Serialize + ?Sized>(
+        &self,
+        model: &str,
+        method: &str,
+        query: &[(&str, &str)],
+        body: &T,
+        policy: &RetryPolicy,
+        pinned_key: Option<&str>,
+    ) -> Result<(reqwest::Response, String), ApiFailure> {
+        let url = self.method_url(model, method);
+        let mut attempt = 0;
+        let mut rotations = 0;
+
+        loop {
+            let key = match pinned_key {
+                Some(label) => self.keys.get(label),
+                None => self.keys.next(),
+            }
+            .ok_or_else(|| ApiFailure::fatal("API key is not set".to_string().into()))?;
+            let result = self
+                .http
+                .post(&url)
+                .header(API_KEY_HEADER, &key.secret)
+                .query(query)
+                .json(body)
+                .send()
+                .await;
+
+            let (error, wait) = match result {
+                Ok(response) if response.status().is_success() => return Ok((response, key.label)),
+                Ok(response) if is_retryable_status(response.status()) => {
+                    let status = response.status();
+                    let header_wait = retry_after(&response);
+                    let error = api_error(response).await;
+                    let wait = header_wait.or(error.retry_after);
+                    // Bench the exhausted key and move straight on if another key is ready.
+                    if pinned_key.is_none() && is_quota_exhausted(status, &error.message) {
+                        self.keys.cool_down(&key.label, wait);
+                        if rotations < self.keys.len() && self.keys.has_ready_key() {
+                            rotations += 1;
+                            continue;
+                        }
+                    }
+                    (error, wait)
+                }
+                Ok(response) => return Err(ApiFailure::fatal(api_error(response).await)),
+                Err(e) if e.is_connect() || e.is_timeout() => (
+                    GenerationError::new(
+                        ErrorKind::Network,
+                        format!("Failed to send request: {}", e),
+                    ),
+                    None,
+                ),
+                Err(e) => {
+                    return Err(ApiFailure::fatal(GenerationError::new(
+                        ErrorKind::Network,
+                        format!("Failed to send request: {}", e),
+                    )))
+                }
+            };
+
+            // Only a wait the server asked for can exceed the cap; it means this model is
+            // out for longer than a retry is worth, so the caller moves to a fallback.
+            let too_long = wait.is_some_and(|wait| wait > policy.max_delay);
+            let wait = wait.unwrap_or_else(|| policy.backoff(attempt));
+            if attempt >= policy.max_retries || too_long {
+                return Err(ApiFailure {
+                    error,
+                    can_fall_back: true,
+                });
+            }
+
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    async fn post_with_fallback<T: Serialize + ?Sized>(
+        &self,
+        models: &[String],
+        method: &str,
+        query: &[(&str, &str)],
+        body: &T,
+        policy: &RetryPolicy,
+        pinned_key: Option<&str>,
+    ) -> Result<(reqwest::Response, String, String), GenerationError> {
+        let mut last_error = GenerationError::from("No model available".to_string());
+
+        for model in models {
+            match self
+                .post_with_retry(model, method, query, body, policy, pinned_key)
+                .await
+            {
+                Ok((response, key_label)) => return Ok((response, model.clone(), key_label)),
+                Err(failure) if failure.can_fall_back => last_error = failure.error,
+                Err(failure) => return Err(failure.error),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Embeddings of `texts`, in one request, meant for comparing them with each other.
+    pub async fn embed(&self, model: &str, texts: &[String]) -> Result<Embeddings, String> {
+        let request = BatchEmbedContentsRequest {
+            requests: texts
+                .iter()
+                .map(|text| EmbedContentRequest {
+                    model: format!("models/{}", model),
+                    content: Content {
+                        role: "user".to_string(),
+                        parts: vec![Part::text(text)],
+                    },
+                    task_type: "SEMANTIC_SIMILARITY",
+                })
+                .collect(),
+        };
+        let (response, api_key_label) = self
+            .post_with_retry(
+                model,
+                "batchEmbedContents",
+                &[],
+                &request,
+                &RetryPolicy::default(),
+                None,
+            )
+            .await
+            .map_err(|failure| failure.error.message)?;
+        let embedded: BatchEmbedContentsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse embeddings: {}", e))?;
+        if embedded.embeddings.len() != texts.len()
+            || embedded.embeddings.iter().any(|e| e.values.is_empty())
+        {
+            return Err("Incomplete embeddings from Gemini".to_string());
+        }
+
+        // Embedding responses carry no usage metadata, so the input is estimated.
+        Ok(Embeddings {
+            vectors: embedded.embeddings.into_iter().map(|e| e.values).collect(),
+            response: ChatResponse {
+                model: model.to_string(),
+                api_key_label,
+                usage: TokenUsage {
+                    prompt_tokens: texts.iter().map(|text| estimate_tokens(text)).sum(),
+                    ..TokenUsage::default()
+                },
+                ..ChatResponse::default()
+            },
+        })
+    }
+
+    pub async fn count_tokens(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        system_prompt: &str,
+    ) -> TokenCount {
+        let contents = build_contents(messages, system_prompt, self.problem_context.as_deref());
+        let policy = RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        };
+        let request = CountTokensRequest {
+            contents: &contents,
+        };
+
+        if let Ok((response, _)) = self
+            .post_with_retry(model, "countTokens", &[], &request, &policy, None)
+            .await
+        {
+            if let Ok(count) = response.json::<CountTokensResponse>().await {
+                return TokenCount {
+                    tokens: count.total_tokens,
+                    estimated: false,
+                };
+            }
+        }
+
+        TokenCount {
+            tokens: self.estimate_request_tokens(messages, system_prompt),
+            estimated: true,
+        }
+    }
+
+    pub async fn chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        system_prompt: &str,
+        options: &ChatOptions,
+        tools: Option<&dyn ToolExecutor>,
+    ) -> Result<ChatResponse, String> {
+        self.generate(model, messages, system_prompt, options, tools, None)
+            .await
+            .map_err(|e| e.message)
+    }
+
+    pub async fn chat_stream(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        system_prompt: &str,
+        options: &ChatOptions,
+        tools: Option<&dyn ToolExecutor>,
+        on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+    ) -> Result<ChatResponse, String> {
+        match self
+            .generate(model, messages, system_prompt, options, tools, Some(on_event))
+            .await
+        {
+            Ok(response) => {
+                on_event(StreamEvent::Usage {
+                    usage: response.usage.clone(),
+                });
+                on_event(StreamEvent::Done);
+                Ok(response)
+            }
+            Err(error) => {
+                on_event(StreamEvent::error(&error));
+                Err(error.message)
+            }
+        }
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        system_prompt: &str,
+        options: &ChatOptions,
+        tools: Option<&dyn ToolExecutor>,
+        on_event: Option<&(dyn Fn(StreamEvent) + Send + Sync)>,
+    ) -> Result<ChatResponse, GenerationError> {
+        // Tool calls run the user's code, so their answers are never replayed.
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|_| tools.is_none() && options.use_cache());
+        let cache_key = cache.map(|_| {
+            response_cache::key(&[
+                model,
+                &serde_json::to_string(&build_contents(
+                    &messages,
+                    system_prompt,
+                    self.problem_context.as_deref(),
+                ))
+                .unwrap_or_default(),
+                &serde_json::to_string(&options.generation_config(model)).unwrap_or_default(),
+                &options
+                    .max_continuations
+                    .unwrap_or(DEFAULT_MAX_CONTINUATIONS)
+                    .to_string(),
+            ])
+        });
+        if let (Some(cache), Some(key)) = (cache, &cache_key) {
+            if let Some(response) = cache.get(key) {
+                if let Some(on_event) = on_event {
+                    if !response.thoughts.is_empty() {
+                        on_event(StreamEvent::thought(response.thoughts.clone()));
+                    }
+                    on_event(StreamEvent::answer(response.text.clone()));
+                }
+                return Ok(ChatResponse {
+                    usage: TokenUsage::default(),
+                    cached: true,
+                    ..response
+                });
+            }
+        }
+
+        let (context, mut context_cache_error) = match self
+            .cached_context(model, system_prompt, options, tools)
+            .await
+        {
+            Ok(context) => (context, None),
+            Err(error) => (
+                None,
+                Some(format!("Context caching unavailable: {}", error)),
+            ),
+        };
+        let result = match &context {
+            Some((context_key, context)) => {
+                let emitted = AtomicBool::new(false);
+                let tracked = |event: StreamEvent| {
+                    emitted.store(true, Ordering::Relaxed);
+                    if let Some(on_event) = on_event {
+                        on_event(event);
+                    }
+                };
+                let tracked_event =
+                    on_event.map(|_| &tracked as &(dyn Fn(StreamEvent) + Send + Sync));
+                let result = self
+                    .generate_turns(
+                        model,
+                        &messages,
+                        system_prompt,
+                        options,
+                        tools,
+                        Some(context),
+                        tracked_event,
+                    )
+                    .await;
+                match result {
+                    // The cache may have expired early or been deleted; resend the prompt
+                    // inline unless part of the answer already reached the user.
+                    Err(error) if !emitted.load(Ordering::Relaxed) => {
+                        context_cache_error = Some(format!(
+                            "Cached context request failed, retried inline: {}",
+                            error
+                        ));
+                        if let Some(caches) = &self.context_caches {
+                            caches.invalidate(context_key);
+                        }
+                        self.generate_turns(
+                            model,
+                            &messages,
+                            system_prompt,
+                            options,
+                            tools,
+                            None,
+                            on_event,
+                        )
+                        .await
+                    }
+                    result => result,
+                }
+            }
+            None => {
+                self.generate_turns(
+                    model,
+                    &messages,
+                    system_prompt,
+                    options,
+                    tools,
+                    None,
+                    on_event,
+                )
+                .await
+            }
+        };
+
+        self.delete_retired_contexts().await;
+        let result = result.map(|response| ChatResponse {
+            context_cache_error,
+            ..response
+        });
+
+        if let (Ok(response), Some(cache), Some(key)) = (&result, cache, &cache_key) {
+            if !response.text.is_empty() {
+                let _ = cache.put(key, response);
+            }
+        }
+        result
+    }
+
+    /// Sends the conversation, running tool calls and continuations until the answer
+    /// is complete. With a cached context the system prompt and problem context are
+    /// referenced instead of resent, which pins the request to the model and key that
+    /// own the cache.
+    #[allow(clippy::too_many_arguments)]
+    async fn generate_turns(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        system_prompt: &str,
+        options: &ChatOptions,
+        tools: Option<&dyn ToolExecutor>,
+        context: Option<&CachedContext>,
+        on_event: Option<&(dyn Fn(StreamEvent) + Send + Sync)>,
+    ) -> Result<ChatResponse, GenerationError> {
+        let mut answer = ChatResponse {
+            model: model.to_string(),
+            ..ChatResponse::default()
+        };
+        let result = self
+            .run_turns(
+                &mut answer,
+                messages,
+                system_prompt,
+                options,
+                tools,
+                context,
+                on_event,
+            )
+            .await;
+
+        match result {
+            Err(error) => Err(error.with_partial(answer)),
+            Ok(finish_reason) if answer.text.is_empty() => {
+                let message = match finish_reason {
+                    Some(reason) => format!("No response from Gemini (finish reason: {})", reason),
+                    None => "No response from Gemini".to_string(),
+                };
+                Err(GenerationError::from(message).with_partial(answer))
+            }
+            Ok(_) => Ok(answer),
+        }
+    }
+
+    /// The loop behind `generate_turns`. Everything received is added to `answer` as
+    /// it arrives, so a failure part way through still leaves the partial answer.
+    /// Returns why the last reply finished, when Gemini said.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_turns(
+        &self,
+        answer: &mut ChatResponse,
+        messages: &[ChatMessage],
+        system_prompt: &str,
+        options: &ChatOptions,
+        tools: Option<&dyn ToolExecutor>,
+        context: Option<&CachedContext>,
+        on_event: Option<&(dyn Fn(StreamEvent) + Send + Sync)>,
+    ) -> Result<Option<String>, GenerationError> {
+        let mut contents = match context {
+            Some(_) => build_contents(messages, "", None),
+            None => build_contents(messages, system_prompt, self.problem_context.as_deref()),
+        };
+        let max_continuations = options.max_continuations.unwrap_or(DEFAULT_MAX_CONTINUATIONS);
+        let policy = options.retry_policy();
+        let declarations = tools.map(|t| t.declarations()).unwrap_or_default();
+        let (method, query): (&str, &[(&str, &str)]) = match on_event {
+            Some(_) => ("streamGenerateContent", &[("alt", "sse")]),
+            None => ("generateContent", &[]),
+        };
+
+        let mut models = match context {
+            Some(_) => vec![answer.model.clone()],
+            None => options.model_chain(&answer.model),
+        };
+        let mut continuations = 0;
+        let mut tool_rounds = 0;
+
+        loop {
+            let request = GeminiRequest {
+                contents: &contents,
+                generation_config: options.generation_config(&models[0]),
+                tools: if declarations.is_empty() {
+                    vec![]
+                } else {
+                    vec![ToolSet {
+                        function_declarations: declarations.clone(),
+                    }]
+                },
+                // The declarations stay so earlier calls in `contents` remain valid;
+                // past the cap the model is only told to stop calling them.
+                tool_config: (!declarations.is_empty() && tool_rounds >= MAX_TOOL_ROUNDS)
+                    .then_some(ToolConfig {
+                        function_calling_config: FunctionCallingConfig { mode: "NONE" },
+                    }),
+                cached_content: context.map(|c| c.name.as_str()),
+            };
+
+            let (response, used_model, key_label) = self
+                .post_with_fallback(
+                    &models,
+                    method,
+                    query,
+                    &request,
+                    &policy,
+                    context.map(|c| c.key_label.as_str()),
+                )
+                .await?;
+            answer.model = used_model.clone();
+            answer.api_key_label = key_label;
+            models = vec![used_model];
+
+            let mut round = Round::default();
+            let read = match on_event {
+                Some(on_event) => read_stream(response, &mut round, on_event).await,
+                None => match response.json::<GeminiResponse>().await {
+                    Ok(gemini_response) => round.absorb(gemini_response, None),
+                    Err(e) => Err(format!("Failed to parse response: {}", e).into()),
+                },
+            };
+
+            if let Some(metadata) = &round.usage {
+                answer.usage.add(metadata);
+            }
+            answer.text.push_str(&round.text);
+            answer.thoughts.push_str(&round.thoughts);
+            read?;
+            if tool_rounds >= MAX_TOOL_ROUNDS && !round.function_calls.is_empty() {
+                return Err(format!(
+                    "Gemini kept calling tools after {} rounds",
+                    MAX_TOOL_ROUNDS
+                )
+                .into());
+            }
+
+            if !round.parts.is_empty() {
+                contents.push(Content {
+                    role: "model".to_string(),
+                    parts: round.parts,
+                });
+            }
+
+            if let (Some(tools), false) = (tools, round.function_calls.is_empty()) {
+                let mut responses = vec![];
+                for call in round.function_calls {
+                    let result = tools.call(&call.name, &call.args).await;
+                    responses.push(Part {
+                        function_response: Some(FunctionResponse {
+                            name: call.name.clone(),
+                            response: result.clone(),
+                        }),
+                        ..Part::default()
+                    });
+                    answer.tool_calls.push(ToolInvocation {
+                        name: call.name,
+                        args: call.args,
+                        result,
+                    });
+                }
+                contents.push(Content {
+                    role: "user".to_string(),
+                    parts: responses,
+                });
+                tool_rounds += 1;
+                continue;
+            }
+
+            if !round.truncated || continuations >= max_continuations {
+                return Ok(round.finish_reason);
+            }
+            contents.push(Content {
+                role: "user".to_string(),
+                parts: vec![Part::text(CONTINUE_PROMPT)],
+            });
+            continuations += 1;
+        }
+    }
+}
+
+/// Reads an SSE reply into `round`. On failure `round` keeps what arrived before it.
+async fn read_stream(
+    response: reqwest::Response,
+    round: &mut Round,
+    on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+) -> Result<(), GenerationError> {
+    let mut stream = response.bytes_stream();
+    let mut pending: Vec<u8> = Vec::new();
+
+    while let Some(chunk_result) = stream.next().await {
+        let bytes = chunk_result.map_err(|e| {
+            GenerationError::new(ErrorKind::Network, format!("Stream error: {}", e))
+        })?;
+        pending.extend_from_slice(&bytes);
+
+        // A network chunk can end anywhere, even inside a UTF-8 character, so only
+        // complete lines are parsed and the rest waits for the next chunk.
+        while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            absorb_sse_line(round, &line, on_event)?;
+        }
+    }
+    absorb_sse_line(round, &pending, on_event)
+}
+
+fn absorb_sse_line(
+    round: &mut Round,
+    line: &[u8],
+    on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+) -> Result<(), GenerationError> {
+    let line = String::from_utf8_lossy(line);
+    let Some(data) = line.trim_end().strip_prefix("data:") else {
+        return Ok(());
     };
+    match serde_json::from_str::<GeminiResponse>(data.trim_start()) {
+        Ok(response) => round.absorb(response, Some(on_event)),
+        Err(_) => Ok(()),
+    }
+}
+
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    let trimmed = text.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(trimmed);
+
+    serde_json::from_str(unfenced.trim()).map_err(|e| format!("Invalid JSON from Gemini: {}", e))
+}
+
+pub fn estimate_tokens(text: &str) -> u32 {
+    let (ascii, other) = text.chars().fold((0u32, 0u32), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+    ascii.div_ceil(4) + other
+}
+
+pub fn estimate_message_tokens(messages: &[ChatMessage], system_prompt: &str) -> u32 {
+    messages
+        .iter()
+        .map(|m| estimate_tokens(&m.content) + MESSAGE_TOKEN_OVERHEAD)
+        .sum::<u32>()
+        + estimate_tokens(system_prompt)
+}
+
+fn build_contents(
+    messages: &[ChatMessage],
+    system_prompt: &str,
+    problem_context: Option<&str>,
+) -> Vec<Content> {
+    let mut contents: Vec<Content> = vec![];
     
-    let url = format!("{}/{}:streamGenerateContent?key={}&alt=sse", GEMINI_API_BASE, model, api_key);
-    
-    let response = client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-    
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API error: {}", error_text));
+    if !system_prompt.is_empty() {
+        contents.push(Content {
+            role: "user".to_string(),
+            parts: vec![Part::text(format!(
+                "[System Instructions]\n{}\n\n[End System Instructions]",
+                system_prompt
+            ))],
+        });
+        contents.push(Content {
+            role: "model".to_string(),
+            parts: vec![Part::text("I understand. I'll follow these instructions.")],
+        });
+    }
+
+    if let Some(problem_context) = problem_context {
+        contents.push(Content {
+            role: "user".to_string(),
+            parts: vec![Part::text(problem_context)],
+        });
+        contents.push(Content {
+            role: "model".to_string(),
+            parts: vec![Part::text("I have read the problem.")],
+        });
     }
     
-    let mut full_response = String::new();
-    let mut stream = response.bytes_stream();
-    let event_name = format!("chat-stream-{}", session_id);
+    for msg in messages {
+        if msg.role != "user" && !msg.tool_calls.is_empty() {
+            contents.push(Content {
+                role: "model".to_string(),
+                parts: msg
+                    .tool_calls
+                    .iter()
+                    .map(|call| Part {
+                        function_call: Some(FunctionCall {
+                            name: call.name.clone(),
+                            args: call.args.clone(),
+                        }),
+                        ..Part::default()
+                    })
+                    .collect(),
+            });
+            contents.push(Content {
+                role: "user".to_string(),
+                parts: msg
+                    .tool_calls
+                    .iter()
+                    .map(|call| Part {
+                        function_response: Some(FunctionResponse {
+                            name: call.name.clone(),
+                            response: call.result.clone(),
+                        }),
+                        ..Part::default()
+                    })
+                    .collect(),
+            });
+        }
+
+        contents.push(Content {
+            role: if msg.role == "user" { "user".to_string() } else { "model".to_string() },
+            parts: vec![Part::text(msg.content.clone())],
+        });
+    }
     
-    while let Some(chunk_result) = stream.next().await {
-        match chunk_result {
-            Ok(bytes) => {
-                let text = String::from_utf8_lossy(&bytes);
-                
-                for line in text.lines() {
-                    if let Some(data) = line.strip_prefix("data: ") {
-                        if let Ok(response) = serde_json::from_str::<GeminiResponse>(data) {
-                            if let Some(candidates) = response.candidates {
-                                for candidate in candidates {
-                                    for part in candidate.content.parts {
-                                        full_response.push_str(&part.text);
-                                        let _ = app.emit(&event_name, StreamChunk {
-                                            text: part.text,
-                                            done: false,
-                                        });
-                                    }
-                                }
-                            }
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyring::ApiKey;
+    use crate::redact;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    struct MockResponse {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: String,
+        fragment_size: Option<usize>,
+    }
+
+    impl MockResponse {
+        fn json(status: u16, body: &str) -> Self {
+            MockResponse {
+                status,
+                headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+                body: body.to_string(),
+                fragment_size: None,
+            }
+        }
+
+        fn sse(events: &[&str]) -> Self {
+            MockResponse {
+                status: 200,
+                headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+                body: events
+                    .iter()
+                    .map(|event| format!("data: {}\r\n\r\n", event))
+                    .collect(),
+                fragment_size: None,
+            }
+        }
+
+        /// Sends the body in `size`-byte writes, splitting events and characters mid-way.
+        fn fragmented(mut self, size: usize) -> Self {
+            self.fragment_size = Some(size);
+            self
+        }
+
+        fn header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+    }
+
+    struct MockServer {
+        base_url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockServer {
+        async fn start(responses: Vec<MockResponse>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}/v1beta/models", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+
+            tokio::spawn(async move {
+                for response in responses {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let request_head = read_request(&mut socket).await;
+                    recorded.lock().unwrap().push(request_head);
+
+                    let mut raw = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        response.status,
+                        response.body.len()
+                    );
+                    for (name, value) in &response.headers {
+                        raw.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    raw.push_str("\r\n");
+                    socket.write_all(raw.as_bytes()).await.unwrap();
+
+                    let body = response.body.as_bytes();
+                    for piece in body.chunks(response.fragment_size.unwrap_or(body.len().max(1))) {
+                        socket.write_all(piece).await.unwrap();
+                        socket.flush().await.unwrap();
+                        if response.fragment_size.is_some() {
+                            tokio::time::sleep(Duration::from_millis(2)).await;
                         }
                     }
+                    socket.shutdown().await.ok();
+                }
+            });
+
+            MockServer { base_url, requests }
+        }
+
+        fn client(&self) -> GeminiClient {
+            GeminiClient::with_base_url(reqwest::Client::new(), "test-key", &self.base_url)
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..n]);
+            let text = String::from_utf8_lossy(&buffer).to_string();
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|l| {
+                        let (name, value) = l.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if buffer.len() >= header_end + 4 + content_length || n == 0 {
+                    return text;
                 }
             }
-            Err(e) => {
-                return Err(format!("Stream error: {}", e));
+            if n == 0 {
+                return String::new();
             }
         }
     }
-    
-    let _ = app.emit(&event_name, StreamChunk {
-        text: String::new(),
-        done: true,
-    });
-    
-    Ok(full_response)
+
+    fn text_response(text: &str) -> MockResponse {
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": { "parts": [{ "text": text }] },
+                "finishReason": "STOP"
+            }]
+        });
+        MockResponse::json(200, &body.to_string())
+    }
+
+    // Trimmed copies of real v1beta responses.
+    const RECORDED_GENERATE: &str = r#"{
+      "candidates": [{
+        "content": {
+          "parts": [{ "text": "두 포인터로 O(N)에 풀 수 있습니다.", "thoughtSignature": "c2ln" }],
+          "role": "model"
+        },
+        "finishReason": "STOP",
+        "index": 0
+      }],
+      "usageMetadata": {
+        "promptTokenCount": 42,
+        "candidatesTokenCount": 12,
+        "totalTokenCount": 80,
+        "thoughtsTokenCount": 26
+      },
+      "modelVersion": "gemini-2.5-flash",
+      "responseId": "mock-response"
+    }"#;
+    const RECORDED_STREAM: [&str; 3] = [
+        r#"{"candidates":[{"content":{"parts":[{"text":"먼저 정렬한 뒤 "}],"role":"model"},"index":0}],"usageMetadata":{"promptTokenCount":42,"totalTokenCount":42},"modelVersion":"gemini-2.5-flash"}"#,
+        r#"{"candidates":[{"content":{"parts":[{"text":"양 끝에서 포인터를 "}],"role":"model"},"index":0}],"usageMetadata":{"promptTokenCount":42,"totalTokenCount":42},"modelVersion":"gemini-2.5-flash"}"#,
+        r#"{"candidates":[{"content":{"parts":[{"text":"좁혀 가세요."}],"role":"model"},"finishReason":"STOP","index":0}],"usageMetadata":{"promptTokenCount":42,"candidatesTokenCount":17,"totalTokenCount":59},"modelVersion":"gemini-2.5-flash"}"#,
+    ];
+    const RECORDED_MODELS: &str = r#"{
+      "models": [
+        {
+          "name": "models/gemini-2.5-flash",
+          "version": "001",
+          "displayName": "Gemini 2.5 Flash",
+          "inputTokenLimit": 1048576,
+          "outputTokenLimit": 65536,
+          "supportedGenerationMethods": ["generateContent", "countTokens"]
+        },
+        {
+          "name": "models/gemini-2.5-pro",
+          "version": "2.5",
+          "displayName": "Gemini 2.5 Pro",
+          "inputTokenLimit": 1048576,
+          "outputTokenLimit": 65536,
+          "supportedGenerationMethods": ["generateContent", "countTokens"]
+        }
+      ]
+    }"#;
+
+    fn collect_events() -> (
+        Arc<Mutex<Vec<StreamEvent>>>,
+        impl Fn(StreamEvent) + Send + Sync,
+    ) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        (events, move |event| sink.lock().unwrap().push(event))
+    }
+
+    fn deltas(events: &[StreamEvent]) -> Vec<(PartKind, String)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Delta { text, kind } => Some((*kind, text.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn quota_error() -> MockResponse {
+        MockResponse::json(
+            429,
+            r#"{"error":{"code":429,"message":"Quota exceeded","status":"RESOURCE_EXHAUSTED"}}"#,
+        )
+    }
+
+    fn fast_options() -> ChatOptions {
+        ChatOptions {
+            max_retries: Some(2),
+            ..ChatOptions::default()
+        }
+    }
+
+    fn user_message(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage::user(content)]
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors_then_succeeds() {
+        let server = MockServer::start(vec![
+            MockResponse::json(503, r#"{"error":{"message":"overloaded","status":"UNAVAILABLE"}}"#)
+                .header("Retry-After", "0"),
+            text_response("hello"),
+        ])
+        .await;
+
+        let response = server
+            .client()
+            .chat("gemini-2.5-pro", user_message("hi"), "", &fast_options(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "hello");
+        assert_eq!(response.model, "gemini-2.5-pro");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn honors_retry_after_header() {
+        let server = MockServer::start(vec![
+            quota_error().header("Retry-After", "1"),
+            text_response("ok"),
+        ])
+        .await;
+
+        let started = Instant::now();
+        let response = server
+            .client()
+            .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "ok");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_quota_is_exhausted() {
+        let server = MockServer::start(vec![
+            quota_error().header("Retry-After", "0"),
+            quota_error().header("Retry-After", "0"),
+            quota_error().header("Retry-After", "0"),
+            text_response("from flash"),
+        ])
+        .await;
+
+        let options = ChatOptions {
+            fallback_models: Some(vec!["gemini-2.5-flash".to_string()]),
+            ..fast_options()
+        };
+        let response = server
+            .client()
+            .chat("gemini-2.5-pro", user_message("hi"), "", &options, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "from flash");
+        assert_eq!(response.model, "gemini-2.5-flash");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[2].contains("gemini-2.5-pro:generateContent"));
+        assert!(requests[3].contains("gemini-2.5-flash:generateContent"));
+    }
+
+    #[tokio::test]
+    async fn skips_to_fallback_when_retry_after_exceeds_max_delay() {
+        let server = MockServer::start(vec![
+            quota_error().header("Retry-After", "3600"),
+            text_response("fallback"),
+        ])
+        .await;
+
+        let options = ChatOptions {
+            fallback_models: Some(vec!["gemini-2.5-flash".to_string()]),
+            ..fast_options()
+        };
+        let response = server
+            .client()
+            .chat("gemini-2.5-pro", user_message("hi"), "", &options, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.model, "gemini-2.5-flash");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn skips_to_fallback_when_retry_info_exceeds_max_delay() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                429,
+                r#"{"error":{"code":429,"message":"Quota exceeded","status":"RESOURCE_EXHAUSTED",
+                    "details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"3600s"}]}}"#,
+            ),
+            text_response("fallback"),
+        ])
+        .await;
+
+        let options = ChatOptions {
+            fallback_models: Some(vec!["gemini-2.5-flash".to_string()]),
+            ..fast_options()
+        };
+        let response = server
+            .client()
+            .chat("gemini-2.5-pro", user_message("hi"), "", &options, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.model, "gemini-2.5-flash");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let server = MockServer::start(vec![MockResponse::json(
+            400,
+            r#"{"error":{"code":400,"message":"API key not valid.","status":"INVALID_ARGUMENT"}}"#,
+        )])
+        .await;
+
+        let options = ChatOptions {
+            fallback_models: Some(vec!["gemini-2.5-flash".to_string()]),
+            ..fast_options()
+        };
+        let error = server
+            .client()
+            .chat("gemini-2.5-pro", user_message("hi"), "", &options, None)
+            .await
+            .unwrap_err();
+
+        assert!(error.contains("API key not valid"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_api_key_in_header_only() {
+        let server = MockServer::start(vec![
+            text_response("ok"),
+            MockResponse::json(200, r#"{"models":[]}"#),
+        ])
+        .await;
+        let client = server.client();
+
+        client
+            .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), None)
+            .await
+            .unwrap();
+        client.fetch_available_models().await.unwrap();
+
+        for request in server.requests() {
+            let request_line = request.lines().next().unwrap();
+            assert!(!request_line.contains("test-key"));
+            assert!(!request_line.contains("key="));
+            assert!(request
+                .lines()
+                .any(|l| l.eq_ignore_ascii_case("x-goog-api-key: test-key")));
+        }
+    }
+
+    #[tokio::test]
+    async fn error_paths_never_leak_the_api_key() {
+        let secret = "leak-check-secret-123";
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                400,
+                r#"{"error":{"code":400,"message":"API key leak-check-secret-123 not valid.","status":"INVALID_ARGUMENT"}}"#,
+            ),
+            MockResponse::json(403, "forbidden for key=leak-check-secret-123"),
+        ])
+        .await;
+        let http = reqwest::Client::new();
+        let client = GeminiClient::with_base_url(http.clone(), secret, &server.base_url);
+        let unreachable =
+            GeminiClient::with_base_url(http, secret, "http://127.0.0.1:1/v1beta/models");
+        let no_retry = ChatOptions {
+            max_retries: Some(0),
+            ..ChatOptions::default()
+        };
+
+        let errors = vec![
+            client
+                .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), None)
+                .await
+                .unwrap_err(),
+            client.fetch_available_models().await.unwrap_err(),
+            unreachable
+                .chat("gemini-2.5-flash", user_message("hi"), "", &no_retry, None)
+                .await
+                .unwrap_err(),
+            unreachable.fetch_available_models().await.unwrap_err(),
+        ];
+
+        for error in errors {
+            let serialized =
+                serde_json::to_string(&redact::CommandError::from(error.clone())).unwrap();
+            assert!(!serialized.contains(secret), "leaked: {}", serialized);
+            assert!(!redact::redact(&error).contains(secret));
+        }
+    }
+
+    #[tokio::test]
+    async fn rotates_to_next_key_when_quota_is_exhausted() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                429,
+                r#"{"error":{"code":429,"message":"Quota exceeded.","status":"RESOURCE_EXHAUSTED"}}"#,
+            )
+            .header("Retry-After", "30"),
+            text_response("ok"),
+        ])
+        .await;
+        let keys = Arc::new(KeyRing::new(vec![
+            ApiKey {
+                label: "first".to_string(),
+                secret: "first-secret".to_string(),
+            },
+            ApiKey {
+                label: "second".to_string(),
+                secret: "second-secret".to_string(),
+            },
+        ]));
+        let client = GeminiClient::with_keys_and_base_url(
+            reqwest::Client::new(),
+            keys.clone(),
+            &server.base_url,
+        );
+
+        let started = Instant::now();
+        let response = client
+            .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.api_key_label, "second");
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let requests = server.requests();
+        assert!(requests[0].contains("first-secret"));
+        assert!(requests[1].contains("second-secret"));
+        let states = keys.states();
+        assert!(states[0].cooldown_secs > 20);
+        assert_eq!(states[1].cooldown_secs, 0);
+        assert_eq!(keys.next().unwrap().label, "second");
+    }
+
+    #[tokio::test]
+    async fn chat_parses_recorded_response() {
+        let server = MockServer::start(vec![MockResponse::json(200, RECORDED_GENERATE)]).await;
+
+        let response = server
+            .client()
+            .chat(
+                "gemini-2.5-flash",
+                user_message("풀이 방향?"),
+                "튜터",
+                &fast_options(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "두 포인터로 O(N)에 풀 수 있습니다.");
+        assert_eq!(response.model, "gemini-2.5-flash");
+        assert_eq!(response.usage.prompt_tokens, 42);
+        assert_eq!(response.usage.output_tokens, 12);
+        assert_eq!(response.usage.thinking_tokens, 26);
+        assert!(server.requests()[0]
+            .starts_with("POST /v1beta/models/gemini-2.5-flash:generateContent "));
+    }
+
+    #[tokio::test]
+    async fn chat_rejects_empty_candidates() {
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"candidates":[],"usageMetadata":{"promptTokenCount":3}}"#,
+        )])
+        .await;
+
+        let error = server
+            .client()
+            .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), None)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error, "No response from Gemini");
+    }
+
+    #[tokio::test]
+    async fn chat_stream_reassembles_fragmented_events() {
+        let server =
+            MockServer::start(vec![MockResponse::sse(&RECORDED_STREAM).fragmented(7)]).await;
+        let (events, on_event) = collect_events();
+
+        let response = server
+            .client()
+            .chat_stream(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                "",
+                &fast_options(),
+                None,
+                &on_event,
+            )
+            .await
+            .unwrap();
+
+        let expected = "먼저 정렬한 뒤 양 끝에서 포인터를 좁혀 가세요.";
+        assert_eq!(response.text, expected);
+        assert_eq!(response.usage.output_tokens, 17);
+        let events = events.lock().unwrap();
+        let streamed: String = deltas(&events).into_iter().map(|(_, text)| text).collect();
+        assert_eq!(streamed, expected);
+        assert!(matches!(
+            &events[events.len() - 2..],
+            [StreamEvent::Usage { usage }, StreamEvent::Done] if usage.output_tokens == 17
+        ));
+        assert!(server.requests()[0].contains(":streamGenerateContent?alt=sse"));
+    }
+
+    #[tokio::test]
+    async fn chat_stream_surfaces_errors_sent_mid_stream() {
+        let server = MockServer::start(vec![MockResponse::sse(&[
+            RECORDED_STREAM[0],
+            r#"{"error":{"code":500,"message":"Internal error encountered.","status":"INTERNAL"}}"#,
+        ])
+        .fragmented(16)])
+        .await;
+        let (events, on_event) = collect_events();
+
+        let error = server
+            .client()
+            .chat_stream(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                "",
+                &fast_options(),
+                None,
+                &on_event,
+            )
+            .await
+            .unwrap_err();
+
+        assert!(error.contains("Internal error encountered."));
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            deltas(&events),
+            [(PartKind::Answer, "먼저 정렬한 뒤 ".to_string())]
+        );
+        match &events[1] {
+            StreamEvent::Error {
+                kind,
+                retryable,
+                partial: Some(partial),
+                ..
+            } => {
+                assert_eq!(*kind, ErrorKind::Server);
+                assert!(retryable);
+                assert_eq!(partial.text, "먼저 정렬한 뒤 ");
+            }
+            other => panic!("expected an error event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn chat_stream_ends_failed_requests_with_a_typed_error() {
+        let server = MockServer::start(vec![MockResponse::json(
+            400,
+            r#"[{"error":{"code":400,"message":"API key not valid. Please pass a valid API key.","status":"INVALID_ARGUMENT"}}]"#,
+        )])
+        .await;
+        let (events, on_event) = collect_events();
+
+        let error = server
+            .client()
+            .chat_stream(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                "",
+                &fast_options(),
+                None,
+                &on_event,
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            "Gemini API error (INVALID_ARGUMENT): API key not valid. Please pass a valid API key."
+        );
+        let events = events.lock().unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [StreamEvent::Error {
+                kind: ErrorKind::Auth,
+                retryable: false,
+                partial: Some(_),
+                ..
+            }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn chat_stream_reports_replies_that_end_without_text() {
+        let server = MockServer::start(vec![MockResponse::sse(&[
+            r#"{"candidates":[{"finishReason":"SAFETY","index":0}],"usageMetadata":{"promptTokenCount":5}}"#,
+        ])])
+        .await;
+        let (events, on_event) = collect_events();
+
+        let error = server
+            .client()
+            .chat_stream(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                "",
+                &fast_options(),
+                None,
+                &on_event,
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(error, "No response from Gemini (finish reason: SAFETY)");
+        let events = events.lock().unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [StreamEvent::Error {
+                kind: ErrorKind::Other,
+                partial: Some(_),
+                ..
+            }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn embed_batches_texts_and_reads_values() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                200,
+                r#"{"embeddings":[{"values":[0.25,-0.5,1.0]},{"values":[1.0,0.0,0.0]}]}"#,
+            ),
+            MockResponse::json(200, r#"{"embeddings":[{"values":[1.0]}]}"#),
+        ])
+        .await;
+        let client = server.client();
+        let texts = ["두 수의 합".to_string(), "최단 경로".to_string()];
+
+        let embedded = client.embed("gemini-embedding-001", &texts).await.unwrap();
+        assert_eq!(embedded.vectors, [vec![0.25, -0.5, 1.0], vec![1.0, 0.0, 0.0]]);
+        assert_eq!(embedded.response.model, "gemini-embedding-001");
+        assert!(embedded.response.usage.prompt_tokens > 0);
+        let request = &server.requests()[0];
+        assert!(request.contains("gemini-embedding-001:batchEmbedContents"));
+        assert!(request.contains(r#""model":"models/gemini-embedding-001""#));
+        assert!(request.contains(r#""task_type":"SEMANTIC_SIMILARITY""#));
+
+        // A response missing a vector is an error, not a shorter list.
+        assert!(client.embed("gemini-embedding-001", &texts).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_available_models_parses_recorded_list() {
+        let server =
+            MockServer::start(vec![MockResponse::json(200, RECORDED_MODELS).fragmented(64)]).await;
+
+        let models = server.client().fetch_available_models().await.unwrap();
+
+        let names: Vec<&str> = models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["gemini-2.5-flash", "gemini-2.5-pro"]);
+        assert_eq!(models[1].display_name, "Gemini 2.5 Pro");
+        assert_eq!(models[1].input_token_limit, 1048576);
+        assert_eq!(models[1].version, "2.5");
+        assert!(models[1].thinking);
+        assert!(server.requests()[0].starts_with("GET /v1beta/models?pageSize=1000 "));
+    }
+
+    #[tokio::test]
+    async fn fetch_available_models_follows_pages_and_drops_non_chat_models() {
+        let first_page = r#"{
+          "models": [
+            {
+              "name": "models/gemini-2.0-flash",
+              "displayName": "Gemini 2.0 Flash",
+              "inputTokenLimit": 1048576,
+              "outputTokenLimit": 8192,
+              "supportedGenerationMethods": ["generateContent", "countTokens"]
+            },
+            {
+              "name": "models/text-embedding-004",
+              "displayName": "Text Embedding 004",
+              "inputTokenLimit": 2048,
+              "outputTokenLimit": 1,
+              "supportedGenerationMethods": ["embedContent"]
+            }
+          ],
+          "nextPageToken": "page-2"
+        }"#;
+        let server = MockServer::start(vec![
+            MockResponse::json(200, first_page),
+            MockResponse::json(200, RECORDED_MODELS),
+        ])
+        .await;
+
+        let models = server.client().fetch_available_models().await.unwrap();
+
+        let names: Vec<&str> = models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["gemini-2.0-flash", "gemini-2.5-flash", "gemini-2.5-pro"]
+        );
+        assert!(!models[0].thinking);
+        assert_eq!(models[0].output_token_limit, 8192);
+        assert!(server.requests()[1].contains("pageToken=page-2"));
+    }
+
+    #[tokio::test]
+    async fn fetch_available_models_reports_http_errors() {
+        let server = MockServer::start(vec![MockResponse::json(
+            403,
+            r#"{"error":{"code":403,"message":"Permission denied.","status":"PERMISSION_DENIED"}}"#,
+        )])
+        .await;
+
+        let error = server.client().fetch_available_models().await.unwrap_err();
+
+        assert!(error.contains("403"));
+    }
+
+    #[tokio::test]
+    async fn replays_deterministic_requests_from_the_response_cache() {
+        let dir = std::env::temp_dir().join(format!("psup-gemini-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let server =
+            MockServer::start(vec![text_response("cached hint"), text_response("fresh")]).await;
+        let client = server.client().with_cache(ResponseCache::open(&dir));
+        let deterministic = ChatOptions {
+            generation: Some(GenerationSettings {
+                temperature: Some(0.0),
+                ..GenerationSettings::default()
+            }),
+            ..fast_options()
+        };
+
+        let first = client
+            .chat(
+                "gemini-2.5-flash",
+                user_message("hint"),
+                "",
+                &deterministic,
+                None,
+            )
+            .await
+            .unwrap();
+        let (events, on_event) = collect_events();
+        let second = client
+            .chat_stream(
+                "gemini-2.5-flash",
+                user_message("hint"),
+                "",
+                &deterministic,
+                None,
+                &on_event,
+            )
+            .await
+            .unwrap();
+        let bypassed = client
+            .chat(
+                "gemini-2.5-flash",
+                user_message("hint"),
+                "",
+                &ChatOptions {
+                    cache: Some(false),
+                    ..deterministic.clone()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(!first.cached);
+        assert!(second.cached);
+        assert_eq!(second.text, "cached hint");
+        assert_eq!(second.usage.prompt_tokens, 0);
+        assert_eq!(
+            deltas(&events.lock().unwrap()),
+            [(PartKind::Answer, "cached hint".to_string())]
+        );
+        assert_eq!(bypassed.text, "fresh");
+        assert_eq!(server.requests().len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn context_client(server: &MockServer) -> GeminiClient {
+        server
+            .client()
+            .with_context_caches(ContextCaches::default())
+    }
+
+    fn long_system_prompt() -> String {
+        format!("문제 설명\n{}", "n개의 수가 주어진다. ".repeat(200))
+    }
+
+    #[tokio::test]
+    async fn references_a_cached_context_on_later_turns() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, r#"{"name":"cachedContents/session-1"}"#),
+            text_response("first"),
+            text_response("second"),
+        ])
+        .await;
+        // A short prompt still reaches the cache minimum together with the statement.
+        let client = context_client(&server).with_problem_context(Some(long_system_prompt()));
+
+        for _ in 0..2 {
+            let response = client
+                .chat(
+                    "gemini-2.5-flash",
+                    user_message("hi"),
+                    "힌트만 주세요.",
+                    &fast_options(),
+                    None,
+                )
+                .await
+                .unwrap();
+            assert!(response.context_cache_error.is_none());
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].starts_with("POST /v1beta/cachedContents "));
+        assert!(requests[0].contains(r#""model":"models/gemini-2.5-flash""#));
+        assert!(requests[0].contains("힌트만 주세요."));
+        assert!(requests[0].contains("n개의 수가 주어진다."));
+        for request in &requests[1..] {
+            assert!(request.contains(r#""cached_content":"cachedContents/session-1""#));
+            assert!(!request.contains("힌트만 주세요."));
+            assert!(!request.contains("n개의 수가 주어진다."));
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_prompt_inline_when_caching_is_refused() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                400,
+                r#"{"error":{"code":400,"message":"Cached content is too small.","status":"INVALID_ARGUMENT"}}"#,
+            ),
+            text_response("first"),
+            text_response("second"),
+        ])
+        .await;
+        let client = context_client(&server);
+        let system_prompt = long_system_prompt();
+
+        let mut errors = vec![];
+        for _ in 0..2 {
+            let response = client
+                .chat(
+                    "gemini-2.5-flash",
+                    user_message("hi"),
+                    &system_prompt,
+                    &fast_options(),
+                    None,
+                )
+                .await
+                .unwrap();
+            errors.push(response.context_cache_error);
+        }
+
+        // Only the failed create is reported; the next turn skips caching quietly.
+        assert!(errors[0]
+            .as_deref()
+            .unwrap()
+            .contains("Cached content is too small."));
+        assert!(errors[1].is_none());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        for request in &requests[1..] {
+            assert!(request.contains(":generateContent "));
+            assert!(request.contains("n개의 수가 주어진다."));
+            assert!(!request.contains("cached_content"));
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_inline_when_the_cached_context_is_gone() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, r#"{"name":"cachedContents/expired"}"#),
+            MockResponse::json(
+                403,
+                r#"{"error":{"code":403,"message":"CachedContent not found (or permission denied)","status":"PERMISSION_DENIED"}}"#,
+            ),
+            text_response("inline"),
+            MockResponse::json(200, "{}"),
+        ])
+        .await;
+
+        let response = context_client(&server)
+            .chat(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                &long_system_prompt(),
+                &fast_options(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "inline");
+        assert!(response
+            .context_cache_error
+            .unwrap()
+            .starts_with("Cached context request failed"));
+        let requests = server.requests();
+        assert!(requests[1].contains("cachedContents/expired"));
+        assert!(!requests[2].contains("cached_content"));
+        assert!(requests[3].starts_with("DELETE /v1beta/cachedContents/expired "));
+    }
+
+    #[tokio::test]
+    async fn streams_thought_summaries_apart_from_the_answer() {
+        let server = MockServer::start(vec![MockResponse::sse(&[
+            r#"{"candidates":[{"content":{"parts":[{"text":"정렬이 필요한지 살펴보자.","thought":true}],"role":"model"}}]}"#,
+            r#"{"candidates":[{"content":{"parts":[{"text":"정렬 후 이분 탐색을 쓰세요."}],"role":"model"},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":10,"candidatesTokenCount":8,"thoughtsTokenCount":30}}"#,
+        ])])
+        .await;
+        let options = ChatOptions {
+            generation: Some(GenerationSettings {
+                include_thoughts: Some(true),
+                ..GenerationSettings::default()
+            }),
+            ..fast_options()
+        };
+        let (events, on_event) = collect_events();
+
+        let response = server
+            .client()
+            .chat_stream(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                "",
+                &options,
+                None,
+                &on_event,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "정렬 후 이분 탐색을 쓰세요.");
+        assert_eq!(response.thoughts, "정렬이 필요한지 살펴보자.");
+        let kinds: Vec<PartKind> = deltas(&events.lock().unwrap())
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect();
+        assert_eq!(kinds, [PartKind::Thought, PartKind::Answer]);
+        assert!(server.requests()[0].contains(r#""thinking_config":{"include_thoughts":true}"#));
+    }
+
+    struct EchoTools;
+
+    impl ToolExecutor for EchoTools {
+        fn declarations(&self) -> Vec<FunctionDeclaration> {
+            vec![FunctionDeclaration {
+                name: "echo".to_string(),
+                description: "Echo the input".to_string(),
+                parameters: None,
+            }]
+        }
+
+        fn call<'a>(
+            &'a self,
+            name: &'a str,
+            args: &'a serde_json::Value,
+        ) -> BoxFuture<'a, serde_json::Value> {
+            Box::pin(async move { serde_json::json!({ "tool": name, "echo": args }) })
+        }
+    }
+
+    #[tokio::test]
+    async fn executes_tool_calls_and_records_them() {
+        let call = serde_json::json!({
+            "candidates": [{
+                "content": { "parts": [{ "functionCall": { "name": "echo", "args": { "x": 1 } } }] },
+                "finishReason": "STOP"
+            }]
+        });
+        let server = MockServer::start(vec![
+            MockResponse::json(200, &call.to_string()),
+            text_response("verified"),
+        ])
+        .await;
+
+        let response = server
+            .client()
+            .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), Some(&EchoTools))
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "verified");
+        assert_eq!(response.tool_calls.len(), 1);
+        assert_eq!(response.tool_calls[0].name, "echo");
+        assert_eq!(response.tool_calls[0].result["echo"]["x"], 1);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn stops_a_model_that_keeps_calling_tools() {
+        let call = serde_json::json!({
+            "candidates": [{
+                "content": { "parts": [{ "functionCall": { "name": "echo", "args": {} } }] },
+                "finishReason": "STOP"
+            }]
+        });
+        let rounds = MAX_TOOL_ROUNDS as usize + 1;
+        let server = MockServer::start(
+            (0..rounds)
+                .map(|_| MockResponse::json(200, &call.to_string()))
+                .collect(),
+        )
+        .await;
+
+        let error = server
+            .client()
+            .chat("gemini-2.5-flash", user_message("hi"), "", &fast_options(), Some(&EchoTools))
+            .await
+            .unwrap_err();
+
+        assert!(error.contains("kept calling tools"));
+        let requests = server.requests();
+        assert_eq!(requests.len(), rounds);
+        assert!(requests.iter().all(|r| r.contains(r#""function_declarations""#)));
+        assert!(!requests[rounds - 2].contains(r#""mode":"NONE""#));
+        assert!(requests[rounds - 1]
+            .contains(r#""tool_config":{"function_calling_config":{"mode":"NONE"}}"#));
+    }
+
+    #[tokio::test]
+    async fn stitches_continuations_after_max_tokens() {
+        let truncated = serde_json::json!({
+            "candidates": [{
+                "content": { "parts": [{ "text": "```cpp\nint main() {" }] },
+                "finishReason": "MAX_TOKENS"
+            }]
+        });
+        let server = MockServer::start(vec![
+            MockResponse::json(200, &truncated.to_string()),
+            text_response(" return 0; }\n```"),
+        ])
+        .await;
+
+        let response = server
+            .client()
+            .chat(
+                "gemini-2.5-flash",
+                user_message("hi"),
+                "",
+                &fast_options(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "```cpp\nint main() { return 0; }\n```");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+
+        let first = policy.backoff(0);
+        assert!(first >= Duration::from_millis(100) && first < Duration::from_millis(150));
+        let third = policy.backoff(2);
+        assert!(third >= Duration::from_millis(400) && third < Duration::from_millis(600));
+        let capped = policy.backoff(10);
+        assert_eq!(capped, Duration::from_millis(1000));
+        assert!((0..20).all(|attempt| policy.backoff(attempt) <= policy.max_delay));
+    }
+
+    #[test]
+    fn reads_retry_delays_from_dates_and_error_details() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(784_111_777))
+        );
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse_http_date("tomorrow"), None);
+
+        let details: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[{"@type":"type.googleapis.com/google.rpc.QuotaFailure"},
+                {"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"1.5s"}]"#,
+        )
+        .unwrap();
+        assert_eq!(retry_delay(&details), Some(Duration::from_millis(1500)));
+        assert_eq!(retry_delay(&[]), None);
+    }
 }
@@ -0,0 +1,189 @@
+use crate::database::{Database, HintRecord};
+use crate::gemini::{parse_json, ChatMessage, ChatOptions, GeminiClient};
+use crate::prompts;
+use crate::usage;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+const HINT_LEVELS: usize = 4;
+const HINT_PROMPT: &str =
+    "Write a ladder of exactly 4 hints for the competitive programming problem below. \
+Level 1 is a small observation that points in the right direction without naming the technique. \
+Level 2 names the key idea or technique. \
+Level 3 outlines the algorithm and the data structures it needs. \
+Level 4 is a near-solution: the full approach step by step with its complexity, but no code. \
+Each hint must build on the previous ones and must not reveal more than its level allows. \
+Write the hints in Korean.";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct HintProgress {
+    pub total: i64,
+    pub revealed: Vec<HintRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HintLadder {
+    hints: Vec<LadderHint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LadderHint {
+    level: u32,
+    content: String,
+}
+
+fn hint_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "hints": {
+                "type": "ARRAY",
+                "items": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "level": { "type": "INTEGER" },
+                        "content": { "type": "STRING" }
+                    },
+                    "required": ["level", "content"]
+                }
+            }
+        },
+        "required": ["hints"]
+    })
+}
+
+/// The hint texts in level order, when the ladder has every level from 1 to 4 once.
+fn ladder_contents(mut hints: Vec<LadderHint>) -> Result<Vec<String>, String> {
+    hints.retain(|h| !h.content.trim().is_empty());
+    if hints.len() != HINT_LEVELS {
+        return Err(format!(
+            "Expected {} hints but got {}",
+            HINT_LEVELS,
+            hints.len()
+        ));
+    }
+    hints.sort_by_key(|h| h.level);
+    let levels: Vec<u32> = hints.iter().map(|h| h.level).collect();
+    if !levels.iter().copied().eq(1..=HINT_LEVELS as u32) {
+        return Err(format!(
+            "Expected hint levels 1 to {} but got {:?}",
+            HINT_LEVELS, levels
+        ));
+    }
+    Ok(hints.into_iter().map(|h| h.content).collect())
+}
+
+pub fn progress(db: &Database, problem_id: &str) -> Result<HintProgress, String> {
+    Ok(HintProgress {
+        total: db.count_hints(problem_id).map_err(|e| e.to_string())?,
+        revealed: db
+            .get_revealed_hints(problem_id)
+            .map_err(|e| e.to_string())?,
+    })
+}
+
+pub async fn generate(
+    client: &GeminiClient,
+    db: &Database,
+    problem_id: &str,
+    model: &str,
+    regenerate: bool,
+    options: ChatOptions,
+) -> Result<HintProgress, String> {
+    if !regenerate && db.count_hints(problem_id).map_err(|e| e.to_string())? > 0 {
+        return progress(db, problem_id);
+    }
+
+    let problem = db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Problem not found: {}", problem_id))?;
+    let messages = vec![ChatMessage::user(format!(
+        "{}\n\n{}",
+        HINT_PROMPT,
+        prompts::problem_context(&problem)
+    ))];
+    let options = ChatOptions {
+        max_continuations: Some(0),
+        response_schema: Some(hint_schema()),
+        ..options
+    };
+
+    let started = Instant::now();
+    let result = client.chat(model, messages, "", &options, None).await;
+    usage::record(db, Some(problem_id), "hints", model, started, &result);
+    let response = result?;
+
+    let ladder: HintLadder = parse_json(&response.text)?;
+    let contents = ladder_contents(ladder.hints)?;
+    db.replace_hints(problem_id, &response.model, &contents)
+        .map_err(|e| e.to_string())?;
+    progress(db, problem_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::{test_db, test_problem};
+
+    fn hints(levels: &[u32]) -> Vec<LadderHint> {
+        levels
+            .iter()
+            .map(|&level| LadderHint {
+                level,
+                content: format!("hint {}", level),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accepts_only_ladders_with_each_level_once() {
+        assert_eq!(
+            ladder_contents(hints(&[3, 1, 4, 2])).unwrap(),
+            ["hint 1", "hint 2", "hint 3", "hint 4"]
+        );
+        assert!(ladder_contents(hints(&[1, 1, 3, 4])).is_err());
+        assert!(ladder_contents(hints(&[0, 2, 5, 9])).is_err());
+        assert!(ladder_contents(hints(&[1, 2, 3])).is_err());
+
+        let mut blank = hints(&[1, 2, 3, 4]);
+        blank[3].content = " ".to_string();
+        assert_eq!(
+            ladder_contents(blank).unwrap_err(),
+            "Expected 4 hints but got 3"
+        );
+    }
+
+    #[test]
+    fn counts_hints_revealed_before_the_solve_across_regenerations() {
+        let db = test_db("hints");
+        db.save_problem(&test_problem("1000", "A+B")).unwrap();
+        let ladder = |name: &str| -> Vec<String> {
+            (1..=HINT_LEVELS)
+                .map(|level| format!("{} {}", name, level))
+                .collect()
+        };
+
+        db.replace_hints("1000", "model", &ladder("first")).unwrap();
+        db.reveal_next_hint("1000").unwrap().unwrap();
+        db.reveal_next_hint("1000").unwrap().unwrap();
+        db.replace_hints("1000", "model", &ladder("second"))
+            .unwrap();
+        let hint = db.reveal_next_hint("1000").unwrap().unwrap();
+        assert_eq!((hint.level, hint.content.as_str()), (1, "second 1"));
+        assert_eq!(progress(&db, "1000").unwrap().revealed.len(), 1);
+
+        db.record_solve("1000").unwrap();
+        assert_eq!(db.get_solve_records("1000").unwrap()[0].hints_used, 3);
+
+        // Undoing the solve hands its hints back to the next one.
+        assert!(db.unrecord_solve("1000").unwrap());
+        db.reveal_next_hint("1000").unwrap().unwrap();
+        db.record_solve("1000").unwrap();
+        assert_eq!(db.get_solve_records("1000").unwrap()[0].hints_used, 4);
+
+        db.reveal_next_hint("1000").unwrap().unwrap();
+        db.reveal_next_hint("1000").unwrap().unwrap();
+        assert!(db.reveal_next_hint("1000").unwrap().is_none());
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+const SETTINGS_FILE: &str = "network.json";
+/// Baekjoon refuses requests that do not look like they come from a browser.
+pub const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36";
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Applies to each read, so long streamed replies are fine as long as they keep arriving.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// `http://`, `https://` or `socks5://` proxy used for every request.
+    pub proxy_url: Option<String>,
+    /// PEM files with extra root certificates, for proxies that intercept TLS.
+    pub ca_certificates: Vec<String>,
+    pub connect_timeout_secs: Option<u64>,
+    pub read_timeout_secs: Option<u64>,
+    pub user_agent: Option<String>,
+}
+
+impl NetworkSettings {
+    pub fn build_client(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(
+                self.user_agent
+                    .as_deref()
+                    .filter(|agent| !agent.trim().is_empty())
+                    .unwrap_or(DEFAULT_USER_AGENT),
+            )
+            .connect_timeout(
+                self.connect_timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+            )
+            .read_timeout(
+                self.read_timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_READ_TIMEOUT),
+            );
+
+        if let Some(url) = self.proxy_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+            let proxy = reqwest::Proxy::all(url)
+                .map_err(|e| format!("Invalid proxy URL {}: {}", url, e))?;
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &self.ca_certificates {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Failed to read certificate {}: {}", path, e))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid certificate {}: {}", path, e))?;
+            if certificates.is_empty() {
+                return Err(format!("No certificate found in {}", path));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))
+    }
+}
+
+/// The saved settings and, when they could not be applied, why the default client
+/// is in use instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkStatus {
+    pub settings: NetworkSettings,
+    pub error: Option<String>,
+}
+
+/// The HTTP client shared by all network code, so connections are reused and the
+/// proxy, certificates and timeouts from the settings apply everywhere.
+pub struct HttpClient {
+    path: PathBuf,
+    settings: RwLock<NetworkSettings>,
+    client: RwLock<reqwest::Client>,
+    error: RwLock<Option<String>>,
+}
+
+impl HttpClient {
+    pub fn open(dir: &Path) -> Self {
+        let path = dir.join(SETTINGS_FILE);
+        let settings: NetworkSettings = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        // A certificate that has since been moved should not keep the app offline, but
+        // the user is told through `status` that their settings are not in effect.
+        let (client, error) = match settings.build_client() {
+            Ok(client) => (client, None),
+            Err(error) => (
+                NetworkSettings::default()
+                    .build_client()
+                    .expect("default HTTP client"),
+                Some(error),
+            ),
+        };
+
+        HttpClient {
+            path,
+            settings: RwLock::new(settings),
+            client: RwLock::new(client),
+            error: RwLock::new(error),
+        }
+    }
+
+    /// A handle to the shared client. Cloning it is cheap and shares the connection pool.
+    pub fn client(&self) -> reqwest::Client {
+        self.client.read().unwrap().clone()
+    }
+
+    pub fn settings(&self) -> NetworkSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn status(&self) -> NetworkStatus {
+        NetworkStatus {
+            settings: self.settings(),
+            error: self.error.read().unwrap().clone(),
+        }
+    }
+
+    /// Applies and saves new settings. Nothing changes if they do not produce a client.
+    pub fn update(&self, settings: NetworkSettings) -> Result<(), String> {
+        let client = settings.build_client()?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json)
+            .map_err(|e| format!("Failed to save network settings: {}", e))?;
+
+        *self.client.write().unwrap() = client;
+        *self.settings.write().unwrap() = settings;
+        *self.error.write().unwrap() = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_valid_settings_and_rejects_broken_ones() {
+        let dir = std::env::temp_dir().join(format!("psup-network-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let http = HttpClient::open(&dir);
+        assert_eq!(http.settings(), NetworkSettings::default());
+
+        let settings = NetworkSettings {
+            proxy_url: Some("http://proxy.example.com:3128".to_string()),
+            read_timeout_secs: Some(30),
+            ..NetworkSettings::default()
+        };
+        http.update(settings.clone()).unwrap();
+        assert_eq!(HttpClient::open(&dir).settings(), settings);
+
+        let missing_certificate = NetworkSettings {
+            ca_certificates: vec![dir.join("missing.pem").display().to_string()],
+            ..NetworkSettings::default()
+        };
+        assert!(http.update(missing_certificate).is_err());
+        assert!(http
+            .update(NetworkSettings {
+                proxy_url: Some("not a url".to_string()),
+                ..NetworkSettings::default()
+            })
+            .is_err());
+        assert_eq!(http.settings(), settings);
+        assert!(http.status().error.is_none());
+
+        // Settings saved earlier that no longer work are reported, not applied.
+        let missing = dir.join("moved.pem").display().to_string();
+        std::fs::write(
+            dir.join(SETTINGS_FILE),
+            serde_json::to_string(&NetworkSettings {
+                ca_certificates: vec![missing.clone()],
+                ..NetworkSettings::default()
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        let status = HttpClient::open(&dir).status();
+        assert_eq!(status.settings.ca_certificates, [missing]);
+        assert!(status.error.unwrap().contains("moved.pem"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
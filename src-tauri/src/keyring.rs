@@ -0,0 +1,163 @@
+use crate::redact;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_LABEL: &str = "default";
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+const MIN_COOLDOWN: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub label: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct KeyState {
+    pub label: String,
+    pub cooldown_secs: u64,
+}
+
+/// Keys of one provider handed out round-robin. A key that ran out of quota is
+/// skipped until its cooldown ends, so pooled free-tier keys share the load.
+pub struct KeyRing {
+    keys: Vec<ApiKey>,
+    cursor: AtomicUsize,
+    cooldowns: Mutex<HashMap<String, Instant>>,
+}
+
+impl KeyRing {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        keys.iter().for_each(|key| redact::register(&key.secret));
+        KeyRing {
+            keys,
+            cursor: AtomicUsize::new(0),
+            cooldowns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn single(secret: &str) -> Self {
+        Self::new(vec![ApiKey {
+            label: DEFAULT_LABEL.to_string(),
+            secret: secret.to_string(),
+        }])
+    }
+
+    /// Rebuilds the ring for a changed key list, keeping the cooldowns of keys that remain.
+    pub fn replace(&self, keys: Vec<ApiKey>) -> Self {
+        let ring = Self::new(keys);
+        let cooldowns = self.cooldowns.lock().unwrap();
+        ring.cooldowns.lock().unwrap().extend(
+            cooldowns
+                .iter()
+                .filter(|(label, _)| ring.keys.iter().any(|k| &k.label == *label))
+                .map(|(label, until)| (label.clone(), *until)),
+        );
+        ring
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The next key in round-robin order that is not cooling down. When every key
+    /// is cooling down, the one that recovers first.
+    pub fn next(&self) -> Option<ApiKey> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let cooldowns = self.cooldowns.lock().unwrap();
+        let cooling_until = |key: &ApiKey| cooldowns.get(&key.label).copied().filter(|t| *t > now);
+
+        (0..self.keys.len())
+            .map(|i| &self.keys[(start + i) % self.keys.len()])
+            .find(|key| cooling_until(key).is_none())
+            .or_else(|| self.keys.iter().min_by_key(|key| cooling_until(key)))
+            .cloned()
+    }
+
+    pub fn get(&self, label: &str) -> Option<ApiKey> {
+        self.keys.iter().find(|key| key.label == label).cloned()
+    }
+
+    /// Benches a key after it hit its quota, for `retry_after` when the API said how long.
+    pub fn cool_down(&self, label: &str, retry_after: Option<Duration>) {
+        let duration = retry_after.unwrap_or(DEFAULT_COOLDOWN).max(MIN_COOLDOWN);
+        self.cooldowns
+            .lock()
+            .unwrap()
+            .insert(label.to_string(), Instant::now() + duration);
+    }
+
+    pub fn has_ready_key(&self) -> bool {
+        let now = Instant::now();
+        let cooldowns = self.cooldowns.lock().unwrap();
+        self.keys
+            .iter()
+            .any(|key| cooldowns.get(&key.label).is_none_or(|until| *until <= now))
+    }
+
+    pub fn states(&self) -> Vec<KeyState> {
+        let now = Instant::now();
+        let cooldowns = self.cooldowns.lock().unwrap();
+        self.keys
+            .iter()
+            .map(|key| KeyState {
+                label: key.label.clone(),
+                cooldown_secs: cooldowns
+                    .get(&key.label)
+                    .map(|until| until.saturating_duration_since(now).as_secs())
+                    .unwrap_or(0),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(labels: &[&str]) -> KeyRing {
+        KeyRing::new(
+            labels
+                .iter()
+                .map(|label| ApiKey {
+                    label: label.to_string(),
+                    secret: format!("secret-for-{}", label),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn rotates_round_robin_and_skips_cooling_keys() {
+        let ring = ring(&["a", "b", "c"]);
+        let labels: Vec<String> = (0..4).map(|_| ring.next().unwrap().label).collect();
+        assert_eq!(labels, ["a", "b", "c", "a"]);
+
+        ring.cool_down("b", None);
+        let labels: Vec<String> = (0..3).map(|_| ring.next().unwrap().label).collect();
+        assert!(!labels.contains(&"b".to_string()));
+        assert!(ring.has_ready_key());
+
+        ring.cool_down("a", Some(Duration::from_secs(5)));
+        ring.cool_down("c", Some(Duration::from_secs(10)));
+        assert!(!ring.has_ready_key());
+        assert_eq!(ring.next().unwrap().label, "a");
+
+        let replaced = ring.replace(vec![ApiKey {
+            label: "c".to_string(),
+            secret: "secret-for-c".to_string(),
+        }]);
+        assert!(replaced.states()[0].cooldown_secs > 0);
+    }
+}
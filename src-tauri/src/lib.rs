@@ -1,107 +1,978 @@
+mod complexity;
+mod context;
+mod context_cache;
 mod crawler;
 mod database;
+mod embeddings;
 mod gemini;
+mod hints;
+mod http;
+mod keyring;
+mod model_cache;
+mod notes;
+mod prompts;
+mod redact;
+mod response_cache;
+mod review;
+mod runner;
+mod secrets;
+mod templates;
+mod testcases;
+mod tools;
+mod usage;
 
+use complexity::ComplexityCheck;
+use context_cache::ContextCaches;
 use crawler::Problem;
-use database::{ActivityData, ChatRecord, Database, ProblemRecord};
-use gemini::{ChatMessage, GeminiModel};
-use tauri::{AppHandle, Manager, State};
+use database::{
+    ActivityData, ChatRecord, CodeReviewRecord, CodeVersionRecord, Database,
+    GenerationPresetRecord, HintRecord, MessageRecord, ModelPrice, NewMessage, NewTestCase,
+    NoteContent, NoteRecord, ProblemRecord, PromptTemplateRecord, SolveRecord, TestCaseRecord,
+    UsageAggregate, UsageGroup,
+};
+use embeddings::{GeminiEmbeddings, SimilarProblem};
+use gemini::{
+    ChatMessage, ChatOptions, ChatResponse, GeminiClient, GeminiModel, GenerationSettings,
+    GenerationError, StreamEvent, TokenCount, ToolInvocation,
+};
+use hints::HintProgress;
+use http::{HttpClient, NetworkSettings, NetworkStatus};
+use model_cache::ModelCache;
+use redact::CommandResult;
+use response_cache::ResponseCache;
+use runner::SourceCode;
+use secrets::{ApiKeyStatus, SecretStore};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
+use templates::TemplateImport;
+use testcases::TestCaseReport;
+use tools::TutorTools;
+
+/// Builds a client with the stored key, or with `candidate` when the user is trying
+/// out a key that has not been saved yet.
+fn gemini_client(
+    secrets: &SecretStore,
+    http: &HttpClient,
+    candidate: Option<String>,
+) -> Result<GeminiClient, String> {
+    match candidate.map(|key| key.trim().to_string()).filter(|key| !key.is_empty()) {
+        Some(api_key) => Ok(GeminiClient::new(http.client(), &api_key)),
+        None => secrets
+            .ring(secrets::GEMINI)
+            .map(|keys| GeminiClient::with_keys(http.client(), keys))
+            .ok_or_else(|| "Gemini API key is not set".to_string()),
+    }
+}
 
 #[tauri::command]
 async fn fetch_problem(
     db: State<'_, Database>,
+    http: State<'_, HttpClient>,
     problem_id: String,
-) -> Result<Problem, String> {
-    let problem = crawler::fetch_problem(&problem_id).await?;
-    db.save_problem(&problem).map_err(|e| e.to_string())?;
+) -> CommandResult<Problem> {
+    let problem = crawler::fetch_problem(&http.client(), &problem_id).await?;
+    db.save_problem(&problem)?;
     Ok(problem)
 }
 
+fn resolve_chat_options(
+    db: &Database,
+    model: &str,
+    options: Option<ChatOptions>,
+) -> Result<ChatOptions, String> {
+    let mut options = options.unwrap_or_default();
+    if let Some(preset_name) = &options.preset {
+        let preset = db
+            .get_generation_preset(model, preset_name)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Generation preset not found: {}", preset_name))?;
+        let preset_settings: GenerationSettings = serde_json::from_str(&preset.settings_json)
+            .map_err(|e| format!("Invalid generation preset: {}", e))?;
+        options.generation = Some(options.generation.unwrap_or_default().or(preset_settings));
+    }
+    Ok(options)
+}
+
+fn tutor_tools<'a>(
+    db: &'a Database,
+    problem_id: Option<&str>,
+    code: Option<SourceCode>,
+    options: &ChatOptions,
+) -> Result<Option<TutorTools<'a>>, String> {
+    if !options.enable_tools.unwrap_or(false) {
+        return Ok(None);
+    }
+    let Some(problem_id) = problem_id else {
+        return Ok(None);
+    };
+    let problem = db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Problem not found: {}", problem_id))?;
+    Ok(Some(TutorTools::new(db, problem, code)))
+}
+
+fn resolve_system_prompt(
+    db: &Database,
+    problem_id: Option<&str>,
+    code: Option<&SourceCode>,
+    options: &ChatOptions,
+    system_prompt: String,
+) -> Result<String, String> {
+    let template = match (options.template_id, problem_id) {
+        (Some(template_id), _) => Some(
+            db.get_prompt_template(template_id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Prompt template not found: {}", template_id))?,
+        ),
+        (None, Some(problem_id)) => db
+            .get_problem_template(problem_id)
+            .map_err(|e| e.to_string())?,
+        (None, None) => None,
+    };
+
+    match template {
+        Some(template) => templates::render_for_problem(db, &template, problem_id, code),
+        None => Ok(system_prompt),
+    }
+}
+
+/// The statement of the problem being discussed, sent ahead of the conversation.
+fn problem_context(db: &Database, problem_id: Option<&str>) -> Result<Option<String>, String> {
+    let Some(problem_id) = problem_id else {
+        return Ok(None);
+    };
+    Ok(db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .map(|problem| prompts::problem_context(&problem)))
+}
+
+fn resolve_thread(
+    db: &Database,
+    problem_id: Option<&str>,
+    thread_id: Option<i64>,
+) -> Result<Option<ChatRecord>, String> {
+    match (thread_id, problem_id) {
+        (Some(thread_id), _) => db.get_chat_thread(thread_id).map_err(|e| e.to_string()),
+        (None, Some(problem_id)) => db.get_chat_by_problem(problem_id).map_err(|e| e.to_string()),
+        (None, None) => Ok(None),
+    }
+}
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn chat_with_ai(
-    api_key: String,
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    response_cache: State<'_, ResponseCache>,
+    context_caches: State<'_, ContextCaches>,
     model: String,
     messages: Vec<ChatMessage>,
     system_prompt: String,
-) -> Result<String, String> {
-    gemini::chat(&api_key, &model, messages, &system_prompt).await
+    problem_id: Option<String>,
+    thread_id: Option<i64>,
+    code: Option<SourceCode>,
+    options: Option<ChatOptions>,
+) -> CommandResult<ChatResponse> {
+    let options = resolve_chat_options(&db, &model, options)?;
+    let system_prompt = resolve_system_prompt(
+        &db,
+        problem_id.as_deref(),
+        code.as_ref(),
+        &options,
+        system_prompt,
+    )?;
+    let client = gemini_client(&secrets, &http, None)?
+        .with_cache(response_cache.inner().clone())
+        .with_context_caches(context_caches.inner().clone())
+        .with_problem_context(problem_context(&db, problem_id.as_deref())?);
+    let thread = resolve_thread(&db, problem_id.as_deref(), thread_id)?;
+    let messages = context::fit_to_budget(
+        &client,
+        &db,
+        thread.as_ref(),
+        &model,
+        messages,
+        &system_prompt,
+        &options,
+    )
+    .await?;
+
+    let tools = tutor_tools(&db, problem_id.as_deref(), code, &options)?;
+
+    let started = Instant::now();
+    let result = client
+        .chat(
+            &model,
+            messages,
+            &system_prompt,
+            &options,
+            tools.as_ref().map(|t| t as &dyn gemini::ToolExecutor),
+        )
+        .await;
+    usage::record(&db, problem_id.as_deref(), "chat", &model, started, &result);
+    Ok(result?)
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn chat_with_ai_stream(
     app: AppHandle,
-    api_key: String,
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    response_cache: State<'_, ResponseCache>,
+    context_caches: State<'_, ContextCaches>,
     model: String,
     messages: Vec<ChatMessage>,
     system_prompt: String,
     session_id: String,
-) -> Result<String, String> {
-    gemini::chat_stream(app, &api_key, &model, messages, &system_prompt, session_id).await
+    problem_id: Option<String>,
+    thread_id: Option<i64>,
+    code: Option<SourceCode>,
+    options: Option<ChatOptions>,
+) -> CommandResult<ChatResponse> {
+    let event_name = format!("chat-stream-{}", session_id);
+    let emit = |event: StreamEvent| {
+        let _ = app.emit(&event_name, event);
+    };
+
+    // A failure before streaming starts still ends the stream, so the listener stops waiting.
+    let prepared: Result<_, String> = async {
+        let options = resolve_chat_options(&db, &model, options)?;
+        let system_prompt = resolve_system_prompt(
+            &db,
+            problem_id.as_deref(),
+            code.as_ref(),
+            &options,
+            system_prompt,
+        )?;
+        let client = gemini_client(&secrets, &http, None)?
+            .with_cache(response_cache.inner().clone())
+            .with_context_caches(context_caches.inner().clone())
+            .with_problem_context(problem_context(&db, problem_id.as_deref())?);
+        let thread = resolve_thread(&db, problem_id.as_deref(), thread_id)?;
+        let messages = context::fit_to_budget(
+            &client,
+            &db,
+            thread.as_ref(),
+            &model,
+            messages,
+            &system_prompt,
+            &options,
+        )
+        .await?;
+        let tools = tutor_tools(&db, problem_id.as_deref(), code, &options)?;
+        Ok((options, system_prompt, client, messages, tools))
+    }
+    .await;
+    let (options, system_prompt, client, messages, tools) = match prepared {
+        Ok(prepared) => prepared,
+        Err(error) => {
+            emit(StreamEvent::error(&GenerationError::from(error.clone())));
+            return Err(error.into());
+        }
+    };
+
+    let started = Instant::now();
+    let result = client
+        .chat_stream(
+            &model,
+            messages,
+            &system_prompt,
+            &options,
+            tools.as_ref().map(|t| t as &dyn gemini::ToolExecutor),
+            &emit,
+        )
+        .await;
+    usage::record(&db, problem_id.as_deref(), "chat", &model, started, &result);
+    Ok(result?)
 }
 
 #[tauri::command]
-async fn get_all_problems(db: State<'_, Database>) -> Result<Vec<ProblemRecord>, String> {
-    db.get_all_problems().map_err(|e| e.to_string())
+async fn count_tokens(
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    model: String,
+    messages: Vec<ChatMessage>,
+    system_prompt: String,
+) -> CommandResult<TokenCount> {
+    Ok(gemini_client(&secrets, &http, None)?
+        .count_tokens(&model, &messages, &system_prompt)
+        .await)
 }
 
 #[tauri::command]
-async fn get_cached_problem(
+async fn get_all_problems(db: State<'_, Database>) -> CommandResult<Vec<ProblemRecord>> {
+    Ok(db.get_all_problems()?)
+}
+
+#[tauri::command]
+async fn find_similar_problems(
     db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
     problem_id: String,
-) -> Result<Option<ProblemRecord>, String> {
-    db.get_problem(&problem_id).map_err(|e| e.to_string())
+    limit: Option<usize>,
+    model: Option<String>,
+) -> CommandResult<Vec<SimilarProblem>> {
+    let provider = GeminiEmbeddings::new(gemini_client(&secrets, &http, None)?, model);
+    let limit = limit.unwrap_or(embeddings::DEFAULT_SIMILAR_LIMIT);
+    Ok(embeddings::find_similar(&db, &provider, &problem_id, limit).await?)
 }
 
 #[tauri::command]
-async fn save_chat(
+async fn get_cached_problem(
     db: State<'_, Database>,
     problem_id: String,
-    messages_json: String,
-) -> Result<i64, String> {
-    db.save_chat(&problem_id, &messages_json)
-        .map_err(|e| e.to_string())
+) -> CommandResult<Option<ProblemRecord>> {
+    Ok(db.get_problem(&problem_id)?)
 }
 
 #[tauri::command]
 async fn get_chat_by_problem(
     db: State<'_, Database>,
     problem_id: String,
-) -> Result<Option<ChatRecord>, String> {
-    db.get_chat_by_problem(&problem_id)
-        .map_err(|e| e.to_string())
+) -> CommandResult<Option<ChatRecord>> {
+    Ok(db.get_chat_by_problem(&problem_id)?)
+}
+
+#[tauri::command]
+async fn create_chat_thread(
+    db: State<'_, Database>,
+    problem_id: String,
+    title: String,
+) -> CommandResult<ChatRecord> {
+    if title.trim().is_empty() {
+        return Err("Thread title is required".into());
+    }
+    Ok(db.create_chat_thread(&problem_id, title.trim())?)
 }
 
 #[tauri::command]
-async fn delete_problem(db: State<'_, Database>, problem_id: String) -> Result<(), String> {
-    db.delete_problem(&problem_id).map_err(|e| e.to_string())
+async fn get_chat_threads(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<Vec<ChatRecord>> {
+    Ok(db.get_chat_threads(&problem_id)?)
 }
 
 #[tauri::command]
-async fn record_solve(db: State<'_, Database>, problem_id: String) -> Result<i64, String> {
-    db.record_solve(&problem_id).map_err(|e| e.to_string())
+async fn get_chat_thread(
+    db: State<'_, Database>,
+    thread_id: i64,
+) -> CommandResult<Option<ChatRecord>> {
+    Ok(db.get_chat_thread(thread_id)?)
 }
 
 #[tauri::command]
-async fn unrecord_solve(db: State<'_, Database>, problem_id: String) -> Result<bool, String> {
-    db.unrecord_solve(&problem_id).map_err(|e| e.to_string())
+async fn rename_chat_thread(
+    db: State<'_, Database>,
+    thread_id: i64,
+    title: String,
+) -> CommandResult<bool> {
+    if title.trim().is_empty() {
+        return Err("Thread title is required".into());
+    }
+    Ok(db.rename_chat_thread(thread_id, title.trim())?)
 }
 
 #[tauri::command]
-async fn is_solved_today(db: State<'_, Database>, problem_id: String) -> Result<bool, String> {
-    db.is_solved_today(&problem_id).map_err(|e| e.to_string())
+async fn delete_chat_thread(db: State<'_, Database>, thread_id: i64) -> CommandResult<bool> {
+    Ok(db.delete_chat_thread(thread_id)?)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn add_chat_message(
+    db: State<'_, Database>,
+    thread_id: i64,
+    parent_id: Option<i64>,
+    role: String,
+    content: String,
+    model: Option<String>,
+    tokens: Option<i64>,
+    tool_calls: Option<Vec<ToolInvocation>>,
+    thoughts: Option<String>,
+) -> CommandResult<MessageRecord> {
+    if role != "user" && role != "assistant" {
+        return Err(format!("Invalid message role: {}", role).into());
+    }
+    if let Some(parent_id) = parent_id {
+        let parent = db
+            .get_message(parent_id)?
+            .ok_or_else(|| format!("Message not found: {}", parent_id))?;
+        if parent.thread_id != thread_id {
+            return Err("Parent message belongs to another thread".into());
+        }
+    }
+    let tool_calls_json =
+        serde_json::to_string(&tool_calls.unwrap_or_default())?;
+
+    let id = db
+        .add_message(&NewMessage {
+            thread_id,
+            parent_id,
+            role: &role,
+            content: &content,
+            model: model.as_deref(),
+            tokens,
+            tool_calls_json: &tool_calls_json,
+            thoughts: thoughts.as_deref().unwrap_or_default(),
+        })?;
+    Ok(db.get_message(id)?
+        .ok_or_else(|| format!("Message not found: {}", id))?)
+}
+
+#[tauri::command]
+async fn edit_chat_message(
+    db: State<'_, Database>,
+    message_id: i64,
+    content: String,
+) -> CommandResult<MessageRecord> {
+    let id = db
+        .edit_message(message_id, &content)?
+        .ok_or_else(|| format!("Message not found: {}", message_id))?;
+    Ok(db.get_message(id)?
+        .ok_or_else(|| format!("Message not found: {}", id))?)
+}
+
+#[tauri::command]
+async fn get_active_branch(
+    db: State<'_, Database>,
+    thread_id: i64,
+) -> CommandResult<Vec<MessageRecord>> {
+    Ok(db.get_active_branch(thread_id)?)
+}
+
+#[tauri::command]
+async fn get_message_branch(
+    db: State<'_, Database>,
+    message_id: i64,
+) -> CommandResult<Vec<MessageRecord>> {
+    Ok(db.get_branch(message_id)?)
+}
+
+#[tauri::command]
+async fn get_message_siblings(
+    db: State<'_, Database>,
+    message_id: i64,
+) -> CommandResult<Vec<MessageRecord>> {
+    Ok(db.get_message_siblings(message_id)?)
+}
+
+#[tauri::command]
+async fn switch_chat_branch(
+    db: State<'_, Database>,
+    message_id: i64,
+) -> CommandResult<Vec<MessageRecord>> {
+    let thread_id = db
+        .switch_branch(message_id)?
+        .ok_or_else(|| format!("Message not found: {}", message_id))?;
+    Ok(db.get_active_branch(thread_id)?)
+}
+
+#[tauri::command]
+async fn delete_problem(db: State<'_, Database>, problem_id: String) -> CommandResult<()> {
+    Ok(db.delete_problem(&problem_id)?)
+}
+
+#[tauri::command]
+async fn record_solve(db: State<'_, Database>, problem_id: String) -> CommandResult<i64> {
+    Ok(db.record_solve(&problem_id)?)
+}
+
+#[tauri::command]
+async fn unrecord_solve(db: State<'_, Database>, problem_id: String) -> CommandResult<bool> {
+    Ok(db.unrecord_solve(&problem_id)?)
+}
+
+#[tauri::command]
+async fn get_solve_records(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<Vec<SolveRecord>> {
+    Ok(db.get_solve_records(&problem_id)?)
+}
+
+#[tauri::command]
+async fn is_solved_today(db: State<'_, Database>, problem_id: String) -> CommandResult<bool> {
+    Ok(db.is_solved_today(&problem_id)?)
 }
 
 #[tauri::command]
 async fn get_activity_data(
     db: State<'_, Database>,
     days: i32,
-) -> Result<Vec<ActivityData>, String> {
-    db.get_activity_data(days).map_err(|e| e.to_string())
+) -> CommandResult<Vec<ActivityData>> {
+    Ok(db.get_activity_data(days)?)
+}
+
+#[tauri::command]
+async fn get_available_models(
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    model_cache: State<'_, ModelCache>,
+    api_key: Option<String>,
+    include_all: Option<bool>,
+    refresh: Option<bool>,
+) -> CommandResult<Vec<GeminiModel>> {
+    // A key that is only being tried out must not overwrite the cached list.
+    let trying_key = api_key.as_deref().is_some_and(|k| !k.trim().is_empty());
+    let cached = (!trying_key && !refresh.unwrap_or(false))
+        .then(|| model_cache.load())
+        .flatten();
+
+    let models = match cached {
+        Some(models) => models,
+        None => {
+            let models = gemini_client(&secrets, &http, api_key)?.fetch_all_models().await?;
+            if !trying_key {
+                model_cache.store(&models)?;
+            }
+            models
+        }
+    };
+
+    if include_all.unwrap_or(false) {
+        return Ok(models);
+    }
+    Ok(models.into_iter().filter(GeminiModel::can_chat).collect())
+}
+
+#[tauri::command]
+async fn set_api_key(
+    secrets: State<'_, SecretStore>,
+    model_cache: State<'_, ModelCache>,
+    api_key: String,
+    provider: Option<String>,
+    label: Option<String>,
+) -> CommandResult<ApiKeyStatus> {
+    let provider = secrets::provider_or_default(provider)?;
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        return Err("API key is empty".into());
+    }
+    let label = label
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| keyring::DEFAULT_LABEL.to_string());
+    secrets.set(&provider, &label, api_key)?;
+    model_cache.clear();
+    Ok(secrets.status(&provider))
+}
+
+#[tauri::command]
+async fn remove_api_key(
+    secrets: State<'_, SecretStore>,
+    model_cache: State<'_, ModelCache>,
+    label: String,
+    provider: Option<String>,
+) -> CommandResult<ApiKeyStatus> {
+    let provider = secrets::provider_or_default(provider)?;
+    if secrets.remove(&provider, &label)? {
+        model_cache.clear();
+    }
+    Ok(secrets.status(&provider))
+}
+
+#[tauri::command]
+async fn test_api_key(
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    api_key: Option<String>,
+) -> CommandResult<usize> {
+    let models = gemini_client(&secrets, &http, api_key)?
+        .fetch_available_models()
+        .await?;
+    Ok(models.len())
+}
+
+#[tauri::command]
+async fn clear_api_key(
+    secrets: State<'_, SecretStore>,
+    model_cache: State<'_, ModelCache>,
+    provider: Option<String>,
+) -> CommandResult<bool> {
+    model_cache.clear();
+    Ok(secrets.clear(&secrets::provider_or_default(provider)?)?)
+}
+
+#[tauri::command]
+async fn get_api_key_status(
+    secrets: State<'_, SecretStore>,
+    provider: Option<String>,
+) -> CommandResult<ApiKeyStatus> {
+    Ok(secrets.status(&secrets::provider_or_default(provider)?))
+}
+
+#[tauri::command]
+async fn clear_response_cache(response_cache: State<'_, ResponseCache>) -> CommandResult<()> {
+    Ok(response_cache.clear()?)
+}
+
+#[tauri::command]
+async fn get_network_settings(http: State<'_, HttpClient>) -> CommandResult<NetworkStatus> {
+    Ok(http.status())
+}
+
+#[tauri::command]
+async fn set_network_settings(
+    http: State<'_, HttpClient>,
+    settings: NetworkSettings,
+) -> CommandResult<NetworkStatus> {
+    http.update(settings)?;
+    Ok(http.status())
+}
+
+#[tauri::command]
+async fn save_generation_preset(
+    db: State<'_, Database>,
+    model: String,
+    name: String,
+    settings: GenerationSettings,
+) -> CommandResult<i64> {
+    let settings_json = serde_json::to_string(&settings)?;
+    Ok(db.save_generation_preset(&model, &name, &settings_json)?)
+}
+
+#[tauri::command]
+async fn get_generation_presets(
+    db: State<'_, Database>,
+    model: Option<String>,
+) -> CommandResult<Vec<GenerationPresetRecord>> {
+    Ok(db.get_generation_presets(model.as_deref())?)
+}
+
+#[tauri::command]
+async fn delete_generation_preset(db: State<'_, Database>, id: i64) -> CommandResult<bool> {
+    Ok(db.delete_generation_preset(id)?)
+}
+
+#[tauri::command]
+async fn get_prompt_templates(
+    db: State<'_, Database>,
+) -> CommandResult<Vec<PromptTemplateRecord>> {
+    Ok(db.get_prompt_templates()?)
+}
+
+#[tauri::command]
+async fn save_prompt_template(
+    db: State<'_, Database>,
+    name: String,
+    description: String,
+    body: String,
+) -> CommandResult<i64> {
+    if name.trim().is_empty() || body.trim().is_empty() {
+        return Err("Template name and body are required".into());
+    }
+    Ok(db.save_prompt_template(name.trim(), &description, &body)?)
+}
+
+#[tauri::command]
+async fn delete_prompt_template(db: State<'_, Database>, id: i64) -> CommandResult<bool> {
+    Ok(db.delete_prompt_template(id)?)
+}
+
+#[tauri::command]
+async fn render_prompt_template(
+    db: State<'_, Database>,
+    template_id: i64,
+    problem_id: Option<String>,
+    code: Option<SourceCode>,
+) -> CommandResult<String> {
+    let template = db
+        .get_prompt_template(template_id)?
+        .ok_or_else(|| format!("Prompt template not found: {}", template_id))?;
+    Ok(templates::render_for_problem(&db, &template, problem_id.as_deref(), code.as_ref())?)
+}
+
+#[tauri::command]
+async fn set_problem_template(
+    db: State<'_, Database>,
+    problem_id: String,
+    template_id: Option<i64>,
+) -> CommandResult<()> {
+    Ok(db.set_problem_template(&problem_id, template_id)?)
+}
+
+#[tauri::command]
+async fn get_problem_template(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<Option<PromptTemplateRecord>> {
+    Ok(db.get_problem_template(&problem_id)?)
+}
+
+#[tauri::command]
+async fn export_prompt_templates(
+    db: State<'_, Database>,
+    path: String,
+    ids: Option<Vec<i64>>,
+) -> CommandResult<usize> {
+    let templates: Vec<PromptTemplateRecord> = db
+        .get_prompt_templates()?
+        .into_iter()
+        .filter(|t| ids.as_ref().is_none_or(|ids| ids.contains(&t.id)))
+        .collect();
+    templates::export_to_file(&templates, std::path::Path::new(&path))?;
+    Ok(templates.len())
+}
+
+#[tauri::command]
+async fn import_prompt_templates(
+    db: State<'_, Database>,
+    path: String,
+) -> CommandResult<TemplateImport> {
+    Ok(templates::import(&db, std::path::Path::new(&path))?)
+}
+
+#[tauri::command]
+async fn get_usage_aggregates(
+    db: State<'_, Database>,
+    group_by: String,
+    days: i32,
+) -> CommandResult<Vec<UsageAggregate>> {
+    let group = UsageGroup::parse(&group_by)?;
+    let days = u32::try_from(days)
+        .ok()
+        .filter(|days| *days > 0)
+        .ok_or_else(|| format!("Days must be a positive number: {}", days))?;
+    Ok(db.get_usage_aggregates(group, days)?)
+}
+
+#[tauri::command]
+async fn set_model_price(
+    db: State<'_, Database>,
+    model: String,
+    input_per_million: f64,
+    output_per_million: f64,
+) -> CommandResult<()> {
+    Ok(db.set_model_price(&model, input_per_million, output_per_million)?)
+}
+
+#[tauri::command]
+async fn get_model_prices(db: State<'_, Database>) -> CommandResult<Vec<ModelPrice>> {
+    Ok(db.get_model_prices()?)
+}
+
+#[tauri::command]
+async fn delete_model_price(db: State<'_, Database>, model: String) -> CommandResult<bool> {
+    Ok(db.delete_model_price(&model)?)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn generate_test_cases(
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    response_cache: State<'_, ResponseCache>,
+    model: String,
+    problem_id: String,
+    count: Option<u32>,
+    reference: Option<SourceCode>,
+    options: Option<ChatOptions>,
+) -> CommandResult<TestCaseReport> {
+    let options = resolve_chat_options(&db, &model, options)?;
+    let client = gemini_client(&secrets, &http, None)?.with_cache(response_cache.inner().clone());
+    Ok(testcases::generate(
+        &client,
+        &db,
+        &problem_id,
+        &model,
+        count,
+        reference.as_ref(),
+        options,
+    )
+    .await?)
+}
+
+#[tauri::command]
+async fn get_test_cases(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<Vec<TestCaseRecord>> {
+    Ok(db.get_test_cases(&problem_id)?)
+}
+
+#[tauri::command]
+async fn save_test_case(
+    db: State<'_, Database>,
+    problem_id: String,
+    input: String,
+    expected_output: String,
+    rationale: Option<String>,
+) -> CommandResult<i64> {
+    Ok(db.save_test_case(&NewTestCase {
+        problem_id: &problem_id,
+        input: &input,
+        expected_output: &expected_output,
+        rationale: rationale.as_deref().unwrap_or_default(),
+        source: "manual",
+        verified: false,
+    })?)
+}
+
+#[tauri::command]
+async fn delete_test_case(db: State<'_, Database>, id: i64) -> CommandResult<bool> {
+    Ok(db.delete_test_case(id)?)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn review_code(
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    response_cache: State<'_, ResponseCache>,
+    model: String,
+    problem_id: String,
+    code: SourceCode,
+    options: Option<ChatOptions>,
+) -> CommandResult<CodeReviewRecord> {
+    let options = resolve_chat_options(&db, &model, options)?;
+    let client = gemini_client(&secrets, &http, None)?.with_cache(response_cache.inner().clone());
+    Ok(review::review(&client, &db, &problem_id, &model, &code, options).await?)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn check_complexity(
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    response_cache: State<'_, ResponseCache>,
+    model: String,
+    problem_id: String,
+    code: SourceCode,
+    options: Option<ChatOptions>,
+) -> CommandResult<ComplexityCheck> {
+    let options = resolve_chat_options(&db, &model, options)?;
+    let client = gemini_client(&secrets, &http, None)?.with_cache(response_cache.inner().clone());
+    Ok(complexity::check(&client, &db, &problem_id, &model, &code, options).await?)
+}
+
+#[tauri::command]
+async fn get_code_versions(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<Vec<CodeVersionRecord>> {
+    Ok(db.get_code_versions(&problem_id)?)
+}
+
+#[tauri::command]
+async fn get_code_reviews(
+    db: State<'_, Database>,
+    code_version_id: i64,
+) -> CommandResult<Vec<CodeReviewRecord>> {
+    Ok(db.get_code_reviews(code_version_id)?)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn generate_hints(
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    response_cache: State<'_, ResponseCache>,
+    model: String,
+    problem_id: String,
+    regenerate: Option<bool>,
+    options: Option<ChatOptions>,
+) -> CommandResult<HintProgress> {
+    let options = resolve_chat_options(&db, &model, options)?;
+    let client = gemini_client(&secrets, &http, None)?.with_cache(response_cache.inner().clone());
+    Ok(hints::generate(
+        &client,
+        &db,
+        &problem_id,
+        &model,
+        regenerate.unwrap_or(false),
+        options,
+    )
+    .await?)
+}
+
+#[tauri::command]
+async fn get_hint_progress(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<HintProgress> {
+    Ok(hints::progress(&db, &problem_id)?)
+}
+
+#[tauri::command]
+async fn reveal_next_hint(
+    db: State<'_, Database>,
+    problem_id: String,
+) -> CommandResult<Option<HintRecord>> {
+    Ok(db.reveal_next_hint(&problem_id)?)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn generate_note(
+    db: State<'_, Database>,
+    secrets: State<'_, SecretStore>,
+    http: State<'_, HttpClient>,
+    model: String,
+    problem_id: String,
+    code: Option<SourceCode>,
+    overwrite: Option<bool>,
+    options: Option<ChatOptions>,
+) -> CommandResult<NoteRecord> {
+    let options = resolve_chat_options(&db, &model, options)?;
+    // Regenerating is asking for a different note, so the response cache is skipped.
+    let client = gemini_client(&secrets, &http, None)?;
+    Ok(notes::generate(
+        &client,
+        &db,
+        &problem_id,
+        &model,
+        code.as_ref(),
+        overwrite.unwrap_or(false),
+        options,
+    )
+    .await?)
+}
+
+#[tauri::command]
+async fn get_note(db: State<'_, Database>, problem_id: String) -> CommandResult<Option<NoteRecord>> {
+    Ok(db.get_note(&problem_id)?)
+}
+
+#[tauri::command]
+async fn update_note(
+    db: State<'_, Database>,
+    id: i64,
+    key_idea: String,
+    algorithm: String,
+    complexity: String,
+    pitfalls: Vec<String>,
+) -> CommandResult<Option<NoteRecord>> {
+    let pitfalls_json = serde_json::to_string(&pitfalls)?;
+    Ok(db.update_note(
+        id,
+        &NoteContent {
+            key_idea: &key_idea,
+            algorithm: &algorithm,
+            complexity: &complexity,
+            pitfalls_json: &pitfalls_json,
+        },
+    )?)
+}
+
+#[tauri::command]
+async fn search_notes(db: State<'_, Database>, query: String) -> CommandResult<Vec<NoteRecord>> {
+    Ok(db.search_notes(&query)?)
 }
 
 #[tauri::command]
-async fn get_available_models(api_key: String) -> Result<Vec<GeminiModel>, String> {
-    gemini::fetch_available_models(&api_key).await
+async fn delete_note(db: State<'_, Database>, id: i64) -> CommandResult<bool> {
+    Ok(db.delete_note(id)?)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -116,22 +987,79 @@ pub fn run() {
             let db_path = app_data_dir.join("psup.db");
             let db = Database::new(db_path).expect("Failed to initialize database");
             app.manage(db);
+            app.manage(SecretStore::open(&app_data_dir));
+            app.manage(ModelCache::open(&app_data_dir));
+            app.manage(ResponseCache::open(&app_data_dir));
+            app.manage(ContextCaches::default());
+            app.manage(HttpClient::open(&app_data_dir));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             fetch_problem,
             chat_with_ai,
             chat_with_ai_stream,
+            count_tokens,
             get_all_problems,
             get_cached_problem,
-            save_chat,
+            find_similar_problems,
             get_chat_by_problem,
+            create_chat_thread,
+            get_chat_threads,
+            get_chat_thread,
+            rename_chat_thread,
+            delete_chat_thread,
+            add_chat_message,
+            edit_chat_message,
+            get_active_branch,
+            get_message_branch,
+            get_message_siblings,
+            switch_chat_branch,
             delete_problem,
             record_solve,
             unrecord_solve,
+            get_solve_records,
             is_solved_today,
             get_activity_data,
-            get_available_models
+            get_available_models,
+            set_api_key,
+            remove_api_key,
+            test_api_key,
+            clear_api_key,
+            get_api_key_status,
+            clear_response_cache,
+            get_network_settings,
+            set_network_settings,
+            save_generation_preset,
+            get_generation_presets,
+            delete_generation_preset,
+            get_prompt_templates,
+            save_prompt_template,
+            delete_prompt_template,
+            render_prompt_template,
+            set_problem_template,
+            get_problem_template,
+            export_prompt_templates,
+            import_prompt_templates,
+            get_usage_aggregates,
+            set_model_price,
+            get_model_prices,
+            delete_model_price,
+            generate_test_cases,
+            get_test_cases,
+            save_test_case,
+            delete_test_case,
+            review_code,
+            check_complexity,
+            get_code_versions,
+            get_code_reviews,
+            generate_hints,
+            get_hint_progress,
+            reveal_next_hint,
+            generate_note,
+            get_note,
+            update_note,
+            search_notes,
+            delete_note
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
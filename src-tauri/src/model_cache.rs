@@ -0,0 +1,94 @@
+use crate::gemini::GeminiModel;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const CACHE_FILE: &str = "models.json";
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct CachedModels {
+    fetched_at: u64,
+    models: Vec<GeminiModel>,
+}
+
+/// The full model list (chat-capable or not) kept on disk, since it rarely changes
+/// and listing it takes several requests when it is paginated.
+pub struct ModelCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl ModelCache {
+    pub fn open(dir: &Path) -> Self {
+        Self::with_ttl(dir, DEFAULT_TTL)
+    }
+
+    pub fn with_ttl(dir: &Path, ttl: Duration) -> Self {
+        ModelCache {
+            path: dir.join(CACHE_FILE),
+            ttl,
+        }
+    }
+
+    /// The cached list, or `None` when there is none or it is older than the TTL.
+    pub fn load(&self) -> Option<Vec<GeminiModel>> {
+        let json = std::fs::read_to_string(&self.path).ok()?;
+        let cached: CachedModels = serde_json::from_str(&json).ok()?;
+        let age = now_secs().saturating_sub(cached.fetched_at);
+        (age < self.ttl.as_secs()).then_some(cached.models)
+    }
+
+    pub fn store(&self, models: &[GeminiModel]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        let json = serde_json::to_string(&CachedModels {
+            fetched_at: now_secs(),
+            models: models.to_vec(),
+        })
+        .map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| format!("Failed to cache models: {}", e))
+    }
+
+    pub fn clear(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serves_fresh_lists_and_expires_stale_ones() {
+        let dir = std::env::temp_dir().join(format!("psup-models-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let model = GeminiModel {
+            name: "gemini-2.5-flash".to_string(),
+            display_name: "Gemini 2.5 Flash".to_string(),
+            version: "001".to_string(),
+            input_token_limit: 1048576,
+            output_token_limit: 65536,
+            supported_generation_methods: vec!["generateContent".to_string()],
+            thinking: true,
+        };
+
+        let cache = ModelCache::open(&dir);
+        assert!(cache.load().is_none());
+        cache.store(&[model]).unwrap();
+        assert_eq!(cache.load().unwrap()[0].name, "gemini-2.5-flash");
+        assert!(ModelCache::with_ttl(&dir, Duration::ZERO).load().is_none());
+
+        cache.clear();
+        assert!(cache.load().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
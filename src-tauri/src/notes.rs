@@ -0,0 +1,257 @@
+use crate::database::{Database, MessageRecord, NoteContent, NoteRecord};
+use crate::gemini::{parse_json, ChatMessage, ChatOptions, GeminiClient};
+use crate::prompts;
+use crate::runner::SourceCode;
+use crate::usage;
+use serde::Deserialize;
+use std::time::Instant;
+
+const EDITED_NOTE_ERROR: &str = "The note was edited by hand; confirm to overwrite it";
+/// Only the end of long tutoring sessions is sent, where the solution usually settled.
+const MAX_TRANSCRIPT_CHARS: usize = 8000;
+const NOTE_PROMPT: &str =
+    "The student has solved the competitive programming problem below with the accepted code shown. \
+Write a short editorial for their personal library: the key idea or observation that unlocks the problem, \
+the algorithm step by step, the time and space complexity, and the pitfalls worth remembering. \
+Use the tutoring conversation to include the mistakes the student actually made along the way. \
+Write everything in Korean.";
+
+#[derive(Debug, Deserialize)]
+struct NoteResponse {
+    key_idea: String,
+    algorithm: String,
+    complexity: String,
+    #[serde(default)]
+    pitfalls: Vec<String>,
+}
+
+fn note_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "key_idea": { "type": "STRING" },
+            "algorithm": { "type": "STRING" },
+            "complexity": { "type": "STRING" },
+            "pitfalls": { "type": "ARRAY", "items": { "type": "STRING" } }
+        },
+        "required": ["key_idea", "algorithm", "complexity", "pitfalls"]
+    })
+}
+
+/// The conversation as `role: content` lines, keeping its most recent part.
+fn transcript(messages: &[MessageRecord]) -> String {
+    let lines: Vec<String> = messages
+        .iter()
+        .filter(|m| !m.content.trim().is_empty())
+        .map(|m| format!("{}: {}", m.role, m.content.trim()))
+        .collect();
+
+    let mut kept = Vec::new();
+    let mut length = 0;
+    for line in lines.iter().rev() {
+        length += line.chars().count() + 1;
+        if length > MAX_TRANSCRIPT_CHARS && !kept.is_empty() {
+            break;
+        }
+        kept.push(line.as_str());
+    }
+    kept.reverse();
+    kept.join("\n")
+}
+
+/// Writes the editorial note for a solved problem from its statement, the accepted
+/// code (the latest saved version when `source` is omitted) and the newest chat.
+/// A note the user edited is only replaced with `overwrite`.
+pub async fn generate(
+    client: &GeminiClient,
+    db: &Database,
+    problem_id: &str,
+    model: &str,
+    source: Option<&SourceCode>,
+    overwrite: bool,
+    options: ChatOptions,
+) -> Result<NoteRecord, String> {
+    let edited = db
+        .get_note(problem_id)
+        .map_err(|e| e.to_string())?
+        .is_some_and(|note| note.edited);
+    if edited && !overwrite {
+        return Err(EDITED_NOTE_ERROR.to_string());
+    }
+
+    let problem = db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Problem not found: {}", problem_id))?;
+
+    let (code_version_id, language, code) = match source {
+        Some(source) if !source.code.trim().is_empty() => {
+            let id = db
+                .save_code_version(problem_id, &source.language, &source.code)
+                .map_err(|e| e.to_string())?;
+            (id, source.language.clone(), source.code.clone())
+        }
+        _ => {
+            let latest = db
+                .get_code_versions(problem_id)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .next()
+                .ok_or_else(|| "No accepted code to write a note about".to_string())?;
+            (latest.id, latest.language, latest.code)
+        }
+    };
+
+    let conversation = match db
+        .get_chat_threads(problem_id)
+        .map_err(|e| e.to_string())?
+        .first()
+    {
+        Some(thread) => transcript(&db.get_active_branch(thread.id).map_err(|e| e.to_string())?),
+        None => String::new(),
+    };
+
+    let mut content = format!(
+        "{}\n\n{}\n\n[정답 코드 ({})]\n{}",
+        NOTE_PROMPT,
+        prompts::problem_context(&problem),
+        language,
+        code
+    );
+    if !conversation.is_empty() {
+        content.push_str(&format!("\n\n[튜터링 대화]\n{}", conversation));
+    }
+    let options = ChatOptions {
+        max_continuations: Some(0),
+        response_schema: Some(note_schema()),
+        ..options
+    };
+
+    let started = Instant::now();
+    let result = client
+        .chat(model, vec![ChatMessage::user(content)], "", &options, None)
+        .await;
+    usage::record(db, Some(problem_id), "note", model, started, &result);
+    let response = result?;
+    let parsed: NoteResponse = parse_json(&response.text)?;
+    let pitfalls: Vec<String> = parsed
+        .pitfalls
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let pitfalls_json = serde_json::to_string(&pitfalls).map_err(|e| e.to_string())?;
+
+    db.save_note(
+        problem_id,
+        &NoteContent {
+            key_idea: parsed.key_idea.trim(),
+            algorithm: parsed.algorithm.trim(),
+            complexity: parsed.complexity.trim(),
+            pitfalls_json: &pitfalls_json,
+        },
+        Some(code_version_id),
+        &response.model,
+        overwrite,
+    )
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| EDITED_NOTE_ERROR.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::{test_db, test_problem};
+
+    fn message(role: &str, content: &str) -> MessageRecord {
+        MessageRecord {
+            id: 0,
+            thread_id: 0,
+            parent_id: None,
+            role: role.to_string(),
+            content: content.to_string(),
+            model: None,
+            tokens: None,
+            tool_calls_json: "[]".to_string(),
+            thoughts: String::new(),
+            sibling_index: 0,
+            sibling_count: 1,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn transcript_keeps_the_latest_messages() {
+        let long = "x".repeat(MAX_TRANSCRIPT_CHARS);
+        let messages = [
+            message("user", &long),
+            message("assistant", " "),
+            message("user", "왜 틀렸나요?"),
+            message("assistant", "int 범위를 넘습니다."),
+        ];
+        assert_eq!(
+            transcript(&messages),
+            "user: 왜 틀렸나요?\nassistant: int 범위를 넘습니다."
+        );
+    }
+
+    #[test]
+    fn saves_edits_and_searches_notes() {
+        let db = test_db("notes");
+        db.save_problem(&test_problem("1753", "최단경로")).unwrap();
+
+        let content = NoteContent {
+            key_idea: "음수 간선이 없다",
+            algorithm: "다익스트라",
+            complexity: "O(E log V)",
+            pitfalls_json: "[\"100%_done\"]",
+        };
+        let note = db
+            .save_note("1753", &content, None, "model", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(note.title, "최단경로");
+        assert!(!note.edited);
+
+        let edited = db
+            .update_note(
+                note.id,
+                &NoteContent {
+                    algorithm: "우선순위 큐 다익스트라",
+                    ..content
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert!(edited.edited);
+        assert_eq!(
+            db.get_note("1753").unwrap().unwrap().algorithm,
+            "우선순위 큐 다익스트라"
+        );
+
+        assert_eq!(db.search_notes("").unwrap().len(), 1);
+        assert_eq!(db.search_notes("최단 우선순위").unwrap().len(), 1);
+        assert_eq!(db.search_notes("100%_").unwrap().len(), 1);
+        assert!(db.search_notes("100_%").unwrap().is_empty());
+        assert!(db.search_notes("플로이드").unwrap().is_empty());
+
+        // A regenerated note never silently replaces the user's edits.
+        assert!(db
+            .save_note("1753", &content, None, "model", false)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            db.get_note("1753").unwrap().unwrap().algorithm,
+            "우선순위 큐 다익스트라"
+        );
+        let regenerated = db
+            .save_note("1753", &content, None, "model", true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(regenerated.algorithm, "다익스트라");
+        assert!(!regenerated.edited);
+
+        assert!(db.delete_note(note.id).unwrap());
+        assert!(db.get_note("1753").unwrap().is_none());
+    }
+}
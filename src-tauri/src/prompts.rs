@@ -0,0 +1,27 @@
+use crate::crawler::Sample;
+use crate::database::ProblemRecord;
+
+pub fn samples(problem: &ProblemRecord) -> Vec<Sample> {
+    serde_json::from_str(&problem.samples_json).unwrap_or_default()
+}
+
+pub fn problem_context(problem: &ProblemRecord) -> String {
+    let samples = samples(problem)
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("예제 {}:\n입력:\n{}\n출력:\n{}", i + 1, s.input, s.output))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        "[문제 정보]\n제목: {} ({}번)\n제한: {}, {}\n\n[문제 설명]\n{}\n\n[입력]\n{}\n\n[출력]\n{}\n\n[예제]\n{}",
+        problem.title,
+        problem.problem_id,
+        problem.time_limit,
+        problem.memory_limit,
+        problem.description,
+        problem.input_description,
+        problem.output_description,
+        samples
+    )
+}
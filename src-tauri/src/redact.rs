@@ -0,0 +1,125 @@
+use serde::{Serialize, Serializer};
+use std::sync::RwLock;
+
+const REDACTED: &str = "[REDACTED]";
+const MIN_SECRET_LEN: usize = 6;
+const GOOGLE_KEY_PREFIX: &str = "AIza";
+const GOOGLE_KEY_MIN_TAIL: usize = 30;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Remembers a secret so that it is scrubbed from every error shown to the user.
+pub fn register(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.read().unwrap().iter() {
+        text = text.replace(secret.as_str(), REDACTED);
+    }
+    redact_google_keys(&redact_key_params(&text))
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Scrubs `key=...` query parameters left in URLs by reqwest errors or proxies.
+fn redact_key_params(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find("key=") {
+        let is_param = rest[..pos].ends_with('?') || rest[..pos].ends_with('&');
+        result.push_str(&rest[..pos + 4]);
+        rest = &rest[pos + 4..];
+        if is_param {
+            let end = rest.find(|c: char| !is_token_char(c)).unwrap_or(rest.len());
+            if end > 0 {
+                result.push_str(REDACTED);
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Scrubs anything shaped like a Google API key, even if it was never registered.
+fn redact_google_keys(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find(GOOGLE_KEY_PREFIX) {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + GOOGLE_KEY_PREFIX.len()..];
+        let end = after.find(|c: char| !is_token_char(c)).unwrap_or(after.len());
+        if end >= GOOGLE_KEY_MIN_TAIL {
+            result.push_str(REDACTED);
+        } else {
+            result.push_str(&rest[pos..pos + GOOGLE_KEY_PREFIX.len() + end]);
+        }
+        rest = &after[end..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Error returned by every Tauri command. The message is redacted when it is
+/// serialized for the webview, so no command can leak a key through its error.
+#[derive(Debug)]
+pub struct CommandError(String);
+
+impl<E: std::fmt::Display> From<E> for CommandError {
+    fn from(error: E) -> Self {
+        CommandError(error.to_string())
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&redact(&self.0))
+    }
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrubs_registered_secrets_params_and_key_shapes() {
+        register("my-registered-secret");
+        let google_key = format!("{}{}", GOOGLE_KEY_PREFIX, "x".repeat(35));
+        let message = format!(
+            "failed: my-registered-secret at https://host/models?alt=sse&key=abc123&x=1 using {}",
+            google_key
+        );
+
+        let redacted = redact(&message);
+        assert!(!redacted.contains("my-registered-secret"));
+        assert!(!redacted.contains("abc123"));
+        assert!(!redacted.contains(&google_key));
+        assert!(redacted.contains("&x=1"));
+        assert_eq!(redact("monkey=banana"), "monkey=banana");
+    }
+
+    #[test]
+    fn command_errors_serialize_redacted() {
+        register("serialized-secret");
+        let error = CommandError::from("bad key serialized-secret".to_string());
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, "\"bad key [REDACTED]\"");
+    }
+}
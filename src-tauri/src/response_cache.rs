@@ -0,0 +1,171 @@
+use crate::gemini::ChatResponse;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const CACHE_DIR: &str = "response_cache";
+const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    key: String,
+    response: ChatResponse,
+}
+
+/// Finished AI responses stored one file per request hash, so asking the exact same
+/// question again is answered from disk. Entries expire after the TTL and the oldest
+/// ones are evicted once the directory grows past `max_bytes`.
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+/// Hashes the parts of a request that decide its answer.
+pub fn key(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl ResponseCache {
+    pub fn open(dir: &Path) -> Self {
+        Self::with_limits(dir, DEFAULT_TTL, DEFAULT_MAX_BYTES)
+    }
+
+    pub fn with_limits(dir: &Path, ttl: Duration, max_bytes: u64) -> Self {
+        ResponseCache {
+            dir: dir.join(CACHE_DIR),
+            ttl,
+            max_bytes,
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn is_expired(&self, modified: SystemTime) -> bool {
+        modified.elapsed().map_or(true, |age| age >= self.ttl)
+    }
+
+    pub fn get(&self, key: &str) -> Option<ChatResponse> {
+        let path = self.entry_path(key);
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if self.is_expired(modified) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        let json = std::fs::read_to_string(&path).ok()?;
+        let cached: CachedResponse = serde_json::from_str(&json).ok()?;
+        (cached.key == key).then_some(cached.response)
+    }
+
+    pub fn put(&self, key: &str, response: &ChatResponse) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create response cache: {}", e))?;
+        let json = serde_json::to_string(&CachedResponse {
+            key: key.to_string(),
+            response: response.clone(),
+        })
+        .map_err(|e| e.to_string())?;
+        std::fs::write(self.entry_path(key), json)
+            .map_err(|e| format!("Failed to write response cache: {}", e))?;
+        self.evict();
+        Ok(())
+    }
+
+    /// Drops expired entries, then the oldest ones until the cache fits in `max_bytes`.
+    fn evict(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+            })
+            .collect();
+
+        files.retain(|(path, _, modified)| {
+            let expired = self.is_expired(*modified);
+            if expired {
+                let _ = std::fs::remove_file(path);
+            }
+            !expired
+        });
+
+        files.sort_by_key(|(_, _, modified)| *modified);
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        for (path, size, _) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to clear response cache: {}", e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::TokenUsage;
+
+    fn response(text: &str) -> ChatResponse {
+        ChatResponse {
+            text: text.to_string(),
+            model: "gemini-2.5-flash".to_string(),
+            api_key_label: "default".to_string(),
+            usage: TokenUsage::default(),
+            tool_calls: vec![],
+            thoughts: String::new(),
+            cached: false,
+            context_cache_error: None,
+        }
+    }
+
+    #[test]
+    fn stores_expires_and_evicts_entries() {
+        let dir = std::env::temp_dir().join(format!("psup-responses-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let cache = ResponseCache::open(&dir);
+        let first = key(&["gemini-2.5-flash", "hint please"]);
+        assert_ne!(first, key(&["gemini-2.5-flas", "hhint please"]));
+        assert!(cache.get(&first).is_none());
+        cache.put(&first, &response("first")).unwrap();
+        assert_eq!(cache.get(&first).unwrap().text, "first");
+
+        let expired = ResponseCache::with_limits(&dir, Duration::ZERO, DEFAULT_MAX_BYTES);
+        assert!(expired.get(&first).is_none());
+
+        let tiny = ResponseCache::with_limits(&dir, DEFAULT_TTL, 1);
+        tiny.put(&key(&["a"]), &response("a")).unwrap();
+        assert_eq!(std::fs::read_dir(dir.join(CACHE_DIR)).unwrap().count(), 0);
+
+        cache.clear().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
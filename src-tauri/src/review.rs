@@ -0,0 +1,207 @@
+use crate::database::{CodeReviewRecord, Database};
+use crate::gemini::{parse_json, ChatMessage, ChatOptions, GeminiClient};
+use crate::prompts;
+use crate::runner::SourceCode;
+use crate::usage;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+const SEVERITIES: [&str; 3] = ["error", "warning", "info"];
+const CATEGORIES: [&str; 8] = [
+    "overflow",
+    "off_by_one",
+    "complexity",
+    "io_speed",
+    "logic",
+    "edge_case",
+    "style",
+    "other",
+];
+const REVIEW_PROMPT: &str =
+    "Review the {language} solution below for the competitive programming problem. \
+Each code line is prefixed with its line number and a `|`. \
+Report concrete problems only: integer overflow, off-by-one errors, time or memory complexity that \
+exceeds the limits, slow input/output, wrong logic and unhandled edge cases. \
+Anchor every finding to the exact line range it is about. \
+Write the summary, messages and suggested fixes in Korean.";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReviewFinding {
+    pub start_line: u32,
+    pub end_line: u32,
+    pub severity: String,
+    pub category: String,
+    pub message: String,
+    #[serde(default)]
+    pub suggested_fix: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReviewResponse {
+    summary: String,
+    findings: Vec<ReviewFinding>,
+}
+
+fn review_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "summary": { "type": "STRING" },
+            "findings": {
+                "type": "ARRAY",
+                "items": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "start_line": { "type": "INTEGER" },
+                        "end_line": { "type": "INTEGER" },
+                        "severity": { "type": "STRING", "enum": SEVERITIES },
+                        "category": { "type": "STRING", "enum": CATEGORIES },
+                        "message": { "type": "STRING" },
+                        "suggested_fix": { "type": "STRING" }
+                    },
+                    "required": ["start_line", "end_line", "severity", "category", "message"]
+                }
+            }
+        },
+        "required": ["summary", "findings"]
+    })
+}
+
+fn numbered(code: &str) -> String {
+    code.lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>4}| {}", i + 1, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn validate(findings: Vec<ReviewFinding>, line_count: u32) -> Vec<ReviewFinding> {
+    findings
+        .into_iter()
+        .filter(|f| !f.message.trim().is_empty())
+        .filter_map(|mut f| {
+            if f.start_line > f.end_line {
+                std::mem::swap(&mut f.start_line, &mut f.end_line);
+            }
+            if f.start_line == 0 || f.start_line > line_count {
+                return None;
+            }
+            f.end_line = f.end_line.min(line_count);
+            if !SEVERITIES.contains(&f.severity.as_str()) {
+                f.severity = "info".to_string();
+            }
+            if !CATEGORIES.contains(&f.category.as_str()) {
+                f.category = "other".to_string();
+            }
+            Some(f)
+        })
+        .collect()
+}
+
+pub async fn review(
+    client: &GeminiClient,
+    db: &Database,
+    problem_id: &str,
+    model: &str,
+    source: &SourceCode,
+    options: ChatOptions,
+) -> Result<CodeReviewRecord, String> {
+    let problem = db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Problem not found: {}", problem_id))?;
+    let line_count = source.code.lines().count() as u32;
+    if line_count == 0 {
+        return Err("No code to review".to_string());
+    }
+
+    let messages = vec![ChatMessage::user(format!(
+        "{}\n\n{}\n\n[코드]\n{}",
+        REVIEW_PROMPT.replace("{language}", &source.language),
+        prompts::problem_context(&problem),
+        numbered(&source.code)
+    ))];
+    let options = ChatOptions {
+        max_continuations: Some(0),
+        response_schema: Some(review_schema()),
+        ..options
+    };
+
+    let started = Instant::now();
+    let result = client.chat(model, messages, "", &options, None).await;
+    usage::record(db, Some(problem_id), "review", model, started, &result);
+    let response = result?;
+    let parsed: ReviewResponse = parse_json(&response.text)?;
+    let findings = validate(parsed.findings, line_count);
+    let findings_json = serde_json::to_string(&findings).map_err(|e| e.to_string())?;
+
+    let code_version_id = db
+        .save_code_version(problem_id, &source.language, &source.code)
+        .map_err(|e| e.to_string())?;
+    db.save_code_review(
+        code_version_id,
+        &response.model,
+        &parsed.summary,
+        &findings_json,
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(start_line: u32, end_line: u32, severity: &str, category: &str) -> ReviewFinding {
+        ReviewFinding {
+            start_line,
+            end_line,
+            severity: severity.to_string(),
+            category: category.to_string(),
+            message: "int 범위를 넘습니다".to_string(),
+            suggested_fix: String::new(),
+        }
+    }
+
+    #[test]
+    fn clamps_line_ranges_and_normalizes_labels() {
+        let findings = validate(
+            vec![
+                finding(2, 3, "error", "overflow"),
+                finding(5, 2, "warning", "logic"),
+                finding(4, 99, "fatal", "naming"),
+                finding(0, 1, "info", "style"),
+                finding(11, 12, "info", "style"),
+                ReviewFinding {
+                    message: "  ".to_string(),
+                    ..finding(1, 1, "info", "style")
+                },
+            ],
+            10,
+        );
+
+        let summary: Vec<(u32, u32, &str, &str)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.start_line,
+                    f.end_line,
+                    f.severity.as_str(),
+                    f.category.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (2, 3, "error", "overflow"),
+                (2, 5, "warning", "logic"),
+                (4, 10, "info", "other"),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_code_lines() {
+        assert_eq!(numbered("a\nb"), "   1| a\n   2| b");
+    }
+}
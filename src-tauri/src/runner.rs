@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const COMPILE_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(2);
+
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SourceCode {
+    pub language: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub elapsed_ms: u64,
+}
+
+impl RunResult {
+    pub fn succeeded(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
+}
+
+pub struct Program {
+    dir: PathBuf,
+    program: String,
+    args: Vec<String>,
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn executable(dir: &Path) -> String {
+    dir.join(format!("main{}", std::env::consts::EXE_SUFFIX))
+        .to_string_lossy()
+        .to_string()
+}
+
+fn python() -> &'static str {
+    if cfg!(windows) {
+        "python"
+    } else {
+        "python3"
+    }
+}
+
+pub async fn compile(source: &SourceCode) -> Result<Program, String> {
+    compile_within(source, COMPILE_TIMEOUT).await
+}
+
+async fn compile_within(source: &SourceCode, timeout: Duration) -> Result<Program, String> {
+    let id = RUN_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("psup-run-{}-{}", std::process::id(), id));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create work dir: {}", e))?;
+
+    let mut program = Program {
+        dir: dir.clone(),
+        program: String::new(),
+        args: vec![],
+    };
+    let dir_str = dir.to_string_lossy().to_string();
+
+    let (file_name, compiler): (&str, Option<(&str, Vec<String>)>) =
+        match source.language.to_lowercase().as_str() {
+            "cpp" | "c++" => (
+                "main.cpp",
+                Some((
+                    "g++",
+                    vec![
+                        "-O2".into(),
+                        "-std=c++17".into(),
+                        "-o".into(),
+                        executable(&dir),
+                        "main.cpp".into(),
+                    ],
+                )),
+            ),
+            "c" => (
+                "main.c",
+                Some((
+                    "gcc",
+                    vec![
+                        "-O2".into(),
+                        "-o".into(),
+                        executable(&dir),
+                        "main.c".into(),
+                        "-lm".into(),
+                    ],
+                )),
+            ),
+            "rust" | "rs" => (
+                "main.rs",
+                Some((
+                    "rustc",
+                    vec!["-O".into(), "-o".into(), executable(&dir), "main.rs".into()],
+                )),
+            ),
+            "java" => ("Main.java", Some(("javac", vec!["Main.java".into()]))),
+            "python" | "py" => ("main.py", None),
+            "javascript" | "js" => ("main.js", None),
+            other => return Err(format!("Unsupported language: {}", other)),
+        };
+
+    std::fs::write(dir.join(file_name), &source.code)
+        .map_err(|e| format!("Failed to write source: {}", e))?;
+
+    if let Some((compiler, args)) = compiler {
+        let output = tokio::time::timeout(
+            timeout,
+            Command::new(compiler)
+                .args(&args)
+                .current_dir(&dir)
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .map_err(|_| "Compilation timed out".to_string())?
+        .map_err(|e| format!("Failed to run {}: {}", compiler, e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Compilation failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    match source.language.to_lowercase().as_str() {
+        "java" => {
+            program.program = "java".into();
+            program.args = vec!["-cp".into(), dir_str, "Main".into()];
+        }
+        "python" | "py" => {
+            program.program = python().into();
+            program.args = vec![dir.join("main.py").to_string_lossy().to_string()];
+        }
+        "javascript" | "js" => {
+            program.program = "node".into();
+            program.args = vec![dir.join("main.js").to_string_lossy().to_string()];
+        }
+        _ => program.program = executable(&dir),
+    }
+
+    Ok(program)
+}
+
+impl Program {
+    pub async fn run(&self, input: &str, time_limit: Duration) -> Result<RunResult, String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start program: {}", e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = input.to_string();
+            tokio::spawn(async move {
+                let _ = stdin.write_all(input.as_bytes()).await;
+            });
+        }
+
+        let started = Instant::now();
+        match tokio::time::timeout(time_limit, child.wait_with_output()).await {
+            Ok(output) => {
+                let output = output.map_err(|e| format!("Failed to run program: {}", e))?;
+                Ok(RunResult {
+                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    exit_code: output.status.code(),
+                    timed_out: false,
+                    elapsed_ms: started.elapsed().as_millis() as u64,
+                })
+            }
+            Err(_) => Ok(RunResult {
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_code: None,
+                timed_out: true,
+                elapsed_ms: time_limit.as_millis() as u64,
+            }),
+        }
+    }
+}
+
+pub fn parse_time_limit(time_limit: &str) -> Option<Duration> {
+    let number: String = time_limit
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let seconds: f64 = number.parse().ok()?;
+    (seconds > 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+pub fn time_limit_or_default(time_limit: &str) -> Duration {
+    parse_time_limit(time_limit).unwrap_or(DEFAULT_TIME_LIMIT)
+}
+
+pub fn outputs_match(expected: &str, actual: &str) -> bool {
+    normalize_output(expected) == normalize_output(actual)
+}
+
+fn normalize_output(output: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = output.lines().map(|l| l.trim_end()).collect();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(language: &str, code: &str) -> SourceCode {
+        SourceCode {
+            language: language.to_string(),
+            code: code.to_string(),
+        }
+    }
+
+    /// Toolchain tests are skipped on machines without the tool installed.
+    fn installed(tool: &str) -> bool {
+        std::process::Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    #[test]
+    fn parses_time_limits() {
+        assert_eq!(parse_time_limit("1 초"), Some(Duration::from_secs(1)));
+        assert_eq!(
+            parse_time_limit(" 0.5 초 (추가 시간 없음)"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(parse_time_limit("0 초"), None);
+        assert_eq!(parse_time_limit("없음"), None);
+        assert_eq!(time_limit_or_default(""), DEFAULT_TIME_LIMIT);
+    }
+
+    #[test]
+    fn compares_outputs_ignoring_trailing_whitespace() {
+        assert!(outputs_match("1 2\n3\n", "1 2  \r\n3\n\n\n"));
+        assert!(outputs_match("", "\n"));
+        assert!(!outputs_match("1 2\n3", "1 2\n\n3"));
+        assert!(!outputs_match("1 2", " 1 2"));
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_languages() {
+        let error = compile(&source("brainfuck", "+")).await.err().unwrap();
+        assert_eq!(error, "Unsupported language: brainfuck");
+    }
+
+    #[tokio::test]
+    async fn runs_programs_and_stops_them_at_the_time_limit() {
+        if !installed(python()) {
+            return;
+        }
+        let program = compile(&source(
+            "python",
+            "import sys, time\nif sys.stdin.read().strip() == 'wait':\n    time.sleep(10)\nprint('done')",
+        ))
+        .await
+        .unwrap();
+
+        let run = program.run("go", Duration::from_secs(5)).await.unwrap();
+        assert!(run.succeeded());
+        assert_eq!(run.stdout.trim(), "done");
+
+        let run = program
+            .run("wait", Duration::from_millis(300))
+            .await
+            .unwrap();
+        assert!(run.timed_out);
+        assert!(!run.succeeded());
+    }
+
+    #[tokio::test]
+    async fn reports_compile_errors_and_timeouts() {
+        if !installed("gcc") {
+            return;
+        }
+        let error = compile(&source("c", "int main() { return }"))
+            .await
+            .err()
+            .unwrap();
+        assert!(error.starts_with("Compilation failed"));
+
+        let error = compile_within(&source("c", "int main() { return 0; }"), Duration::ZERO)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error, "Compilation timed out");
+    }
+}
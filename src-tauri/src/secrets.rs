@@ -0,0 +1,366 @@
+use crate::keyring::{ApiKey, KeyRing, KeyState};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const GEMINI: &str = "gemini";
+pub const PROVIDERS: [&str; 1] = [GEMINI];
+
+const SECRETS_FILE: &str = "secrets.json";
+const BACKUP_FILE: &str = "secrets.json.bak";
+const FORMAT_VERSION: u32 = 1;
+const KEY_CONTEXT: &[u8] = b"psup-secrets-v1";
+
+#[derive(Serialize, Deserialize)]
+struct SealedFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredKey {
+    label: String,
+    secret: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Secrets {
+    #[serde(default)]
+    pools: BTreeMap<String, Vec<StoredKey>>,
+    /// One key per provider, as written before key pools existed.
+    #[serde(default, skip_serializing)]
+    keys: BTreeMap<String, String>,
+}
+
+impl Secrets {
+    fn migrate_single_keys(&mut self) {
+        for (provider, secret) in std::mem::take(&mut self.keys) {
+            let pool = self.pools.entry(provider).or_default();
+            if pool.is_empty() {
+                pool.push(StoredKey {
+                    label: crate::keyring::DEFAULT_LABEL.to_string(),
+                    secret,
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiKeyStatus {
+    pub provider: String,
+    pub configured: bool,
+    pub keys: Vec<KeyState>,
+    /// Why the stored keys could not be loaded, so the user knows to enter them again.
+    pub load_error: Option<String>,
+}
+
+/// API keys encrypted at rest with a key derived from the machine id, so the file
+/// is useless when copied elsewhere. Keys never leave the backend once stored.
+/// A file that cannot be read is moved aside before anything new is written.
+pub struct SecretStore {
+    path: PathBuf,
+    secrets: Mutex<Secrets>,
+    rings: Mutex<HashMap<String, Arc<KeyRing>>>,
+    load_error: Option<String>,
+    unreadable_on_disk: AtomicBool,
+}
+
+impl SecretStore {
+    pub fn open(dir: &Path) -> Self {
+        let path = dir.join(SECRETS_FILE);
+        let loaded = match std::fs::read_to_string(&path) {
+            Ok(json) => unseal(&json),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Secrets::default()),
+            Err(e) => Err(e.to_string()),
+        };
+        let (mut secrets, load_error) = match loaded {
+            Ok(secrets) => (secrets, None),
+            Err(e) => (
+                Secrets::default(),
+                Some(format!("Failed to load stored API keys: {}", e)),
+            ),
+        };
+        secrets.migrate_single_keys();
+
+        let store = SecretStore {
+            path,
+            secrets: Mutex::new(secrets),
+            rings: Mutex::new(HashMap::new()),
+            unreadable_on_disk: AtomicBool::new(load_error.is_some()),
+            load_error,
+        };
+        for provider in PROVIDERS {
+            store.rebuild_ring(provider, &store.secrets.lock().unwrap());
+        }
+        store
+    }
+
+    /// The shared key ring of a provider, so rotation and cooldowns carry over
+    /// between requests. `None` when no key is stored.
+    pub fn ring(&self, provider: &str) -> Option<Arc<KeyRing>> {
+        self.rings
+            .lock()
+            .unwrap()
+            .get(provider)
+            .filter(|ring| !ring.is_empty())
+            .cloned()
+    }
+
+    /// Adds a key under `label`, replacing the key that already has that label.
+    pub fn set(&self, provider: &str, label: &str, api_key: &str) -> Result<(), String> {
+        let mut secrets = self.secrets.lock().unwrap();
+        let pool = secrets.pools.entry(provider.to_string()).or_default();
+        let key = StoredKey {
+            label: label.to_string(),
+            secret: api_key.to_string(),
+        };
+        match pool.iter_mut().find(|k| k.label == label) {
+            Some(existing) => *existing = key,
+            None => pool.push(key),
+        }
+        self.persist(&secrets)?;
+        self.rebuild_ring(provider, &secrets);
+        Ok(())
+    }
+
+    pub fn remove(&self, provider: &str, label: &str) -> Result<bool, String> {
+        let mut secrets = self.secrets.lock().unwrap();
+        let Some(pool) = secrets.pools.get_mut(provider) else {
+            return Ok(false);
+        };
+        let before = pool.len();
+        pool.retain(|k| k.label != label);
+        let removed = pool.len() != before;
+        if pool.is_empty() {
+            secrets.pools.remove(provider);
+        }
+        if removed {
+            self.persist(&secrets)?;
+            self.rebuild_ring(provider, &secrets);
+        }
+        Ok(removed)
+    }
+
+    pub fn clear(&self, provider: &str) -> Result<bool, String> {
+        let mut secrets = self.secrets.lock().unwrap();
+        let removed = secrets.pools.remove(provider).is_some();
+        if removed {
+            self.persist(&secrets)?;
+            self.rebuild_ring(provider, &secrets);
+        }
+        Ok(removed)
+    }
+
+    pub fn status(&self, provider: &str) -> ApiKeyStatus {
+        let keys = self.ring(provider).map(|r| r.states()).unwrap_or_default();
+        ApiKeyStatus {
+            provider: provider.to_string(),
+            configured: !keys.is_empty(),
+            keys,
+            load_error: self.load_error.clone(),
+        }
+    }
+
+    fn rebuild_ring(&self, provider: &str, secrets: &Secrets) {
+        let keys = secrets
+            .pools
+            .get(provider)
+            .into_iter()
+            .flatten()
+            .map(|k| ApiKey {
+                label: k.label.clone(),
+                secret: k.secret.clone(),
+            })
+            .collect();
+        let mut rings = self.rings.lock().unwrap();
+        let ring = match rings.get(provider) {
+            Some(previous) => previous.replace(keys),
+            None => KeyRing::new(keys),
+        };
+        rings.insert(provider.to_string(), Arc::new(ring));
+    }
+
+    /// Keeps a file that failed to load as `secrets.json.bak` instead of overwriting it,
+    /// so the keys can still be recovered if it becomes readable again.
+    fn move_unreadable_aside(&self) -> Result<(), String> {
+        if !self.unreadable_on_disk.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let backup = self.path.with_file_name(BACKUP_FILE);
+        match std::fs::rename(&self.path, &backup) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(format!("Failed to move unreadable API keys aside: {}", e));
+            }
+            _ => {}
+        }
+        self.unreadable_on_disk.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn persist(&self, secrets: &Secrets) -> Result<(), String> {
+        self.move_unreadable_aside()?;
+        if secrets.pools.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(format!("Failed to remove stored API keys: {}", e))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, seal(secrets)?)
+            .map_err(|e| format!("Failed to write API keys: {}", e))?;
+        restrict_permissions(&tmp);
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("Failed to write API keys: {}", e))
+    }
+}
+
+pub fn provider_or_default(provider: Option<String>) -> Result<String, String> {
+    let provider = provider.unwrap_or_else(|| GEMINI.to_string());
+    if PROVIDERS.contains(&provider.as_str()) {
+        Ok(provider)
+    } else {
+        Err(format!("Unknown provider: {}", provider))
+    }
+}
+
+fn machine_key(salt: &[u8]) -> Key {
+    let machine_id = machine_uid::get().unwrap_or_default();
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_CONTEXT);
+    hasher.update(salt);
+    hasher.update(machine_id.trim().as_bytes());
+    hasher.update(user.as_bytes());
+    Key::from(<[u8; 32]>::from(hasher.finalize()))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| format!("Failed to generate random bytes: {}", e))?;
+    Ok(bytes)
+}
+
+fn seal(secrets: &Secrets) -> Result<String, String> {
+    let salt = random_bytes::<16>()?;
+    let nonce = random_bytes::<12>()?;
+    let plaintext = serde_json::to_vec(secrets).map_err(|e| e.to_string())?;
+
+    let ciphertext = ChaCha20Poly1305::new(&machine_key(&salt))
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| "Failed to encrypt API keys".to_string())?;
+
+    serde_json::to_string(&SealedFile {
+        version: FORMAT_VERSION,
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+    .map_err(|e| e.to_string())
+}
+
+fn unseal(json: &str) -> Result<Secrets, String> {
+    let sealed: SealedFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    if sealed.version != FORMAT_VERSION {
+        return Err(format!("Unsupported secrets version: {}", sealed.version));
+    }
+    let decode = |value: &str| BASE64.decode(value).map_err(|e| e.to_string());
+    let salt = decode(&sealed.salt)?;
+    let nonce = decode(&sealed.nonce)?;
+    if nonce.len() != 12 {
+        return Err("Invalid nonce".to_string());
+    }
+
+    let plaintext = ChaCha20Poly1305::new(&machine_key(&salt))
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            decode(&sealed.ciphertext)?.as_slice(),
+        )
+        .map_err(|_| {
+            "Stored API keys were encrypted on another machine or are corrupted".to_string()
+        })?;
+    serde_json::from_slice(&plaintext).map_err(|e| e.to_string())
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_keys_encrypted_and_reloads_them() {
+        let dir = std::env::temp_dir().join(format!("psup-secrets-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let store = SecretStore::open(&dir);
+        store.set(GEMINI, "main", "AIza-test-secret").unwrap();
+        store.set(GEMINI, "backup", "AIza-backup-secret").unwrap();
+
+        let on_disk = std::fs::read_to_string(dir.join(SECRETS_FILE)).unwrap();
+        assert!(!on_disk.contains("AIza-test-secret"));
+
+        let reopened = SecretStore::open(&dir);
+        let ring = reopened.ring(GEMINI).unwrap();
+        assert_eq!(ring.len(), 2);
+        assert_eq!(ring.next().unwrap().secret, "AIza-test-secret");
+        assert!(reopened.remove(GEMINI, "backup").unwrap());
+        assert_eq!(reopened.status(GEMINI).keys.len(), 1);
+        assert!(reopened.clear(GEMINI).unwrap());
+        assert!(!dir.join(SECRETS_FILE).exists());
+        assert!(!SecretStore::open(&dir).status(GEMINI).configured);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keeps_an_unreadable_file_instead_of_overwriting_it() {
+        let dir = std::env::temp_dir().join(format!("psup-secrets-bad-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let corrupted =
+            r#"{"version":1,"salt":"AAAA","nonce":"AAAAAAAAAAAAAAAA","ciphertext":"AAAA"}"#;
+        std::fs::write(dir.join(SECRETS_FILE), corrupted).unwrap();
+
+        let store = SecretStore::open(&dir);
+        let status = store.status(GEMINI);
+        assert!(!status.configured);
+        assert!(status.load_error.unwrap().contains("corrupted"));
+
+        store.set(GEMINI, "main", "AIza-new-secret").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(BACKUP_FILE)).unwrap(),
+            corrupted
+        );
+        let reopened = SecretStore::open(&dir);
+        assert!(reopened.status(GEMINI).load_error.is_none());
+        assert_eq!(reopened.ring(GEMINI).unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
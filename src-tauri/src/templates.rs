@@ -0,0 +1,320 @@
+use crate::complexity;
+use crate::database::{Database, ProblemRecord, PromptTemplateRecord};
+use crate::prompts;
+use crate::runner::SourceCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const BUILTIN_TEMPLATES: [(&str, &str, &str); 4] = [
+    (
+        "tutor",
+        "힌트 위주로 스스로 풀도록 돕는 튜터",
+        "당신은 알고리즘 문제 해결을 돕는 튜터입니다.
+
+문제: {{title}}
+제한: {{time_limit}}, {{memory_limit}}
+입력 범위: {{constraints}}
+
+역할:
+- 힌트 제공: 직접적인 답 대신 학생이 스스로 풀 수 있도록 유도
+- 코드 리뷰: 제출된 코드 분석, 시간/공간 복잡도, 개선점 제안
+- 테스트케이스: 엣지 케이스, 코너 케이스, 반례 생성
+
+학생의 질문에 따라 적절한 도움을 제공하세요.",
+    ),
+    (
+        "strict-reviewer",
+        "코드의 결함을 엄격하게 찾는 리뷰어",
+        "당신은 엄격한 코드 리뷰어입니다.
+
+문제: {{title}}
+제한: {{time_limit}}, {{memory_limit}}
+입력 범위: {{constraints}}
+
+아래 {{language}} 코드에서 오버플로우, off-by-one, 시간 초과, 느린 입출력, 예외 케이스 누락을 찾아
+줄 번호와 함께 구체적으로 지적하세요. 칭찬이나 일반론은 생략하세요.
+
+```{{language}}
+{{code}}
+```",
+    ),
+    (
+        "test-generator",
+        "반례와 엣지 케이스를 만드는 테스트 생성기",
+        "당신은 테스트케이스 생성기입니다.
+
+문제: {{title}}
+제한: {{time_limit}}, {{memory_limit}}
+입력 범위: {{constraints}}
+
+입력 형식을 정확히 지키는 엣지 케이스와 반례를 만들고, 각 케이스마다 기대 출력과 의도를 설명하세요.",
+    ),
+    (
+        "editorial-writer",
+        "풀이를 정리하는 에디토리얼 작성자",
+        "당신은 알고리즘 풀이 에디토리얼 작성자입니다.
+
+문제: {{title}}
+제한: {{time_limit}}, {{memory_limit}}
+입력 범위: {{constraints}}
+
+핵심 아이디어, 알고리즘, 시간/공간 복잡도, 주의할 점 순서로 풀이를 정리하세요.
+사용자의 {{language}} 코드가 있다면 그 코드를 기준으로 설명하세요.
+
+```{{language}}
+{{code}}
+```",
+    ),
+];
+
+/// The result of importing a template file. Entries named like a builtin template
+/// are skipped so a file cannot replace the builtin body.
+#[derive(Debug, Serialize, Clone)]
+pub struct TemplateImport {
+    pub imported: Vec<PromptTemplateRecord>,
+    pub skipped_builtins: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TemplateFileEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub body: String,
+}
+
+pub fn variables(
+    problem: Option<&ProblemRecord>,
+    code: Option<&SourceCode>,
+) -> HashMap<&'static str, String> {
+    let mut vars = HashMap::new();
+
+    if let Some(problem) = problem {
+        vars.insert("problem_id", problem.problem_id.clone());
+        vars.insert("title", problem.title.clone());
+        vars.insert("constraints", constraints(problem));
+        vars.insert("time_limit", problem.time_limit.clone());
+        vars.insert("memory_limit", problem.memory_limit.clone());
+        vars.insert("description", problem.description.clone());
+        vars.insert("input", problem.input_description.clone());
+        vars.insert("output", problem.output_description.clone());
+        vars.insert("problem", prompts::problem_context(problem));
+    }
+    vars.insert("code", code.map(|c| c.code.clone()).unwrap_or_default());
+    vars.insert(
+        "language",
+        code.map(|c| c.language.clone()).unwrap_or_default(),
+    );
+
+    vars
+}
+
+/// The input bounds parsed from the statement, or the input description when
+/// none could be read.
+fn constraints(problem: &ProblemRecord) -> String {
+    let constraints = complexity::problem_constraints(problem);
+    if constraints.is_empty() {
+        return problem.input_description.clone();
+    }
+    constraints
+        .iter()
+        .map(|c| format!("{} ≤ {}", c.name, c.max))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn render(body: &str, vars: &HashMap<&str, String>) -> String {
+    let mut rendered = String::with_capacity(body.len());
+    let mut rest = body;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rendered.push_str(&rest[start..]);
+            return rendered;
+        };
+
+        let name = after[..end].trim();
+        match vars.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+pub fn render_for_problem(
+    db: &Database,
+    template: &PromptTemplateRecord,
+    problem_id: Option<&str>,
+    code: Option<&SourceCode>,
+) -> Result<String, String> {
+    let problem = match problem_id {
+        Some(id) => db.get_problem(id).map_err(|e| e.to_string())?,
+        None => None,
+    };
+    Ok(render(&template.body, &variables(problem.as_ref(), code)))
+}
+
+pub fn export_to_file(templates: &[PromptTemplateRecord], path: &Path) -> Result<(), String> {
+    let entries: Vec<TemplateFileEntry> = templates
+        .iter()
+        .map(|t| TemplateFileEntry {
+            name: t.name.clone(),
+            description: t.description.clone(),
+            body: t.body.clone(),
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write templates: {}", e))
+}
+
+pub fn read_file(path: &Path) -> Result<Vec<TemplateFileEntry>, String> {
+    let json =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read templates: {}", e))?;
+    let entries: Vec<TemplateFileEntry> =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid template file: {}", e))?;
+
+    if let Some(entry) = entries
+        .iter()
+        .find(|e| e.name.trim().is_empty() || e.body.trim().is_empty())
+    {
+        return Err(format!("Template without name or body: {:?}", entry.name));
+    }
+    Ok(entries)
+}
+
+pub fn import(db: &Database, path: &Path) -> Result<TemplateImport, String> {
+    let mut result = TemplateImport {
+        imported: vec![],
+        skipped_builtins: vec![],
+    };
+    for entry in read_file(path)? {
+        let name = entry.name.trim();
+        if BUILTIN_TEMPLATES
+            .iter()
+            .any(|(builtin, _, _)| *builtin == name)
+        {
+            result.skipped_builtins.push(name.to_string());
+            continue;
+        }
+        let id = db
+            .save_prompt_template(name, &entry.description, &entry.body)
+            .map_err(|e| e.to_string())?;
+        if let Some(template) = db.get_prompt_template(id).map_err(|e| e.to_string())? {
+            result.imported.push(template);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::Problem;
+    use crate::database::tests::{test_db, test_problem};
+
+    fn vars(pairs: &[(&'static str, &str)]) -> HashMap<&'static str, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect()
+    }
+
+    fn write_file(name: &str, json: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("psup-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn renders_known_variables_and_keeps_the_rest() {
+        let vars = vars(&[("title", "A+B"), ("code", "print(1)")]);
+        assert_eq!(
+            render("{{ title }}: {{code}} {{unknown}}", &vars),
+            "A+B: print(1) {{unknown}}"
+        );
+        assert_eq!(render("{{title}} {{title", &vars), "A+B {{title");
+        assert_eq!(render("no variables", &vars), "no variables");
+    }
+
+    #[test]
+    fn fills_problem_variables_with_the_input_constraints() {
+        let db = test_db("templates");
+        db.save_problem(&Problem {
+            input_description: "<p>첫째 줄에 N이 주어진다. (1 &le; N &le; 100,000)</p>".to_string(),
+            ..test_problem("1000", "A+B")
+        })
+        .unwrap();
+        db.save_problem(&Problem {
+            input_description: "두 정수가 주어진다.".to_string(),
+            ..test_problem("1001", "A-B")
+        })
+        .unwrap();
+
+        let problem = db.get_problem("1000").unwrap().unwrap();
+        let vars = variables(Some(&problem), None);
+        assert_eq!(vars["constraints"], "N ≤ 100000");
+        assert_eq!(vars["time_limit"], "1 초");
+        assert_eq!(vars["code"], "");
+
+        let problem = db.get_problem("1001").unwrap().unwrap();
+        assert_eq!(
+            variables(Some(&problem), None)["constraints"],
+            "두 정수가 주어진다."
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_template_files() {
+        let path = write_file("templates-invalid", "{");
+        assert!(read_file(&path)
+            .unwrap_err()
+            .starts_with("Invalid template file"));
+        std::fs::write(&path, r#"[{"name":"mine","body":"  "}]"#).unwrap();
+        assert_eq!(
+            read_file(&path).unwrap_err(),
+            "Template without name or body: \"mine\""
+        );
+        let _ = std::fs::remove_file(&path);
+        assert!(read_file(&path)
+            .unwrap_err()
+            .starts_with("Failed to read templates"));
+    }
+
+    #[test]
+    fn imports_templates_without_replacing_builtins() {
+        let db = test_db("templates-import");
+        let path = write_file(
+            "templates-import",
+            r#"[
+                {"name": "tutor", "body": "replaced"},
+                {"name": " mine ", "description": "내 템플릿", "body": "{{title}}"}
+            ]"#,
+        );
+
+        let result = import(&db, &path).unwrap();
+        assert_eq!(result.skipped_builtins, ["tutor"]);
+        assert_eq!(result.imported.len(), 1);
+        assert_eq!(
+            (result.imported[0].name.as_str(), result.imported[0].builtin),
+            ("mine", false)
+        );
+        let tutor = db
+            .get_prompt_templates()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.name == "tutor")
+            .unwrap();
+        assert_eq!(tutor.body, BUILTIN_TEMPLATES[0].2);
+
+        export_to_file(&result.imported, &path).unwrap();
+        assert_eq!(read_file(&path).unwrap()[0].body, "{{title}}");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::database::{Database, NewTestCase, TestCaseRecord};
+use crate::gemini::{parse_json, ChatMessage, ChatOptions, GeminiClient};
+use crate::prompts;
+use crate::runner::{self, RunResult, SourceCode};
+use crate::usage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Instant;
+
+const DEFAULT_TEST_CASE_COUNT: u32 = 5;
+const MAX_TEST_CASE_COUNT: u32 = 20;
+const MAX_INPUT_BYTES: usize = 64 * 1024;
+const TEST_CASE_PROMPT: &str =
+    "Generate {count} test cases for the competitive programming problem below. \
+Prefer edge cases, corner cases and counterexamples that commonly break wrong solutions \
+(minimum/maximum values, overflow, off-by-one, empty or repeated elements). \
+Every input must follow the input format exactly and respect the constraints. \
+Every expected_output must be exactly what a correct solution prints. \
+Keep inputs small enough to be checked by hand unless the case is about performance. \
+Write each rationale in Korean, in one or two sentences.";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeneratedTestCase {
+    pub input: String,
+    pub expected_output: String,
+    pub rationale: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RejectedTestCase {
+    pub test_case: GeneratedTestCase,
+    pub reason: String,
+    pub actual_output: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TestCaseReport {
+    pub model: String,
+    pub accepted: Vec<TestCaseRecord>,
+    pub rejected: Vec<RejectedTestCase>,
+}
+
+fn test_case_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "ARRAY",
+        "items": {
+            "type": "OBJECT",
+            "properties": {
+                "input": { "type": "STRING" },
+                "expected_output": { "type": "STRING" },
+                "rationale": { "type": "STRING" }
+            },
+            "required": ["input", "expected_output", "rationale"]
+        }
+    })
+}
+
+fn validate(test_case: &GeneratedTestCase, seen: &HashSet<String>) -> Result<(), String> {
+    if test_case.input.trim().is_empty() {
+        return Err("Input is empty".to_string());
+    }
+    if test_case.expected_output.trim().is_empty() {
+        return Err("Expected output is empty".to_string());
+    }
+    if test_case.input.len() > MAX_INPUT_BYTES {
+        return Err(format!("Input is larger than {} bytes", MAX_INPUT_BYTES));
+    }
+    if seen.contains(test_case.input.trim()) {
+        return Err("Duplicate input".to_string());
+    }
+    Ok(())
+}
+
+/// Why the reference solution's run rules the case out, if it does.
+fn reference_mismatch(test_case: &GeneratedTestCase, run: &RunResult) -> Option<String> {
+    if run.timed_out {
+        Some("Reference solution timed out".to_string())
+    } else if !run.succeeded() {
+        Some(format!("Reference solution failed: {}", run.stderr.trim()))
+    } else if !runner::outputs_match(&test_case.expected_output, &run.stdout) {
+        Some("Reference solution output differs".to_string())
+    } else {
+        None
+    }
+}
+
+pub async fn generate(
+    client: &GeminiClient,
+    db: &Database,
+    problem_id: &str,
+    model: &str,
+    count: Option<u32>,
+    reference: Option<&SourceCode>,
+    options: ChatOptions,
+) -> Result<TestCaseReport, String> {
+    let problem = db
+        .get_problem(problem_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Problem not found: {}", problem_id))?;
+    let count = count
+        .unwrap_or(DEFAULT_TEST_CASE_COUNT)
+        .clamp(1, MAX_TEST_CASE_COUNT);
+
+    let messages = vec![ChatMessage::user(format!(
+        "{}\n\n{}",
+        TEST_CASE_PROMPT.replace("{count}", &count.to_string()),
+        prompts::problem_context(&problem)
+    ))];
+    let options = ChatOptions {
+        max_continuations: Some(0),
+        response_schema: Some(test_case_schema()),
+        ..options
+    };
+
+    let started = Instant::now();
+    let result = client.chat(model, messages, "", &options, None).await;
+    usage::record(db, Some(problem_id), "test_cases", model, started, &result);
+    let response = result?;
+    let generated: Vec<GeneratedTestCase> = parse_json(&response.text)?;
+
+    let program = match reference {
+        Some(source) => Some(
+            runner::compile(source)
+                .await
+                .map_err(|e| format!("Reference solution: {}", e))?,
+        ),
+        None => None,
+    };
+    let time_limit = runner::time_limit_or_default(&problem.time_limit) * 2;
+
+    let mut seen: HashSet<String> = db
+        .get_test_cases(problem_id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|t| t.input.trim().to_string())
+        .collect();
+    let mut accepted = vec![];
+    let mut rejected = vec![];
+
+    for test_case in generated {
+        if let Err(reason) = validate(&test_case, &seen) {
+            rejected.push(RejectedTestCase {
+                test_case,
+                reason,
+                actual_output: None,
+            });
+            continue;
+        }
+
+        if let Some(program) = &program {
+            // One case that cannot be run is rejected rather than failing the batch.
+            let run = match program.run(&test_case.input, time_limit).await {
+                Ok(run) => run,
+                Err(e) => {
+                    rejected.push(RejectedTestCase {
+                        test_case,
+                        reason: format!("Reference solution could not run: {}", e),
+                        actual_output: None,
+                    });
+                    continue;
+                }
+            };
+            if let Some(reason) = reference_mismatch(&test_case, &run) {
+                rejected.push(RejectedTestCase {
+                    test_case,
+                    reason,
+                    actual_output: Some(run.stdout),
+                });
+                continue;
+            }
+        }
+
+        let id = db
+            .save_test_case(&NewTestCase {
+                problem_id,
+                input: &test_case.input,
+                expected_output: &test_case.expected_output,
+                rationale: &test_case.rationale,
+                source: "ai",
+                verified: program.is_some(),
+            })
+            .map_err(|e| e.to_string())?;
+        if let Some(record) = db.get_test_case(id).map_err(|e| e.to_string())? {
+            accepted.push(record);
+        }
+        seen.insert(test_case.input.trim().to_string());
+    }
+
+    Ok(TestCaseReport {
+        model: response.model,
+        accepted,
+        rejected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(input: &str, expected_output: &str) -> GeneratedTestCase {
+        GeneratedTestCase {
+            input: input.to_string(),
+            expected_output: expected_output.to_string(),
+            rationale: String::new(),
+        }
+    }
+
+    fn run(stdout: &str, exit_code: Option<i32>, timed_out: bool) -> RunResult {
+        RunResult {
+            stdout: stdout.to_string(),
+            stderr: "panicked".to_string(),
+            exit_code,
+            timed_out,
+            elapsed_ms: 0,
+        }
+    }
+
+    #[test]
+    fn validate_rejects_empty_oversized_and_duplicate_inputs() {
+        let seen: HashSet<String> = ["1 2".to_string()].into_iter().collect();
+
+        assert!(validate(&case("3 4\n", "7\n"), &seen).is_ok());
+        assert_eq!(
+            validate(&case(" \n", "7"), &seen).unwrap_err(),
+            "Input is empty"
+        );
+        assert_eq!(
+            validate(&case("3 4", "\n"), &seen).unwrap_err(),
+            "Expected output is empty"
+        );
+        assert!(
+            validate(&case(&"1".repeat(MAX_INPUT_BYTES + 1), "1"), &seen)
+                .unwrap_err()
+                .starts_with("Input is larger")
+        );
+        assert_eq!(
+            validate(&case("1 2\n", "3"), &seen).unwrap_err(),
+            "Duplicate input"
+        );
+    }
+
+    #[test]
+    fn reference_runs_reject_timeouts_failures_and_wrong_output() {
+        let test_case = case("1 2", "3\n");
+
+        assert_eq!(
+            reference_mismatch(&test_case, &run("3  \n\n", Some(0), false)),
+            None
+        );
+        assert_eq!(
+            reference_mismatch(&test_case, &run("", None, true)).unwrap(),
+            "Reference solution timed out"
+        );
+        assert_eq!(
+            reference_mismatch(&test_case, &run("3", Some(1), false)).unwrap(),
+            "Reference solution failed: panicked"
+        );
+        assert_eq!(
+            reference_mismatch(&test_case, &run("4", Some(0), false)).unwrap(),
+            "Reference solution output differs"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::database::{Database, ProblemRecord};
+use crate::gemini::{FunctionDeclaration, ToolExecutor};
+use crate::prompts;
+use crate::runner::{self, SourceCode};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+
+pub struct TutorTools<'a> {
+    db: &'a Database,
+    problem: ProblemRecord,
+    code: Option<SourceCode>,
+}
+
+impl<'a> TutorTools<'a> {
+    pub fn new(db: &'a Database, problem: ProblemRecord, code: Option<SourceCode>) -> Self {
+        TutorTools { db, problem, code }
+    }
+
+    /// Only the user's own editor code is ever run; the model cannot supply code,
+    /// since a crawled statement could otherwise inject commands into the host.
+    fn source(&self) -> Result<&SourceCode, String> {
+        self.code
+            .as_ref()
+            .ok_or_else(|| "The user has not provided any code".to_string())
+    }
+
+    async fn run_code(&self, args: &Value) -> Result<Value, String> {
+        let input = args
+            .get("input")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let program = runner::compile(self.source()?).await?;
+        let result = program
+            .run(
+                input,
+                runner::time_limit_or_default(&self.problem.time_limit),
+            )
+            .await?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    }
+
+    async fn run_samples(&self) -> Result<Value, String> {
+        let program = runner::compile(self.source()?).await?;
+        let time_limit = runner::time_limit_or_default(&self.problem.time_limit);
+        let mut results = vec![];
+
+        for (i, sample) in prompts::samples(&self.problem).iter().enumerate() {
+            let run = program.run(&sample.input, time_limit).await?;
+            results.push(json!({
+                "sample": i + 1,
+                "passed": run.succeeded() && runner::outputs_match(&sample.output, &run.stdout),
+                "expected_output": sample.output,
+                "actual_output": run.stdout,
+                "stderr": run.stderr,
+                "timed_out": run.timed_out,
+                "elapsed_ms": run.elapsed_ms,
+            }));
+        }
+
+        Ok(json!({ "results": results }))
+    }
+
+    fn problem_constraints(&self) -> Value {
+        json!({
+            "time_limit": self.problem.time_limit,
+            "memory_limit": self.problem.memory_limit,
+            "input_description": self.problem.input_description,
+            "output_description": self.problem.output_description,
+        })
+    }
+
+    fn list_test_cases(&self) -> Result<Value, String> {
+        let test_cases = self
+            .db
+            .get_test_cases(&self.problem.problem_id)
+            .map_err(|e| e.to_string())?;
+        let test_cases: Vec<Value> = test_cases
+            .into_iter()
+            .map(|t| {
+                json!({
+                    "id": t.id,
+                    "input": t.input,
+                    "expected_output": t.expected_output,
+                    "rationale": t.rationale,
+                    "verified": t.verified,
+                })
+            })
+            .collect();
+        Ok(json!({ "test_cases": test_cases }))
+    }
+}
+
+impl ToolExecutor for TutorTools<'_> {
+    fn declarations(&self) -> Vec<FunctionDeclaration> {
+        vec![
+            FunctionDeclaration {
+                name: "run_code".to_string(),
+                description: "Compile and run the user's code on one input and return stdout, \
+                    stderr, exit code and elapsed time."
+                    .to_string(),
+                parameters: Some(json!({
+                    "type": "OBJECT",
+                    "properties": {
+                        "input": { "type": "STRING", "description": "Text passed to standard input" }
+                    },
+                    "required": ["input"]
+                })),
+            },
+            FunctionDeclaration {
+                name: "run_all_samples".to_string(),
+                description:
+                    "Run the user's code on every sample of the problem and report which samples pass."
+                        .to_string(),
+                parameters: None,
+            },
+            FunctionDeclaration {
+                name: "get_problem_constraints".to_string(),
+                description:
+                    "Get the time limit, memory limit and input/output format of the problem."
+                        .to_string(),
+                parameters: None,
+            },
+            FunctionDeclaration {
+                name: "list_test_cases".to_string(),
+                description: "List the test cases saved for this problem.".to_string(),
+                parameters: None,
+            },
+        ]
+    }
+
+    fn call<'b>(&'b self, name: &'b str, args: &'b Value) -> BoxFuture<'b, Value> {
+        Box::pin(async move {
+            let result = match name {
+                "run_code" => self.run_code(args).await,
+                "run_all_samples" => self.run_samples().await,
+                "get_problem_constraints" => Ok(self.problem_constraints()),
+                "list_test_cases" => self.list_test_cases(),
+                other => Err(format!("Unknown tool: {}", other)),
+            };
+            result.unwrap_or_else(|error| json!({ "error": error }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::{test_db, test_problem};
+
+    #[tokio::test]
+    async fn never_runs_code_written_by_the_model() {
+        let db = test_db("tools");
+        db.save_problem(&test_problem("1000", "A+B")).unwrap();
+        let problem = db.get_problem("1000").unwrap().unwrap();
+        let tools = TutorTools::new(&db, problem, None);
+
+        for declaration in tools.declarations() {
+            let parameters = declaration.parameters.unwrap_or_default();
+            assert!(parameters["properties"].get("code").is_none());
+        }
+        let result = tools
+            .call(
+                "run_code",
+                &json!({ "input": "1 2", "code": "import os", "language": "python" }),
+            )
+            .await;
+        assert_eq!(result["error"], "The user has not provided any code");
+    }
+}
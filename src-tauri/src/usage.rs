@@ -0,0 +1,62 @@
+use crate::database::{AiUsageEntry, Database};
+use crate::gemini::ChatResponse;
+use std::time::Instant;
+
+pub fn record(
+    db: &Database,
+    problem_id: Option<&str>,
+    kind: &str,
+    model: &str,
+    started: Instant,
+    result: &Result<ChatResponse, String>,
+) {
+    let latency_ms = started.elapsed().as_millis() as i64;
+    let entry = match result {
+        Ok(response) => AiUsageEntry {
+            problem_id,
+            model: &response.model,
+            api_key_label: Some(&response.api_key_label),
+            kind,
+            prompt_tokens: response.usage.prompt_tokens,
+            output_tokens: response.usage.output_tokens,
+            thinking_tokens: response.usage.thinking_tokens,
+            latency_ms,
+            error: None,
+        },
+        Err(error) => AiUsageEntry {
+            problem_id,
+            model,
+            api_key_label: None,
+            kind,
+            prompt_tokens: 0,
+            output_tokens: 0,
+            thinking_tokens: 0,
+            latency_ms,
+            error: Some(error),
+        },
+    };
+
+    let _ = db.log_ai_usage(&entry);
+
+    // The answer itself succeeded, but a context cache that could not be used is
+    // logged on its own so repeated cache failures show up in the usage history.
+    if let Ok(ChatResponse {
+        model,
+        api_key_label,
+        context_cache_error: Some(error),
+        ..
+    }) = result
+    {
+        let _ = db.log_ai_usage(&AiUsageEntry {
+            problem_id,
+            model,
+            api_key_label: Some(api_key_label),
+            kind: "context_cache",
+            prompt_tokens: 0,
+            output_tokens: 0,
+            thinking_tokens: 0,
+            latency_ms: 0,
+            error: Some(error),
+        });
+    }
+}
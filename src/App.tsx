@@ -7,11 +7,17 @@ import { ChatPanel } from "./components/ChatPanel";
 import { CodeEditor } from "./components/CodeEditor";
 import { Settings } from "./components/Settings";
 import { HistorySidebar } from "./components/HistorySidebar";
+import { NotePanel } from "./components/NotePanel";
+import { NoteLibrary } from "./components/NoteLibrary";
+import { ComplexityWarning } from "./components/ComplexityWarning";
 // 커스텀 훅 가져오기
 import { useProblem } from "./hooks/useProblem";
 import { useChat } from "./hooks/useChat";
 import { useSettings } from "./hooks/useSettings";
 import { useHistory } from "./hooks/useHistory";
+import { useSimilarProblems } from "./hooks/useSimilarProblems";
+import { useNotes } from "./hooks/useNotes";
+import { useComplexityCheck } from "./hooks/useComplexityCheck";
 import "./App.css";
 
 // 메인 앱 컴포넌트 - 전체 화면 구성
@@ -22,15 +28,58 @@ function App() {
   const [showApiKeyError, setShowApiKeyError] = useState(false);
   // 사용자가 입력한 코드
   const [userCode, setUserCode] = useState("");
+  // 코드 에디터에서 고른 언어
+  const [userLanguage, setUserLanguage] = useState("python");
+  // 풀이 노트 모아보기 창 표시 여부
+  const [showNotes, setShowNotes] = useState(false);
   // 오늘 이 문제를 풀었는지 여부
   const [isSolvedToday, setIsSolvedToday] = useState(false);
 
   // 설정 관리 (API 키, 모델, 프롬프트)
-  const { settings, models, loadingModels, modelLoadError, saveSettings, clearSettings, fetchModels, DEFAULT_PROMPT } = useSettings();
+  const {
+    settings,
+    apiKeys,
+    apiKeyLoadError,
+    networkSettings,
+    networkStatusError,
+    models,
+    loadingModels,
+    modelLoadError,
+    saveSettings,
+    removeApiKey,
+    saveNetworkSettings,
+    clearSettings,
+    fetchModels,
+    DEFAULT_PROMPT,
+  } = useSettings();
   // 문제 정보 관리 (번호로 검색한 문제)
   const { problem, loading: problemLoading, error, fetchProblem } = useProblem();
   // 문제 히스토리 관리 (과거에 풀었던 문제들)
   const { problems, activityData, loading: historyLoading, refreshHistory, deleteProblem } = useHistory();
+  // 비슷한 문제 찾기 (임베딩 검색)
+  const {
+    similar: similarProblems,
+    loading: similarLoading,
+    error: similarError,
+    findSimilar,
+  } = useSimilarProblems(problem?.id ?? null);
+  // 풀이 노트 (문제를 푼 뒤 AI가 정리, 사용자가 수정)
+  const {
+    note,
+    loading: noteLoading,
+    error: noteError,
+    generateNote,
+    saveNote,
+    deleteNote,
+  } = useNotes(problem?.id ?? null, settings.model);
+  // 복잡도 확인 (예상 연산 수를 시간 제한과 비교)
+  const {
+    result: complexityResult,
+    loading: complexityLoading,
+    error: complexityError,
+    checkComplexity,
+    dismiss: dismissComplexity,
+  } = useComplexityCheck(problem?.id ?? null, settings.model, userCode, userLanguage);
   // 채팅 관리 (AI와의 대화)
   const {
     messages,
@@ -38,6 +87,7 @@ function App() {
     sendMessage,
     clearMessages,
     streamingContent,
+    streamingThoughts,
   } = useChat(settings, problem, () => {
     // API 키가 유효하지 않으면 에러 모달과 설정 창 표시
     setShowApiKeyError(true);
@@ -69,7 +119,7 @@ function App() {
   // 메시지를 보낼 때 실행 - API 키 확인
   const handleSendMessage = (content: string) => {
     // API 키가 없으면 설정 창 열기
-    if (!settings.apiKey) {
+    if (!settings.hasApiKey) {
       setShowSettings(true);
       return;
     }
@@ -119,16 +169,25 @@ function App() {
       {/* 맨 위 헤더 (PSUP 제목, 설정 버튼) */}
       <header className="flex items-center justify-between px-6 py-4 border-b border-gray-800 bg-gray-900 z-10">
         <h1 className="text-xl font-bold">PSUP</h1>
-        {/* 설정 버튼 - API 키 여부에 따라 색상 변경 */}
-        <button
-          onClick={() => setShowSettings(true)}
-          className={`p-2 rounded-lg transition-colors ${
-            settings.apiKey ? "text-green-400 hover:bg-gray-800" : "text-yellow-400 hover:bg-gray-800"
-          }`}
-          title={settings.apiKey ? "API 키 설정됨" : "API 키 필요"}
-        >
-          <SettingsIcon />
-        </button>
+        <div className="flex items-center gap-2">
+          {/* 풀이 노트 모아보기 버튼 */}
+          <button
+            onClick={() => setShowNotes(true)}
+            className="px-3 py-1.5 text-sm text-gray-300 rounded-lg hover:bg-gray-800 transition-colors"
+          >
+            풀이 노트
+          </button>
+          {/* 설정 버튼 - API 키 여부에 따라 색상 변경 */}
+          <button
+            onClick={() => setShowSettings(true)}
+            className={`p-2 rounded-lg transition-colors ${
+              settings.hasApiKey ? "text-green-400 hover:bg-gray-800" : "text-yellow-400 hover:bg-gray-800"
+            }`}
+            title={settings.hasApiKey ? "API 키 설정됨" : "API 키 필요"}
+          >
+            <SettingsIcon />
+          </button>
+        </div>
       </header>
 
       {/* 메인 컨텐츠 영역 */}
@@ -160,7 +219,25 @@ function App() {
                 isSolvedToday={isSolvedToday}
                 onMarkSolved={handleMarkSolved}
                 onUnmarkSolved={handleUnmarkSolved}
-              />
+                similarProblems={similarProblems}
+                similarLoading={similarLoading}
+                similarError={similarError}
+                onFindSimilar={settings.hasApiKey ? findSimilar : undefined}
+                onSelectProblem={handleSelectProblem}
+              >
+                <NotePanel
+                  note={note}
+                  loading={noteLoading}
+                  error={noteError}
+                  onGenerate={
+                    settings.hasApiKey
+                      ? (overwrite) => generateNote(userCode, userLanguage, overwrite)
+                      : undefined
+                  }
+                  onSave={saveNote}
+                  onDelete={deleteNote}
+                />
+              </ProblemView>
             </div>
 
             {/* 오른쪽: AI 채팅 + 코드 에디터 (위아래) */}
@@ -171,15 +248,32 @@ function App() {
                   messages={messages}
                   loading={chatLoading}
                   streamingContent={streamingContent}
-                  hasApiKey={!!settings.apiKey}
+                  streamingThoughts={streamingThoughts}
+                  hasApiKey={settings.hasApiKey}
                   onSendMessage={handleSendMessage}
                   onClear={clearMessages}
                 />
               </div>
 
+              {/* 복잡도 확인 결과 */}
+              <ComplexityWarning
+                result={complexityResult}
+                error={complexityError}
+                onDismiss={dismissComplexity}
+              />
+
               {/* 아래: 코드 에디터 */}
               <div className="h-48">
-                <CodeEditor code={userCode} onChange={setUserCode} />
+                <CodeEditor
+                  code={userCode}
+                  onChange={setUserCode}
+                  language={userLanguage}
+                  onLanguageChange={setUserLanguage}
+                  onCheckComplexity={
+                    settings.hasApiKey && problem ? checkComplexity : undefined
+                  }
+                  checking={complexityLoading}
+                />
               </div>
             </div>
           </div>
@@ -207,16 +301,27 @@ function App() {
         </div>
       )}
 
+      {/* 풀이 노트 모아보기 모달 */}
+      {showNotes && (
+        <NoteLibrary onSelectProblem={handleSelectProblem} onClose={() => setShowNotes(false)} />
+      )}
+
       {/* 설정 모달 - API 키, 모델, 프롬프트를 설정할 때 표시 */}
       {showSettings && (
         <Settings
           settings={settings}
+          apiKeys={apiKeys}
+          apiKeyLoadError={apiKeyLoadError}
+          networkSettings={networkSettings}
+          networkStatusError={networkStatusError}
           models={models}
           loadingModels={loadingModels}
           modelLoadError={modelLoadError}
           defaultPrompt={DEFAULT_PROMPT}
           onFetchModels={fetchModels}
           onSave={saveSettings}
+          onRemoveKey={removeApiKey}
+          onSaveNetwork={saveNetworkSettings}
           onClear={clearSettings}
           onClose={() => setShowSettings(false)}
         />
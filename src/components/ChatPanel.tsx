@@ -10,6 +10,8 @@ interface ChatPanelProps {
   loading: boolean;
   // AI가 보내고 있는 텍스트 (스트리밍 중일 때)
   streamingContent: string;
+  // AI가 보내고 있는 생각 요약 (생각 요약을 요청했을 때만)
+  streamingThoughts: string;
   // API 키가 있는지 없는지
   hasApiKey: boolean;
   // 메시지를 보낼 때 실행할 함수
@@ -23,6 +25,7 @@ export function ChatPanel({
   messages,
   loading,
   streamingContent,
+  streamingThoughts,
   hasApiKey,
   onSendMessage,
   onClear,
@@ -87,6 +90,8 @@ export function ChatPanel({
               isLastAssistant && loading && streamingContent
                 ? streamingContent
                 : msg.content;
+            const displayThoughts =
+              isLastAssistant && loading ? streamingThoughts : msg.thoughts;
 
             // AI가 보낸 빈 메시지는 표시하지 않기
             if (msg.role === "assistant" && !displayContent && !loading) {
@@ -107,6 +112,15 @@ export function ChatPanel({
                 <div className="text-xs text-gray-500 mb-1">
                   {msg.role === "user" ? "나" : "AI"}
                 </div>
+                {/* 생각 요약은 기본으로 접어 두고, 펼치면 볼 수 있음 */}
+                {displayThoughts && (
+                  <details className="mb-2 text-sm text-gray-400">
+                    <summary className="cursor-pointer select-none">생각 과정</summary>
+                    <div className="prose prose-invert prose-sm max-w-none mt-1 pl-3 border-l border-gray-700 text-gray-400">
+                      <ReactMarkdown>{displayThoughts}</ReactMarkdown>
+                    </div>
+                  </details>
+                )}
                 {/* 메시지 내용 표시 (마크다운 형식 지원) */}
                 <div className="text-gray-200">
                   <div className="prose prose-invert prose-sm max-w-none prose-pre:bg-gray-900 prose-pre:border prose-pre:border-gray-700 prose-code:text-blue-300 prose-code:before:content-none prose-code:after:content-none">
@@ -1,4 +1,3 @@
-import { useState } from "react";
 import Editor from "@monaco-editor/react";
 
 const LANGUAGES = [
@@ -17,26 +16,47 @@ const LANGUAGES = [
 interface CodeEditorProps {
   code: string;
   onChange: (code: string) => void;
+  language: string;
+  onLanguageChange: (language: string) => void;
+  // 복잡도 확인 (API 키가 없으면 버튼을 숨김)
+  onCheckComplexity?: () => void;
+  checking?: boolean;
 }
 
-export function CodeEditor({ code, onChange }: CodeEditorProps) {
-  const [language, setLanguage] = useState("python");
-
+export function CodeEditor({
+  code,
+  onChange,
+  language,
+  onLanguageChange,
+  onCheckComplexity,
+  checking,
+}: CodeEditorProps) {
   return (
     <div className="flex flex-col h-full">
       <div className="flex items-center justify-between mb-2">
         <div className="text-sm text-gray-400">코드 (리뷰용)</div>
-        <select
-          value={language}
-          onChange={(e) => setLanguage(e.target.value)}
-          className="px-2 py-1 text-xs bg-gray-800 border border-gray-700 rounded text-gray-300 focus:outline-none focus:border-blue-500"
-        >
-          {LANGUAGES.map((lang) => (
-            <option key={lang.id} value={lang.id}>
-              {lang.name}
-            </option>
-          ))}
-        </select>
+        <div className="flex items-center gap-2">
+          {onCheckComplexity && (
+            <button
+              onClick={onCheckComplexity}
+              disabled={checking || !code.trim()}
+              className="px-2 py-1 text-xs text-blue-400 hover:text-blue-300 disabled:opacity-50"
+            >
+              {checking ? "확인 중..." : "복잡도 확인"}
+            </button>
+          )}
+          <select
+            value={language}
+            onChange={(e) => onLanguageChange(e.target.value)}
+            className="px-2 py-1 text-xs bg-gray-800 border border-gray-700 rounded text-gray-300 focus:outline-none focus:border-blue-500"
+          >
+            {LANGUAGES.map((lang) => (
+              <option key={lang.id} value={lang.id}>
+                {lang.name}
+              </option>
+            ))}
+          </select>
+        </div>
       </div>
       <div className="flex-1 rounded-lg overflow-hidden border border-gray-700">
         <Editor
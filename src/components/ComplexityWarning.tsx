@@ -0,0 +1,58 @@
+import type { ComplexityCheck } from "../types";
+
+interface ComplexityWarningProps {
+  result: ComplexityCheck | null;
+  error: string | null;
+  onDismiss: () => void;
+}
+
+const VERDICTS = {
+  ok: { label: "시간 안에 통과할 것 같아요", className: "border-green-500/40 text-green-300" },
+  tight: { label: "시간 제한에 빠듯해요", className: "border-yellow-500/40 text-yellow-300" },
+  too_slow: { label: "시간 초과가 날 가능성이 높아요", className: "border-red-500/40 text-red-300" },
+  unknown: { label: "시간 제한과 비교하지 못했어요", className: "border-gray-600 text-gray-300" },
+};
+
+// 큰 수를 10^k 꼴로 짧게 표시
+function formatCount(value: number) {
+  if (value < 1e4) return Math.round(value).toLocaleString();
+  const exponent = Math.floor(Math.log10(value));
+  const mantissa = value / 10 ** exponent;
+  return `${mantissa.toFixed(1)}×10^${exponent}`;
+}
+
+// 복잡도 검사 결과 (예상 연산 수와 시간 제한 비교)
+export function ComplexityWarning({ result, error, onDismiss }: ComplexityWarningProps) {
+  if (!result && !error) return null;
+
+  if (error) {
+    return (
+      <div className="mb-2 px-3 py-2 rounded-lg border border-red-500/40 text-sm text-red-400 flex justify-between">
+        <span>{error}</span>
+        <button onClick={onDismiss} className="text-gray-400 hover:text-gray-200">닫기</button>
+      </div>
+    );
+  }
+  if (!result) return null;
+
+  const verdict = VERDICTS[result.verdict];
+  return (
+    <div className={`mb-2 px-3 py-2 rounded-lg border bg-gray-800 text-sm ${verdict.className}`}>
+      <div className="flex justify-between">
+        <span className="font-semibold">
+          {verdict.label} · 시간 {result.time_complexity}, 공간 {result.space_complexity}
+        </span>
+        <button onClick={onDismiss} className="text-gray-400 hover:text-gray-200">닫기</button>
+      </div>
+      {result.estimated_operations !== null && (
+        <p className="text-gray-300">
+          예상 연산 {formatCount(result.estimated_operations)}회
+          {result.estimated_by_model && " (AI 추정)"}
+          {result.operations_budget !== null &&
+            ` / ${result.time_limit_secs}초 동안 약 ${formatCount(result.operations_budget)}회`}
+        </p>
+      )}
+      <p className="text-gray-400 whitespace-pre-wrap">{result.explanation}</p>
+    </div>
+  );
+}
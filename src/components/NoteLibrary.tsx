@@ -0,0 +1,63 @@
+import { useState, useEffect } from "react";
+import { useNoteSearch, notePitfalls } from "../hooks/useNotes";
+
+interface NoteLibraryProps {
+  onSelectProblem: (id: string) => void;
+  onClose: () => void;
+}
+
+// 풀이 노트 모아보기 (제목, 아이디어, 알고리즘 등에서 검색)
+export function NoteLibrary({ onSelectProblem, onClose }: NoteLibraryProps) {
+  const [query, setQuery] = useState("");
+  const { results, error, searchNotes } = useNoteSearch();
+
+  // 검색어를 입력할 때마다 잠깐 기다렸다가 검색
+  useEffect(() => {
+    const timer = setTimeout(() => searchNotes(query), 200);
+    return () => clearTimeout(timer);
+  }, [query]);
+
+  return (
+    <div className="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
+      <div className="bg-gray-800 rounded-lg p-6 w-full max-w-2xl max-h-[80vh] flex flex-col">
+        <div className="flex items-center justify-between mb-4">
+          <h2 className="text-lg font-bold">풀이 노트</h2>
+          <button onClick={onClose} className="text-gray-400 hover:text-gray-200">
+            닫기
+          </button>
+        </div>
+        <input
+          value={query}
+          onChange={(e) => setQuery(e.target.value)}
+          placeholder="검색 (예: 다익스트라, 오버플로)"
+          autoFocus
+          className="w-full px-3 py-2 mb-3 bg-gray-900 border border-gray-700 rounded-lg text-white focus:outline-none focus:border-blue-500"
+        />
+        {error && <p className="text-sm text-red-400 mb-2">{error}</p>}
+        <ul className="flex-1 overflow-y-auto space-y-2">
+          {results.length === 0 && <li className="text-sm text-gray-500">노트가 없습니다.</li>}
+          {results.map((note) => (
+            <li key={note.id}>
+              <button
+                onClick={() => {
+                  onSelectProblem(note.problem_id);
+                  onClose();
+                }}
+                className="w-full text-left px-4 py-3 bg-gray-900 hover:bg-gray-700 rounded-lg"
+              >
+                <div className="text-sm font-semibold text-gray-200">
+                  {note.problem_id}번: {note.title}
+                </div>
+                <div className="text-sm text-gray-400 line-clamp-2">{note.key_idea}</div>
+                <div className="text-xs text-gray-500 mt-1">
+                  {note.complexity}
+                  {notePitfalls(note).length > 0 && ` · 주의할 점 ${notePitfalls(note).length}개`}
+                </div>
+              </button>
+            </li>
+          ))}
+        </ul>
+      </div>
+    </div>
+  );
+}
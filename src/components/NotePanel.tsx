@@ -0,0 +1,171 @@
+import { useState, useEffect } from "react";
+import type { Note } from "../types";
+import { notePitfalls, type NoteDraft } from "../hooks/useNotes";
+
+interface NotePanelProps {
+  note: Note | null;
+  loading: boolean;
+  error: string | null;
+  // API 키가 없으면 생성 버튼을 숨김
+  onGenerate?: (overwrite: boolean) => void;
+  onSave: (draft: NoteDraft) => void;
+  onDelete: () => void;
+}
+
+// 풀이 노트 (AI가 정리하고 사용자가 고쳐 쓰는 개인 에디토리얼)
+export function NotePanel({ note, loading, error, onGenerate, onSave, onDelete }: NotePanelProps) {
+  const [editing, setEditing] = useState(false);
+  const [draft, setDraft] = useState<NoteDraft | null>(null);
+  // 직접 수정한 노트를 다시 작성하기 전에 한 번 더 확인
+  const [confirmingOverwrite, setConfirmingOverwrite] = useState(false);
+
+  // 노트가 바뀌면 편집 상태 초기화
+  useEffect(() => {
+    setEditing(false);
+    setDraft(null);
+    setConfirmingOverwrite(false);
+  }, [note?.id, note?.updated_at]);
+
+  const handleGenerate = () => {
+    if (!onGenerate) return;
+    if (note?.edited && !confirmingOverwrite) {
+      setConfirmingOverwrite(true);
+      return;
+    }
+    setConfirmingOverwrite(false);
+    onGenerate(!!note?.edited);
+  };
+
+  const startEditing = () => {
+    if (!note) return;
+    setDraft({
+      key_idea: note.key_idea,
+      algorithm: note.algorithm,
+      complexity: note.complexity,
+      pitfalls: notePitfalls(note),
+    });
+    setEditing(true);
+  };
+
+  const handleSave = () => {
+    if (!draft) return;
+    onSave({ ...draft, pitfalls: draft.pitfalls.map((p) => p.trim()).filter(Boolean) });
+  };
+
+  return (
+    <div>
+      <div className="flex items-center justify-between mb-2">
+        <h3 className="text-lg font-semibold text-white">풀이 노트</h3>
+        <div className="flex gap-3 text-sm">
+          {note && !editing && (
+            <>
+              <button onClick={startEditing} className="text-gray-400 hover:text-gray-200">
+                수정
+              </button>
+              <button onClick={onDelete} className="text-gray-400 hover:text-red-400">
+                삭제
+              </button>
+            </>
+          )}
+          {onGenerate && !editing && (
+            <button
+              onClick={handleGenerate}
+              disabled={loading}
+              className="text-blue-400 hover:text-blue-300 disabled:opacity-50"
+            >
+              {loading ? "작성 중..." : note ? "다시 작성" : "AI로 정리하기"}
+            </button>
+          )}
+        </div>
+      </div>
+      {confirmingOverwrite && (
+        <div className="flex items-center justify-between gap-2 mb-2 text-sm text-yellow-300">
+          <span>직접 수정한 내용이 사라집니다. 다시 작성할까요?</span>
+          <div className="flex gap-3">
+            <button onClick={() => setConfirmingOverwrite(false)} className="text-gray-400 hover:text-gray-200">
+              취소
+            </button>
+            <button onClick={handleGenerate} className="text-blue-400 hover:text-blue-300">
+              다시 작성
+            </button>
+          </div>
+        </div>
+      )}
+      {error && <p className="text-sm text-red-400 mb-2">{error}</p>}
+      {!note && !loading && (
+        <p className="text-sm text-gray-500">문제를 푼 뒤 코드와 대화를 바탕으로 풀이를 정리할 수 있습니다.</p>
+      )}
+
+      {note && editing && draft && (
+        <div className="space-y-3">
+          <NoteField label="핵심 아이디어" value={draft.key_idea} onChange={(v) => setDraft({ ...draft, key_idea: v })} />
+          <NoteField label="알고리즘" value={draft.algorithm} onChange={(v) => setDraft({ ...draft, algorithm: v })} />
+          <NoteField label="복잡도" value={draft.complexity} onChange={(v) => setDraft({ ...draft, complexity: v })} />
+          <NoteField
+            label="주의할 점 (한 줄에 하나)"
+            value={draft.pitfalls.join("\n")}
+            onChange={(v) => setDraft({ ...draft, pitfalls: v.split("\n") })}
+          />
+          <div className="flex justify-end gap-2">
+            <button
+              onClick={() => setEditing(false)}
+              className="px-3 py-1.5 text-sm text-gray-300 bg-gray-800 rounded-lg hover:bg-gray-700"
+            >
+              취소
+            </button>
+            <button
+              onClick={handleSave}
+              className="px-3 py-1.5 text-sm text-white bg-blue-600 rounded-lg hover:bg-blue-700"
+            >
+              저장
+            </button>
+          </div>
+        </div>
+      )}
+
+      {note && !editing && (
+        <div className="space-y-3 text-sm text-gray-300">
+          <NoteText label="핵심 아이디어" text={note.key_idea} />
+          <NoteText label="알고리즘" text={note.algorithm} />
+          <NoteText label="복잡도" text={note.complexity} />
+          {notePitfalls(note).length > 0 && (
+            <div>
+              <div className="text-gray-400 mb-1">주의할 점</div>
+              <ul className="list-disc list-inside space-y-0.5">
+                {notePitfalls(note).map((p, idx) => (
+                  <li key={idx}>{p}</li>
+                ))}
+              </ul>
+            </div>
+          )}
+          <p className="text-xs text-gray-500">
+            {note.edited ? "직접 수정함" : `${note.model}로 작성`} · {note.updated_at}
+          </p>
+        </div>
+      )}
+    </div>
+  );
+}
+
+function NoteText({ label, text }: { label: string; text: string }) {
+  return (
+    <div>
+      <div className="text-gray-400 mb-1">{label}</div>
+      <p className="whitespace-pre-wrap">{text}</p>
+    </div>
+  );
+}
+
+function NoteField({ label, value, onChange }: { label: string; value: string; onChange: (value: string) => void }) {
+  return (
+    <label className="block">
+      <span className="text-sm text-gray-400">{label}</span>
+      <textarea
+        value={value}
+        onChange={(e) => onChange(e.target.value)}
+        rows={3}
+        className="mt-1 w-full px-3 py-2 text-sm bg-gray-800 border border-gray-700 rounded-lg text-gray-200 focus:outline-none focus:border-blue-500"
+      />
+    </label>
+  );
+}
@@ -1,4 +1,5 @@
-import type { Problem } from "../types";
+import type { ReactNode } from "react";
+import type { Problem, SimilarProblem } from "../types";
 
 interface ProblemViewProps {
   problem: Problem | null;
@@ -7,9 +8,30 @@ interface ProblemViewProps {
   isSolvedToday?: boolean;
   onMarkSolved?: () => void;
   onUnmarkSolved?: () => void;
+  // 비슷한 문제 찾기 (찾기 전에는 null)
+  similarProblems?: SimilarProblem[] | null;
+  similarLoading?: boolean;
+  similarError?: string | null;
+  onFindSimilar?: () => void;
+  onSelectProblem?: (id: string) => void;
+  // 문제 설명 아래에 붙는 내용 (풀이 노트 등)
+  children?: ReactNode;
 }
 
-export function ProblemView({ problem, loading, error, isSolvedToday, onMarkSolved, onUnmarkSolved }: ProblemViewProps) {
+export function ProblemView({
+  problem,
+  loading,
+  error,
+  isSolvedToday,
+  onMarkSolved,
+  onUnmarkSolved,
+  similarProblems,
+  similarLoading,
+  similarError,
+  onFindSimilar,
+  onSelectProblem,
+  children,
+}: ProblemViewProps) {
   if (loading) {
     return (
       <div className="flex items-center justify-center h-full text-gray-400">
@@ -97,6 +119,45 @@ export function ProblemView({ problem, loading, error, isSolvedToday, onMarkSolv
           ))}
         </div>
       </div>
+
+      {/* 비슷한 문제 (지금까지 불러온 문제들 중에서 찾음) */}
+      {onFindSimilar && (
+        <div>
+          <div className="flex items-center justify-between mb-2">
+            <h3 className="text-lg font-semibold text-white">비슷한 문제</h3>
+            <button
+              onClick={onFindSimilar}
+              disabled={similarLoading}
+              className="text-sm text-blue-400 hover:text-blue-300 disabled:opacity-50"
+            >
+              {similarLoading ? "찾는 중..." : "찾기"}
+            </button>
+          </div>
+          {similarError && <p className="text-sm text-red-400">{similarError}</p>}
+          {similarProblems && similarProblems.length === 0 && (
+            <p className="text-sm text-gray-500">비교할 다른 문제가 아직 없습니다.</p>
+          )}
+          {similarProblems && similarProblems.length > 0 && (
+            <ul className="space-y-1">
+              {similarProblems.map((p) => (
+                <li key={p.problem_id}>
+                  <button
+                    onClick={() => onSelectProblem?.(p.problem_id)}
+                    className="w-full flex justify-between px-3 py-1.5 bg-gray-800 hover:bg-gray-700 rounded-lg text-sm text-left"
+                  >
+                    <span className="text-gray-200">
+                      {p.problem_id}번: {p.title}
+                    </span>
+                    <span className="text-gray-500">{Math.round(p.score * 100)}%</span>
+                  </button>
+                </li>
+              ))}
+            </ul>
+          )}
+        </div>
+      )}
+
+      {children}
     </div>
   );
 }
@@ -1,49 +1,102 @@
 import { useState, useEffect } from "react";
-import type { Settings, AvailableModel } from "../hooks/useSettings";
+import type { Settings, AvailableModel, ApiKeyInfo, NetworkSettings } from "../hooks/useSettings";
 
 interface SettingsProps {
   settings: Settings;
+  apiKeys: ApiKeyInfo[];
+  // 저장된 키 파일을 읽지 못한 이유 (읽지 못한 파일은 secrets.json.bak으로 보관됨)
+  apiKeyLoadError: string | null;
+  networkSettings: NetworkSettings | null;
+  // 저장된 네트워크 설정을 시작할 때 적용하지 못한 이유
+  networkStatusError: string | null;
   models: AvailableModel[];
   loadingModels: boolean;
   modelLoadError: boolean;
   defaultPrompt: string;
-  onFetchModels: (apiKey: string) => void;
-  onSave: (settings: Settings) => void;
+  onFetchModels: (apiKey?: string, refresh?: boolean) => void;
+  onSave: (settings: Omit<Settings, "hasApiKey">, apiKey?: string, label?: string) => void;
+  onRemoveKey: (label: string) => void;
+  onSaveNetwork: (settings: NetworkSettings) => Promise<string | null>;
   onClear: () => void;
   onClose: () => void;
 }
 
 export function Settings({
   settings,
+  apiKeys,
+  apiKeyLoadError,
+  networkSettings,
+  networkStatusError,
   models,
   loadingModels,
   modelLoadError,
   defaultPrompt,
   onFetchModels,
   onSave,
+  onRemoveKey,
+  onSaveNetwork,
   onClear,
   onClose,
 }: SettingsProps) {
-  const [apiKey, setApiKey] = useState(settings.apiKey);
+  // 새로 입력한 키만 담음 (저장된 키는 백엔드에서 돌려주지 않음)
+  const [apiKey, setApiKey] = useState("");
+  const [keyLabel, setKeyLabel] = useState("");
   const [model, setModel] = useState(settings.model);
   const [customPrompt, setCustomPrompt] = useState(settings.customPrompt);
+  const [showThoughts, setShowThoughts] = useState(settings.showThoughts);
+  // 네트워크 설정은 입력 그대로 문자열로 들고 있다가 저장할 때 변환
+  const [proxyUrl, setProxyUrl] = useState("");
+  const [caCertificates, setCaCertificates] = useState("");
+  const [connectTimeout, setConnectTimeout] = useState("");
+  const [readTimeout, setReadTimeout] = useState("");
+  const [userAgent, setUserAgent] = useState("");
+  const [networkError, setNetworkError] = useState<string | null>(null);
 
   useEffect(() => {
-    setApiKey(settings.apiKey);
+    setApiKey("");
+    setKeyLabel("");
     setModel(settings.model);
     setCustomPrompt(settings.customPrompt);
+    setShowThoughts(settings.showThoughts);
   }, [settings]);
 
+  useEffect(() => {
+    setProxyUrl(networkSettings?.proxy_url ?? "");
+    setCaCertificates(networkSettings?.ca_certificates.join("\n") ?? "");
+    setConnectTimeout(networkSettings?.connect_timeout_secs?.toString() ?? "");
+    setReadTimeout(networkSettings?.read_timeout_secs?.toString() ?? "");
+    setUserAgent(networkSettings?.user_agent ?? "");
+  }, [networkSettings]);
+
   const handleApiKeyChange = (e: React.ChangeEvent<HTMLInputElement>) => {
     const newKey = e.target.value;
     setApiKey(newKey);
-    if (newKey && newKey !== settings.apiKey) {
+    if (newKey) {
       onFetchModels(newKey);
     }
   };
 
-  const handleSave = () => {
-    onSave({ apiKey, model, customPrompt });
+  const handleSave = async () => {
+    const toSeconds = (value: string) => (value.trim() ? Number(value) : null);
+    const newNetwork: NetworkSettings = {
+      proxy_url: proxyUrl.trim() || null,
+      ca_certificates: caCertificates
+        .split("\n")
+        .map((path) => path.trim())
+        .filter(Boolean),
+      connect_timeout_secs: toSeconds(connectTimeout),
+      read_timeout_secs: toSeconds(readTimeout),
+      user_agent: userAgent.trim() || null,
+    };
+    if (JSON.stringify(newNetwork) !== JSON.stringify(networkSettings)) {
+      const error = await onSaveNetwork(newNetwork);
+      if (error) {
+        setNetworkError(error);
+        return;
+      }
+    }
+    setNetworkError(null);
+    onSave({ model, customPrompt, showThoughts }, apiKey || undefined, keyLabel.trim() || undefined);
     onClose();
   };
 
@@ -60,15 +113,55 @@ export function Settings({
           <label className="block text-sm text-gray-400 mb-2">
             Google Gemini API Key
           </label>
-          <input
-            type="password"
-            value={apiKey}
-            onChange={handleApiKeyChange}
-            placeholder="API 키를 입력하세요"
-            className="w-full px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500"
-          />
+          {apiKeyLoadError && (
+            <p className="mb-2 text-xs text-red-400">
+              저장된 API 키를 읽지 못했습니다. 키를 다시 입력해 주세요: {apiKeyLoadError}
+            </p>
+          )}
+          {apiKeys.length > 0 && (
+            <ul className="mb-2 space-y-1">
+              {apiKeys.map((key) => (
+                <li
+                  key={key.label}
+                  className="flex items-center justify-between px-3 py-1.5 bg-gray-800 rounded-lg text-sm"
+                >
+                  <span className="text-gray-300">
+                    {key.label}
+                    {key.cooldown_secs > 0 && (
+                      <span className="ml-2 text-xs text-yellow-400">
+                        할당량 소진 ({key.cooldown_secs}초 후 재사용)
+                      </span>
+                    )}
+                  </span>
+                  <button
+                    onClick={() => onRemoveKey(key.label)}
+                    className="text-xs text-red-400 hover:text-red-300"
+                  >
+                    삭제
+                  </button>
+                </li>
+              ))}
+            </ul>
+          )}
+          <div className="flex gap-2">
+            <input
+              type="text"
+              value={keyLabel}
+              onChange={(e) => setKeyLabel(e.target.value)}
+              placeholder="라벨 (선택)"
+              className="w-32 px-3 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500"
+            />
+            <input
+              type="password"
+              value={apiKey}
+              onChange={handleApiKeyChange}
+              placeholder={settings.hasApiKey ? "키를 추가하거나 같은 라벨로 교체" : "API 키를 입력하세요"}
+              className="flex-1 px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500"
+            />
+          </div>
           <p className="text-xs text-gray-500 mt-2">
-            API 키는 로컬 저장소에 저장됩니다
+            API 키는 이 기기에서만 풀 수 있도록 암호화되어 저장됩니다. 여러 키를 등록하면 번갈아 사용하고,
+            할당량이 소진된 키는 잠시 쉬게 합니다.
           </p>
         </div>
 
@@ -76,11 +169,19 @@ export function Settings({
            <label className="block text-sm text-gray-400 mb-2">
              모델 선택 {loadingModels && <span className="text-blue-400 text-xs">(업데이트 중...)</span>}
              {modelLoadError && <span className="text-red-400 text-xs">(API 키 오류)</span>}
+             {settings.hasApiKey && !loadingModels && (
+               <button
+                 onClick={() => onFetchModels(apiKey || undefined, true)}
+                 className="ml-2 text-xs text-blue-400 hover:text-blue-300"
+               >
+                 새로고침
+               </button>
+             )}
            </label>
            <select
              value={model}
              onChange={(e) => setModel(e.target.value)}
-             disabled={loadingModels || !apiKey || models.length === 0 || modelLoadError}
+             disabled={loadingModels || (!apiKey && !settings.hasApiKey) || models.length === 0 || modelLoadError}
              className="w-full px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white focus:outline-none focus:border-blue-500 disabled:opacity-50 disabled:cursor-not-allowed"
            >
              {models.length === 0 ? (
@@ -89,6 +190,8 @@ export function Settings({
                models.map((m) => (
                  <option key={m.id} value={m.id}>
                    {m.name} ({m.id})
+                   {m.inputTokenLimit ? ` · ${Math.round(m.inputTokenLimit / 1000)}K` : ""}
+                   {m.thinking ? " · thinking" : ""}
                  </option>
                ))
              )}
@@ -115,14 +218,79 @@ export function Settings({
           />
         </div>
 
+        {/* 회사망 등에서 프록시나 사설 인증서가 필요할 때만 설정 */}
+        <details className="mb-4" open={!!(networkError || networkStatusError)}>
+          <summary className="text-sm text-gray-400 cursor-pointer select-none">네트워크 설정</summary>
+          <div className="mt-2 space-y-2">
+            <input
+              type="text"
+              value={proxyUrl}
+              onChange={(e) => setProxyUrl(e.target.value)}
+              placeholder="프록시 주소 (예: http://proxy.example.com:3128)"
+              className="w-full px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500 text-sm"
+            />
+            <textarea
+              value={caCertificates}
+              onChange={(e) => setCaCertificates(e.target.value)}
+              placeholder="추가 루트 인증서 PEM 파일 경로 (한 줄에 하나)"
+              rows={2}
+              className="w-full px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500 resize-none text-sm"
+            />
+            <div className="flex gap-2">
+              <input
+                type="number"
+                min={1}
+                value={connectTimeout}
+                onChange={(e) => setConnectTimeout(e.target.value)}
+                placeholder="연결 제한 시간 (초, 기본 10)"
+                className="flex-1 px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500 text-sm"
+              />
+              <input
+                type="number"
+                min={1}
+                value={readTimeout}
+                onChange={(e) => setReadTimeout(e.target.value)}
+                placeholder="응답 대기 시간 (초, 기본 120)"
+                className="flex-1 px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500 text-sm"
+              />
+            </div>
+            <input
+              type="text"
+              value={userAgent}
+              onChange={(e) => setUserAgent(e.target.value)}
+              placeholder="User-Agent (비워 두면 기본값)"
+              className="w-full px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg text-white placeholder-gray-500 focus:outline-none focus:border-blue-500 text-sm"
+            />
+            {networkError && <p className="text-xs text-red-400">{networkError}</p>}
+            {!networkError && networkStatusError && (
+              <p className="text-xs text-red-400">
+                저장된 설정을 적용하지 못해 기본 설정으로 연결 중입니다: {networkStatusError}
+              </p>
+            )}
+          </div>
+        </details>
+
+        {/* 생각하는 모델에서만 효과가 있음 */}
+        <div className="mb-4">
+          <label className="flex items-center gap-2 text-sm text-gray-400">
+            <input
+              type="checkbox"
+              checked={showThoughts}
+              onChange={(e) => setShowThoughts(e.target.checked)}
+            />
+            AI의 생각 과정 요약 보기
+          </label>
+        </div>
+
         <div className="flex gap-2 justify-end">
-          {settings.apiKey && (
+          {settings.hasApiKey && (
             <button
               onClick={() => {
                 onClear();
                 setApiKey("");
                 setModel("gemini-2.5-flash");
                 setCustomPrompt(defaultPrompt);
+                setShowThoughts(false);
               }}
               className="px-4 py-2 text-red-400 hover:text-red-300"
             >
@@ -2,66 +2,129 @@ import { useState, useCallback, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 // 타입 정의
-import type { ChatMessage, Problem } from "../types";
+import type { ChatMessage, Problem, ToolInvocation } from "../types";
 // 타입 정의
 import type { Settings } from "./useSettings";
 
-interface StreamChunk {
+// 스트림은 항상 done 또는 error 하나로 끝남
+type StreamEvent =
+  | { type: "delta"; text: string; kind: "answer" | "thought" } // 생각 요약은 답변과 따로 쌓아서 접어서 보여줌
+  | { type: "usage"; usage: ChatResponse["usage"] }
+  | { type: "done" }
+  | {
+      type: "error";
+      kind: "network" | "rate_limited" | "auth" | "invalid_request" | "server" | "other";
+      message: string;
+      retryable: boolean; // 잠시 후 다시 보내면 될 수 있는 오류인지
+      partial: ChatResponse | null; // 실패하기 전까지 받은 답변
+    };
+
+type StreamError = Extract<StreamEvent, { type: "error" }>;
+
+interface ChatResponse {
   text: string;
-  done: boolean;
+  model: string;
+  api_key_label: string;
+  usage: { prompt_tokens: number; output_tokens: number; thinking_tokens: number; cached_tokens: number };
+  tool_calls: ToolInvocation[];
+  thoughts: string;
+  cached: boolean; // 같은 요청의 답을 디스크 캐시에서 재사용한 경우 (API 호출 없음)
 }
 
-interface ChatRecord {
+export interface ChatRecord {
   id: number;
   problem_id: string;
-  messages_json: string;
+  title: string;
+  active_message_id: number | null;
   created_at: string;
   updated_at: string;
 }
 
+interface MessageRecord {
+  id: number;
+  thread_id: number;
+  parent_id: number | null;
+  role: "user" | "assistant";
+  content: string;
+  thoughts: string;
+  model: string | null;
+  tokens: number | null;
+  tool_calls_json: string;
+  sibling_index: number;
+  sibling_count: number;
+  created_at: string;
+}
+
+const DEFAULT_THREAD_TITLE = "기본 대화";
+
+function toChatMessage(record: MessageRecord): ChatMessage {
+  return {
+    id: record.id,
+    role: record.role,
+    content: record.content,
+    thoughts: record.thoughts || undefined,
+    tool_calls: JSON.parse(record.tool_calls_json) as ToolInvocation[],
+    sibling_index: record.sibling_index,
+    sibling_count: record.sibling_count,
+  };
+}
+
 // 함수/상수
 export function useChat(settings: Settings, problem: Problem | null, onApiKeyError?: () => void) {
   const [messages, setMessages] = useState<ChatMessage[]>([]);
   const [loading, setLoading] = useState(false);
   const [streamingContent, setStreamingContent] = useState("");
+  const [streamingThoughts, setStreamingThoughts] = useState("");
+  const [threads, setThreads] = useState<ChatRecord[]>([]);
+  const [threadId, setThreadId] = useState<number | null>(null);
   const sessionIdRef = useRef<string>("");
+  const streamErrorRef = useRef<StreamError | null>(null);
   const currentProblemIdRef = useRef<string | null>(null);
 
+  // 상태 관리 함수
+  const applyThread = useCallback(async (chat: ChatRecord | null) => {
+    setThreadId(chat?.id ?? null);
+    setStreamingContent("");
+    setStreamingThoughts("");
+    if (!chat) {
+      setMessages([]);
+      return;
+    }
+    const branch = await invoke<MessageRecord[]>("get_active_branch", { threadId: chat.id });
+    setMessages(branch.map(toChatMessage));
+  }, []);
+
   // 상태 관리 함수
   const loadChat = useCallback(async (problemId: string) => {
     console.log("[useChat] loadChat called with problemId:", problemId);
     try {
-      const chat = await invoke<ChatRecord | null>("get_chat_by_problem", {
-        problemId,
-      });
-      console.log("[useChat] loaded chat:", chat);
-      if (chat && chat.messages_json) {
-        const parsed = JSON.parse(chat.messages_json) as ChatMessage[];
-        console.log("[useChat] parsed messages:", parsed);
-        setMessages(parsed);
-      } else {
-        console.log("[useChat] no chat found, clearing messages");
-        setMessages([]);
-      }
+      const list = await invoke<ChatRecord[]>("get_chat_threads", { problemId });
+      setThreads(list);
+      await applyThread(list[0] ?? null);
     } catch (e) {
       console.error("[useChat] Failed to load chat:", e);
-      setMessages([]);
+      setThreads([]);
+      applyThread(null);
     }
-  }, []);
+  }, [applyThread]);
 
   // 상태 관리 함수
-  const saveChat = useCallback(async (problemId: string, msgs: ChatMessage[]) => {
-    console.log("[useChat] saveChat called:", problemId, msgs);
-    try {
-      await invoke("save_chat", {
-        problemId,
-        messagesJson: JSON.stringify(msgs),
+  const appendMessage = useCallback(
+    async (thread: number, parentId: number | null, message: ChatMessage, response?: ChatResponse) => {
+      const record = await invoke<MessageRecord>("add_chat_message", {
+        threadId: thread,
+        parentId,
+        role: message.role,
+        content: message.content,
+        model: response?.model ?? null,
+        tokens: response?.usage.output_tokens ?? null,
+        toolCalls: message.tool_calls ?? [],
+        thoughts: message.thoughts ?? null,
       });
-      console.log("[useChat] chat saved successfully");
-    } catch (e) {
-      console.error("[useChat] Failed to save chat:", e);
-    }
-  }, []);
+      return toChatMessage(record);
+    },
+    []
+  );
 
   // 상태 관리 함수
   useEffect(() => {
@@ -69,6 +132,7 @@ export function useChat(settings: Settings, problem: Problem | null, onApiKeyErr
     if (problem?.id !== currentProblemIdRef.current) {
       currentProblemIdRef.current = problem?.id ?? null;
       setStreamingContent("");
+      setStreamingThoughts("");
       
       if (problem?.id) {
         loadChat(problem.id);
@@ -78,34 +142,6 @@ export function useChat(settings: Settings, problem: Problem | null, onApiKeyErr
     }
   }, [problem?.id, loadChat]);
 
-  // 상태 관리 함수
-  const buildContext = useCallback(
-    (userCode?: string) => {
-      if (!problem) return "";
-      let context = `[문제 정보]
-제목: ${problem.title} (${problem.id}번)
-제한: ${problem.time_limit}, ${problem.memory_limit}
-
-[문제 설명]
-${problem.description}
-
-[입력]
-${problem.input_description}
-
-[출력]
-${problem.output_description}
-
-[예제]
-${problem.samples.map((s, i) => `예제 ${i + 1}:\n입력:\n${s.input}\n출력:\n${s.output}`).join("\n\n")}`;
-
-      if (userCode) {
-        context += `\n\n[사용자 코드]\n${userCode}`;
-      }
-      return context;
-    },
-    [problem]
-  );
-
   // 상태 관리 함수
   useEffect(() => {
     if (!sessionIdRef.current) return;
@@ -113,25 +149,35 @@ ${problem.samples.map((s, i) => `예제 ${i + 1}:\n입력:\n${s.input}\n출력:\
     const eventName = `chat-stream-${sessionIdRef.current}`;
     let unlistenFn: (() => void) | undefined;
 
-    listen<StreamChunk>(eventName, (event) => {
-      const { text, done } = event.payload;
-      if (done) {
-        setMessages((prev) => {
-          const updated = prev.map((m, i) =>
-            i === prev.length - 1 && m.role === "assistant" && m.content === ""
-              ? { ...m, content: streamingContent + text }
-              : m
-          );
-          if (problem?.id) {
-            saveChat(problem.id, updated);
+    listen<StreamEvent>(eventName, (event) => {
+      const payload = event.payload;
+      switch (payload.type) {
+        case "delta":
+          if (payload.kind === "thought") {
+            setStreamingThoughts((prev) => prev + payload.text);
+          } else {
+            setStreamingContent((prev) => prev + payload.text);
           }
-          return updated;
-        });
-        setStreamingContent("");
-        setLoading(false);
-        sessionIdRef.current = "";
-      } else {
-        setStreamingContent((prev) => prev + text);
+          break;
+        case "usage":
+          break;
+        case "done":
+          setMessages((prev) => {
+            return prev.map((m, i) =>
+              i === prev.length - 1 && m.role === "assistant" && m.content === ""
+                ? { ...m, content: streamingContent }
+                : m
+            );
+          });
+          setStreamingContent("");
+          setStreamingThoughts("");
+          setLoading(false);
+          sessionIdRef.current = "";
+          break;
+        case "error":
+          // 실패 처리는 invoke의 catch에서 하고, 여기서는 받은 데까지의 답변만 기억함
+          streamErrorRef.current = payload;
+          break;
       }
     }).then((fn) => {
       unlistenFn = fn;
@@ -140,79 +186,190 @@ ${problem.samples.map((s, i) => `예제 ${i + 1}:\n입력:\n${s.input}\n출력:\
     return () => {
       unlistenFn?.();
     };
-  }, [sessionIdRef.current, streamingContent, problem?.id, saveChat]);
-
-   const sendMessage = async (content: string, userCode?: string) => {
-     if (!settings.apiKey || !problem) return;
- 
-     const displayContent = userCode
-       ? `${content}\n\n\`\`\`\n${userCode}\n\`\`\``
-       : content;
-     const userMessage: ChatMessage = { role: "user", content: displayContent };
-     const newMessages = [...messages, userMessage];
-     setMessages([...newMessages, { role: "assistant", content: "" }]);
-     setLoading(true);
-     setStreamingContent("");
- 
-     const sessionId = crypto.randomUUID();
-     sessionIdRef.current = sessionId;
- 
-     try {
-        const contextedMessages = newMessages.map((m, i) => {
-          if (i === 0) {
-            return {
-              ...m,
-              content: `${buildContext()}\\n\\n${m.content}`,
-            };
-          }
-          return m;
-        });
- 
-       const fullResponse = await invoke<string>("chat_with_ai_stream", {
-         apiKey: settings.apiKey,
-         model: settings.model,
-         messages: contextedMessages,
-         systemPrompt: settings.customPrompt,
-         sessionId,
-       });
- 
-       const finalMessages = newMessages.concat([
-         { role: "assistant", content: fullResponse },
-       ]);
-       setMessages(finalMessages);
-       await saveChat(problem.id, finalMessages);
-     } catch (e) {
-       const errorStr = String(e);
-       const isApiKeyInvalid = errorStr.includes("API_KEY_INVALID") || errorStr.includes("API key not valid");
-       
-       if (isApiKeyInvalid) {
-         setMessages(newMessages);
-         onApiKeyError?.();
-       } else {
-         const errorMessages = newMessages.concat([
-           { role: "assistant", content: `오류: ${e}` },
-         ]);
-         setMessages(errorMessages);
-       }
-     } finally {
-       setLoading(false);
-       sessionIdRef.current = "";
-     }
-   };
+  }, [sessionIdRef.current, streamingContent]);
 
+  // 현재 브랜치를 AI에 보내고 답변을 마지막 메시지 아래에 저장
+  const requestReply = async (thread: number, history: ChatMessage[]) => {
+    if (!settings.hasApiKey || !problem) return;
+    const parent = history[history.length - 1];
+
+    setMessages([...history, { role: "assistant", content: "" }]);
+    setLoading(true);
+    setStreamingContent("");
+    setStreamingThoughts("");
+
+    const sessionId = crypto.randomUUID();
+    sessionIdRef.current = sessionId;
+    streamErrorRef.current = null;
+
+    try {
+      // 문제 정보는 백엔드가 problemId로 찾아서 대화 앞에 붙임
+      const requestMessages = history.map((m) => ({
+        role: m.role,
+        content: m.content,
+        tool_calls: m.tool_calls,
+      }));
+
+      const response = await invoke<ChatResponse>("chat_with_ai_stream", {
+        model: settings.model,
+        messages: requestMessages,
+        systemPrompt: settings.customPrompt,
+        sessionId,
+        problemId: problem.id,
+        threadId: thread,
+        options: settings.showThoughts ? { generation: { include_thoughts: true } } : null,
+      });
+
+      const reply = await appendMessage(
+        thread,
+        parent.id ?? null,
+        {
+          role: "assistant",
+          content: response.text,
+          thoughts: response.thoughts || undefined,
+          tool_calls: response.tool_calls,
+        },
+        response
+      );
+      setMessages([...history, reply]);
+    } catch (e) {
+      const streamError = streamErrorRef.current;
+      const errorStr = String(e);
+      const isApiKeyInvalid =
+        streamError?.kind === "auth" ||
+        errorStr.includes("API_KEY_INVALID") ||
+        errorStr.includes("API key not valid");
+
+      if (isApiKeyInvalid) {
+        setMessages(history);
+        onApiKeyError?.();
+      } else {
+        // 중간에 끊긴 경우 받은 데까지의 답변은 남기고 아래에 오류를 덧붙임
+        const partial = streamError?.partial;
+        const notice = streamError?.retryable ? "\n\n잠시 후 다시 시도해 보세요." : "";
+        const content = partial?.text
+          ? `${partial.text}\n\n---\n오류: ${e}${notice}`
+          : `오류: ${e}${notice}`;
+        setMessages([
+          ...history,
+          { role: "assistant", content, thoughts: partial?.thoughts || undefined },
+        ]);
+      }
+    } finally {
+      setLoading(false);
+      sessionIdRef.current = "";
+    }
+  };
+
+  const ensureThread = async () => {
+    if (threadId !== null) return threadId;
+    if (!problem?.id) return null;
+    const thread = await invoke<ChatRecord>("create_chat_thread", {
+      problemId: problem.id,
+      title: DEFAULT_THREAD_TITLE,
+    });
+    setThreads((prev) => [thread, ...prev]);
+    setThreadId(thread.id);
+    return thread.id;
+  };
+
+  const sendMessage = async (content: string, userCode?: string) => {
+    if (!settings.hasApiKey || !problem) return;
+
+    const displayContent = userCode
+      ? `${content}\n\n\`\`\`\n${userCode}\n\`\`\``
+      : content;
+    const thread = await ensureThread();
+    if (thread === null) return;
+
+    const parentId = messages.length > 0 ? messages[messages.length - 1].id ?? null : null;
+    const userMessage = await appendMessage(thread, parentId, { role: "user", content: displayContent });
+    await requestReply(thread, [...messages, userMessage]);
+  };
+
+  // 과거 사용자 메시지를 수정하면 새 브랜치를 만들고 답변을 다시 받음
+  const editMessage = async (messageId: number, content: string) => {
+    if (threadId === null) return;
+    const index = messages.findIndex((m) => m.id === messageId);
+    if (index < 0 || messages[index].role !== "user") return;
+
+    const edited = await invoke<MessageRecord>("edit_chat_message", { messageId, content });
+    await requestReply(threadId, [...messages.slice(0, index), toChatMessage(edited)]);
+  };
+
+  // AI 답변을 다시 생성 (이전 답변은 형제 브랜치로 남음)
+  const regenerate = async (messageId: number) => {
+    if (threadId === null) return;
+    const index = messages.findIndex((m) => m.id === messageId);
+    if (index < 1 || messages[index].role !== "assistant") return;
+
+    await requestReply(threadId, messages.slice(0, index));
+  };
+
+  // 같은 위치의 다른 버전(형제 메시지)으로 브랜치 전환
+  const switchSibling = async (messageId: number, offset: number) => {
+    const siblings = await invoke<MessageRecord[]>("get_message_siblings", { messageId });
+    const index = siblings.findIndex((m) => m.id === messageId);
+    const target = siblings[index + offset];
+    if (!target) return;
+
+    const branch = await invoke<MessageRecord[]>("switch_chat_branch", { messageId: target.id });
+    setMessages(branch.map(toChatMessage));
+  };
+
+  // 대화를 지우는 대신 새 스레드를 시작 (이전 기록은 유지)
   const clearMessages = async () => {
-    setMessages([]);
     setStreamingContent("");
     if (problem?.id) {
-      await saveChat(problem.id, []);
+      await createThread(DEFAULT_THREAD_TITLE);
+    } else {
+      setMessages([]);
     }
   };
 
+  // 상태 관리 함수
+  const createThread = async (title: string) => {
+    if (!problem?.id) return;
+    const thread = await invoke<ChatRecord>("create_chat_thread", {
+      problemId: problem.id,
+      title,
+    });
+    setThreads((prev) => [thread, ...prev]);
+    await applyThread(thread);
+  };
+
+  const selectThread = async (id: number) => {
+    const thread = await invoke<ChatRecord | null>("get_chat_thread", { threadId: id });
+    if (thread) await applyThread(thread);
+  };
+
+  const renameThread = async (id: number, title: string) => {
+    await invoke("rename_chat_thread", { threadId: id, title });
+    setThreads((prev) => prev.map((t) => (t.id === id ? { ...t, title } : t)));
+  };
+
+  const deleteThread = async (id: number) => {
+    await invoke("delete_chat_thread", { threadId: id });
+    const remaining = threads.filter((t) => t.id !== id);
+    setThreads(remaining);
+    if (id === threadId) await applyThread(remaining[0] ?? null);
+  };
+
   return {
     messages,
+    threads,
+    threadId,
+    createThread,
+    selectThread,
+    renameThread,
+    deleteThread,
+    editMessage,
+    regenerate,
+    switchSibling,
     loading,
     sendMessage,
     clearMessages,
     streamingContent,
+    streamingThoughts,
   };
 }
@@ -0,0 +1,63 @@
+import { useState, useEffect, useRef } from "react";
+import { invoke } from "@tauri-apps/api/core";
+// 타입 정의
+import type { ComplexityCheck } from "../types";
+
+// 함수/상수 (제출 전에 코드의 복잡도를 시간 제한과 비교)
+export function useComplexityCheck(
+  problemId: string | null,
+  model: string,
+  code: string,
+  language: string
+) {
+  const [result, setResult] = useState<ComplexityCheck | null>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  // 응답이 도착했을 때 아직 같은 문제를 보고 있는지 확인하는 용도
+  const currentProblemIdRef = useRef(problemId);
+
+  // 상태 관리 함수 (문제가 바뀌면 이전 결과를 지우고 진행 중인 확인은 무시)
+  useEffect(() => {
+    currentProblemIdRef.current = problemId;
+    setResult(null);
+    setError(null);
+    setLoading(false);
+  }, [problemId]);
+
+  // 코드가 바뀌면 이전 코드에 대한 판정은 더 이상 맞지 않으므로 지움
+  useEffect(() => {
+    setResult(null);
+    setError(null);
+  }, [code, language]);
+
+  const checkComplexity = async () => {
+    if (!problemId || !code.trim()) return;
+    const requestedProblemId = problemId;
+    setLoading(true);
+    setError(null);
+    try {
+      const check = await invoke<ComplexityCheck>("check_complexity", {
+        model,
+        problemId: requestedProblemId,
+        code: { language, code },
+        options: null,
+      });
+      if (currentProblemIdRef.current !== requestedProblemId) return;
+      setResult(check);
+    } catch (e) {
+      if (currentProblemIdRef.current !== requestedProblemId) return;
+      setError(String(e));
+    } finally {
+      if (currentProblemIdRef.current === requestedProblemId) {
+        setLoading(false);
+      }
+    }
+  };
+
+  const dismiss = () => {
+    setResult(null);
+    setError(null);
+  };
+
+  return { result, loading, error, checkComplexity, dismiss };
+}
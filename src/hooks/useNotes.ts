@@ -0,0 +1,115 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+// 타입 정의
+import type { Note } from "../types";
+
+// 노트를 수정할 때 보내는 내용
+export interface NoteDraft {
+  key_idea: string;
+  algorithm: string;
+  complexity: string;
+  pitfalls: string[];
+}
+
+// 노트의 주의할 점 목록 꺼내기 (잘못된 JSON이면 빈 목록)
+export function notePitfalls(note: Note): string[] {
+  try {
+    const pitfalls = JSON.parse(note.pitfalls_json);
+    return Array.isArray(pitfalls) ? pitfalls.map(String) : [];
+  } catch {
+    return [];
+  }
+}
+
+// 함수/상수 (지금 문제의 풀이 노트 생성/수정)
+export function useNotes(problemId: string | null, model: string) {
+  const [note, setNote] = useState<Note | null>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  // 상태 관리 함수 (문제가 바뀌면 저장된 노트를 불러옴)
+  useEffect(() => {
+    setNote(null);
+    setError(null);
+    if (!problemId) return;
+    let cancelled = false;
+    invoke<Note | null>("get_note", { problemId })
+      .then((saved) => {
+        if (!cancelled) setNote(saved);
+      })
+      .catch((e) => {
+        if (!cancelled) setError(String(e));
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [problemId]);
+
+  // 코드가 비어 있으면 백엔드가 마지막으로 저장된 코드를 사용함
+  // 직접 수정한 노트는 overwrite를 넘겨야만 새로 작성한 노트로 바뀜
+  const generateNote = async (code: string, language: string, overwrite = false) => {
+    if (!problemId) return;
+    setLoading(true);
+    setError(null);
+    try {
+      const generated = await invoke<Note>("generate_note", {
+        model,
+        problemId,
+        code: code.trim() ? { language, code } : null,
+        overwrite,
+        options: null,
+      });
+      setNote(generated);
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const saveNote = async (draft: NoteDraft) => {
+    if (!note) return;
+    setError(null);
+    try {
+      const updated = await invoke<Note | null>("update_note", {
+        id: note.id,
+        keyIdea: draft.key_idea,
+        algorithm: draft.algorithm,
+        complexity: draft.complexity,
+        pitfalls: draft.pitfalls,
+      });
+      setNote(updated);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const deleteNote = async () => {
+    if (!note) return;
+    try {
+      await invoke("delete_note", { id: note.id });
+      setNote(null);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return { note, loading, error, generateNote, saveNote, deleteNote };
+}
+
+// 함수/상수 (모든 노트에서 검색, 빈 검색어는 전체 목록)
+export function useNoteSearch() {
+  const [results, setResults] = useState<Note[]>([]);
+  const [error, setError] = useState<string | null>(null);
+
+  const searchNotes = async (query: string) => {
+    setError(null);
+    try {
+      setResults(await invoke<Note[]>("search_notes", { query }));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return { results, error, searchNotes };
+}
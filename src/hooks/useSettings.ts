@@ -1,10 +1,13 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 
+// 예전 버전은 API 키를 localStorage에 평문으로 저장했음 (시작 시 백엔드로 옮기고 삭제)
+const LEGACY_API_KEY = "gemini_api_key";
+
 const STORAGE_KEYS = {
-  apiKey: "gemini_api_key",
   model: "gemini_model",
   customPrompt: "gemini_custom_prompt",
+  showThoughts: "gemini_show_thoughts",
 };
 
 const DEFAULT_PROMPT = `당신은 알고리즘 문제 해결을 돕는 튜터입니다.
@@ -29,39 +32,119 @@ export const FALLBACK_MODELS = [
 
 // 타입 정의
 export interface Settings {
-  apiKey: string;
+  hasApiKey: boolean; // 키 자체는 Rust 백엔드에 암호화되어 저장되고 웹뷰로 돌아오지 않음
   model: string;
   customPrompt: string;
+  showThoughts: boolean; // 생각하는 모델에게 생각 요약을 함께 요청해서 답변 위에 접어서 보여줌
+}
+
+// 타입 정의 (회사망처럼 프록시나 사설 인증서가 필요한 환경용, 모든 네트워크 요청에 적용됨)
+export interface NetworkSettings {
+  proxy_url: string | null;
+  ca_certificates: string[]; // 추가로 신뢰할 루트 인증서 PEM 파일 경로
+  connect_timeout_secs: number | null;
+  read_timeout_secs: number | null;
+  user_agent: string | null;
+}
+
+// 타입 정의 (저장된 네트워크 설정을 적용하지 못했으면 error에 이유가 있고 기본 설정으로 연결함)
+interface NetworkStatus {
+  settings: NetworkSettings;
+  error: string | null;
+}
+
+// 타입 정의 (키 목록에는 라벨과 쿨다운만 있고 키 값은 없음)
+export interface ApiKeyInfo {
+  label: string;
+  cooldown_secs: number;
+}
+
+interface ApiKeyStatus {
+  configured: boolean;
+  keys: ApiKeyInfo[];
+  load_error: string | null;
 }
 
 // 타입 정의
 export interface AvailableModel {
   id: string;
   name: string;
+  inputTokenLimit?: number;
+  thinking?: boolean;
+}
+
+interface GeminiModelInfo {
+  name: string;
+  display_name: string;
+  version: string;
+  input_token_limit: number;
+  output_token_limit: number;
+  supported_generation_methods: string[];
+  thinking: boolean;
 }
 
 // 함수/상수
 export function useSettings() {
   const [settings, setSettings] = useState<Settings>({
-    apiKey: "",
+    hasApiKey: false,
     model: "gemini-2.5-flash",
     customPrompt: DEFAULT_PROMPT,
+    showThoughts: false,
   });
+  const [apiKeys, setApiKeys] = useState<ApiKeyInfo[]>([]);
+  // 저장된 키 파일을 읽지 못했을 때의 오류 (키를 다시 입력해야 함)
+  const [apiKeyLoadError, setApiKeyLoadError] = useState<string | null>(null);
+  const [networkSettings, setNetworkSettings] = useState<NetworkSettings | null>(null);
+  const [networkStatusError, setNetworkStatusError] = useState<string | null>(null);
   const [models, setModels] = useState<AvailableModel[]>(FALLBACK_MODELS);
   const [loadingModels, setLoadingModels] = useState(false);
   const [modelLoadError, setModelLoadError] = useState(false);
 
   // 상태 관리 함수
   useEffect(() => {
-    setSettings({
-      apiKey: localStorage.getItem(STORAGE_KEYS.apiKey) ?? "",
-      model: localStorage.getItem(STORAGE_KEYS.model) ?? "gemini-2.5-flash",
-      customPrompt: localStorage.getItem(STORAGE_KEYS.customPrompt) ?? DEFAULT_PROMPT,
-    });
+    const load = async () => {
+      const legacyKey = localStorage.getItem(LEGACY_API_KEY);
+      if (legacyKey) {
+        try {
+          await invoke("set_api_key", { apiKey: legacyKey });
+          localStorage.removeItem(LEGACY_API_KEY);
+        } catch (error) {
+          console.error("Failed to migrate API key:", error);
+        }
+      }
+
+      let hasApiKey = false;
+      try {
+        const status = await invoke<ApiKeyStatus>("get_api_key_status");
+        hasApiKey = status.configured;
+        setApiKeys(status.keys);
+        setApiKeyLoadError(status.load_error);
+      } catch (error) {
+        console.error("Failed to read API key status:", error);
+      }
+
+      try {
+        const status = await invoke<NetworkStatus>("get_network_settings");
+        setNetworkSettings(status.settings);
+        setNetworkStatusError(status.error);
+      } catch (error) {
+        console.error("Failed to read network settings:", error);
+      }
+
+      setSettings({
+        hasApiKey,
+        model: localStorage.getItem(STORAGE_KEYS.model) ?? "gemini-2.5-flash",
+        customPrompt: localStorage.getItem(STORAGE_KEYS.customPrompt) ?? DEFAULT_PROMPT,
+        showThoughts: localStorage.getItem(STORAGE_KEYS.showThoughts) === "true",
+      });
+    };
+    load();
   }, []);
 
-  const fetchModels = async (apiKey: string) => {
-    if (!apiKey) {
+  // apiKey를 넘기면 저장 전의 새 키로, 없으면 저장된 키로 모델 목록을 가져옴
+  // 저장된 키의 목록은 백엔드가 디스크에 캐시하므로 refresh일 때만 다시 받아옴
+  const fetchModels = async (apiKey?: string, refresh = false) => {
+    if (!apiKey && !settings.hasApiKey) {
       setModels(FALLBACK_MODELS);
       setModelLoadError(false);
       return;
@@ -70,13 +153,15 @@ export function useSettings() {
     setLoadingModels(true);
     setModelLoadError(false);
     try {
-      const response = await invoke<Array<{ name: string; display_name: string }>>(
-        "get_available_models",
-        { apiKey }
-      );
+      const response = await invoke<GeminiModelInfo[]>("get_available_models", {
+        apiKey: apiKey || null,
+        refresh,
+      });
       const mappedModels = response.map((m) => ({
         id: m.name,
         name: m.display_name,
+        inputTokenLimit: m.input_token_limit,
+        thinking: m.thinking,
       }));
       setModels(mappedModels);
       setModelLoadError(false);
@@ -89,18 +174,82 @@ export function useSettings() {
     }
   };
 
-  const saveSettings = (newSettings: Settings) => {
-    setSettings(newSettings);
-    localStorage.setItem(STORAGE_KEYS.apiKey, newSettings.apiKey);
+  // 새 API 키는 백엔드에만 넘기고 프론트엔드에는 보관하지 않음
+  // 같은 라벨의 키는 교체되고, 다른 라벨이면 키 풀에 추가되어 번갈아 사용됨
+  const saveSettings = async (
+    newSettings: Omit<Settings, "hasApiKey">,
+    apiKey?: string,
+    label?: string
+  ) => {
+    let hasApiKey = settings.hasApiKey;
+    if (apiKey) {
+      try {
+        const status = await invoke<ApiKeyStatus>("set_api_key", { apiKey, label: label || null });
+        hasApiKey = status.configured;
+        setApiKeys(status.keys);
+      } catch (error) {
+        console.error("Failed to save API key:", error);
+      }
+    }
+    setSettings({ ...newSettings, hasApiKey });
     localStorage.setItem(STORAGE_KEYS.model, newSettings.model);
     localStorage.setItem(STORAGE_KEYS.customPrompt, newSettings.customPrompt);
+    localStorage.setItem(STORAGE_KEYS.showThoughts, String(newSettings.showThoughts));
   };
 
-  const clearSettings = () => {
-    const cleared = { apiKey: "", model: "gemini-2.5-flash", customPrompt: DEFAULT_PROMPT };
-    setSettings(cleared);
+  // 잘못된 프록시 주소나 인증서면 기존 설정을 유지하고 오류 메시지를 돌려줌
+  const saveNetworkSettings = async (newSettings: NetworkSettings): Promise<string | null> => {
+    try {
+      const status = await invoke<NetworkStatus>("set_network_settings", { settings: newSettings });
+      setNetworkSettings(status.settings);
+      setNetworkStatusError(status.error);
+      return null;
+    } catch (error) {
+      console.error("Failed to save network settings:", error);
+      return String(error);
+    }
+  };
+
+  const removeApiKey = async (label: string) => {
+    try {
+      const status = await invoke<ApiKeyStatus>("remove_api_key", { label });
+      setApiKeys(status.keys);
+      setSettings((prev) => ({ ...prev, hasApiKey: status.configured }));
+    } catch (error) {
+      console.error("Failed to remove API key:", error);
+    }
+  };
+
+  const clearSettings = async () => {
+    try {
+      await invoke("clear_api_key");
+    } catch (error) {
+      console.error("Failed to clear API key:", error);
+    }
+    setApiKeys([]);
+    setSettings({
+      hasApiKey: false,
+      model: "gemini-2.5-flash",
+      customPrompt: DEFAULT_PROMPT,
+      showThoughts: false,
+    });
     Object.values(STORAGE_KEYS).forEach((key) => localStorage.removeItem(key));
   };
 
-  return { settings, models, loadingModels, modelLoadError, saveSettings, clearSettings, fetchModels, DEFAULT_PROMPT };
+  return {
+    settings,
+    apiKeys,
+    apiKeyLoadError,
+    networkSettings,
+    networkStatusError,
+    models,
+    loadingModels,
+    modelLoadError,
+    saveSettings,
+    removeApiKey,
+    saveNetworkSettings,
+    clearSettings,
+    fetchModels,
+    DEFAULT_PROMPT,
+  };
 }
@@ -0,0 +1,32 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+// 타입 정의
+import type { SimilarProblem } from "../types";
+
+// 함수/상수 (임베딩이 없는 문제는 검색할 때 백엔드가 계산해서 저장함)
+export function useSimilarProblems(problemId: string | null) {
+  const [similar, setSimilar] = useState<SimilarProblem[] | null>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  // 상태 관리 함수 (문제가 바뀌면 이전 결과를 지움)
+  useEffect(() => {
+    setSimilar(null);
+    setError(null);
+  }, [problemId]);
+
+  const findSimilar = async () => {
+    if (!problemId) return;
+    setLoading(true);
+    setError(null);
+    try {
+      setSimilar(await invoke<SimilarProblem[]>("find_similar_problems", { problemId }));
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return { similar, loading, error, findSimilar };
+}
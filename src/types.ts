@@ -10,16 +10,71 @@ export interface Problem {
   memory_limit: string; // 메모리 제한
 }
 
+// 저장된 문제 중 지금 문제와 비슷한 문제 (임베딩 코사인 유사도 순)
+export interface SimilarProblem {
+  problem_id: string;
+  title: string;
+  score: number; // 1에 가까울수록 비슷함
+}
+
+// 문제를 푼 뒤 AI가 정리해 준 풀이 노트 (사용자가 수정 가능)
+export interface Note {
+  id: number;
+  problem_id: string;
+  title: string; // 문제 제목
+  key_idea: string; // 핵심 아이디어
+  algorithm: string; // 알고리즘 설명
+  complexity: string; // 시간/공간 복잡도
+  pitfalls_json: string; // 주의할 점 목록 (JSON 문자열 배열)
+  code_version_id: number | null; // 노트를 쓸 때 사용한 코드
+  model: string;
+  edited: boolean; // 사용자가 직접 수정했는지
+  created_at: string;
+  updated_at: string;
+}
+
+// 문제에서 읽어 낸 변수의 최댓값 (예: N ≤ 100000)
+export interface Constraint {
+  name: string;
+  max: number;
+}
+
+// 코드의 복잡도를 시간 제한과 비교한 결과
+export interface ComplexityCheck {
+  time_complexity: string; // 예: "O(N^2)"
+  space_complexity: string;
+  operations: string; // 연산 수 식 (예: "N^2")
+  explanation: string;
+  constraints: Constraint[];
+  time_limit_secs: number | null;
+  estimated_operations: number | null; // 최대 입력에서의 예상 연산 수
+  estimated_by_model: boolean; // 식을 계산하지 못해 AI가 추정한 값을 썼는지
+  operations_budget: number | null; // 시간 제한 안에 가능한 연산 수
+  verdict: "ok" | "tight" | "too_slow" | "unknown";
+}
+
 // 입출력 예제 한 개
 export interface Sample {
   input: string; // 입력 예제
   output: string; // 출력 예제
 }
 
+// AI가 답변 중 실행한 도구 호출 하나
+export interface ToolInvocation {
+  name: string; // 도구 이름
+  args: unknown; // 호출 인자
+  result: unknown; // 실행 결과
+}
+
 // AI와의 대화 메시지 하나
 export interface ChatMessage {
+  id?: number; // 저장된 메시지 ID (저장 전에는 없음)
   role: "user" | "assistant"; // "user"는 사용자 메시지, "assistant"는 AI 메시지
   content: string; // 메시지 내용
+  thoughts?: string; // AI가 답하기 전에 한 생각의 요약 (요청했을 때만 있음)
+  tool_calls?: ToolInvocation[]; // AI가 답변 전에 실행한 도구 호출들
+  sibling_index?: number; // 같은 위치의 다른 버전들 중 몇 번째인지 (0부터)
+  sibling_count?: number; // 같은 위치에 있는 버전 수 (수정/재생성으로 늘어남)
 }
 
 // 데이터베이스에 저장된 문제 정보